@@ -0,0 +1,427 @@
+// Block based ADPCM decoders for WAVE format tags 2 (Microsoft ADPCM) and 0x11
+// (IMA/DVI ADPCM). Both encode 4 bits per sample and restart the predictor at
+// the beginning of every block of block_align bytes, which carries the initial
+// state for each channel in a small header.
+//
+// https://wiki.multimedia.cx/index.php/IMA_ADPCM
+// https://wiki.multimedia.cx/index.php/Microsoft_ADPCM
+
+const IMA_INDEX_TABLE: [i8; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const MS_ADAPTATION_TABLE: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+
+// The highest delta, which keeps the adapted delta below i32::MAX as in ffmpeg.
+const MS_MAX_DELTA: i32 = i32::MAX / 768;
+
+// The coefficient pairs every MS ADPCM file has to start with.
+pub const MS_STANDARD_COEFFICIENTS: [(i16, i16); 7] = [
+    (256, 0),
+    (512, -256),
+    (0, 0),
+    (192, 64),
+    (240, 0),
+    (460, -208),
+    (392, -232),
+];
+
+struct ImaChannel {
+    predictor: i32,
+    step_index: i32,
+}
+
+impl ImaChannel {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = IMA_STEP_TABLE[self.step_index as usize];
+
+        let mut diff = step >> 3;
+        if nibble & 1 != 0 {
+            diff += step >> 2;
+        }
+        if nibble & 2 != 0 {
+            diff += step >> 1;
+        }
+        if nibble & 4 != 0 {
+            diff += step;
+        }
+        if nibble & 8 != 0 {
+            diff = -diff;
+        }
+
+        self.predictor = (self.predictor + diff).clamp(i16::MIN as i32, i16::MAX as i32);
+        self.step_index = (self.step_index + IMA_INDEX_TABLE[nibble as usize] as i32).clamp(0, 88);
+
+        self.predictor as i16
+    }
+}
+
+struct MsChannel {
+    coefficients: (i32, i32),
+    delta: i32,
+    sample1: i32,
+    sample2: i32,
+}
+
+impl MsChannel {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        // the products of crafted blocks exceed 32 bits
+        let predictor = (self.sample1 as i64 * self.coefficients.0 as i64
+            + self.sample2 as i64 * self.coefficients.1 as i64)
+            >> 8;
+        let signed_nibble = ((nibble << 4) as i8 >> 4) as i64;
+
+        let sample = (predictor + signed_nibble * self.delta as i64)
+            .clamp(i16::MIN as i64, i16::MAX as i64) as i32;
+
+        self.sample2 = self.sample1;
+        self.sample1 = sample;
+        self.delta = ((MS_ADAPTATION_TABLE[nibble as usize] as i64 * self.delta as i64) >> 8)
+            .clamp(16, MS_MAX_DELTA as i64) as i32;
+
+        sample as i16
+    }
+}
+
+// Decodes interleaved IMA ADPCM blocks into interleaved 16 bit samples. Each
+// channel starts with a 4 byte header (initial predictor and step index),
+// followed by groups of 4 bytes (8 samples) per channel in turn. Blocks are cut
+// to samples_per_block frames, unless it is 0.
+pub fn decode_ima_adpcm(
+    data: &[u8],
+    num_channels: usize,
+    block_align: usize,
+    samples_per_block: usize,
+) -> Vec<i16> {
+    let mut result = Vec::new();
+
+    if num_channels == 0 || block_align < 4 * num_channels {
+        return result;
+    }
+
+    for block in data.chunks(block_align) {
+        if block.len() < 4 * num_channels {
+            break;
+        }
+
+        let mut channels: Vec<ImaChannel> = block[..4 * num_channels]
+            .chunks_exact(4)
+            .map(|header| ImaChannel {
+                predictor: i16::from_le_bytes([header[0], header[1]]) as i32,
+                step_index: (header[2] as i32).clamp(0, 88),
+            })
+            .collect();
+
+        let groups = (block.len() - 4 * num_channels) / (4 * num_channels);
+        let start = result.len();
+
+        result.resize(start + (1 + groups * 8) * num_channels, 0);
+
+        for (channel, state) in channels.iter().enumerate() {
+            result[start + channel] = state.predictor as i16;
+        }
+
+        let mut offset = 4 * num_channels;
+
+        for group in 0..groups {
+            for (channel, state) in channels.iter_mut().enumerate() {
+                for (i, byte) in block[offset..offset + 4].iter().enumerate() {
+                    let frame = 1 + group * 8 + i * 2;
+                    result[start + frame * num_channels + channel] = state.decode(byte & 0x0f);
+                    result[start + (frame + 1) * num_channels + channel] = state.decode(byte >> 4);
+                }
+                offset += 4;
+            }
+        }
+
+        truncate_block(&mut result, start, num_channels, samples_per_block);
+    }
+
+    result
+}
+
+// Decodes interleaved Microsoft ADPCM blocks into interleaved 16 bit samples.
+// The block header holds the coefficient index, initial delta and the first two
+// samples of every channel, followed by nibbles (high nibble first) which
+// alternate between channels. Blocks are cut to samples_per_block frames, unless
+// it is 0.
+pub fn decode_ms_adpcm(
+    data: &[u8],
+    num_channels: usize,
+    block_align: usize,
+    samples_per_block: usize,
+    coefficients: &[(i16, i16)],
+) -> Vec<i16> {
+    let mut result = Vec::new();
+    let header_size = 7 * num_channels;
+
+    if num_channels == 0 || block_align < header_size {
+        return result;
+    }
+
+    for block in data.chunks(block_align) {
+        if block.len() < header_size {
+            break;
+        }
+
+        let start = result.len();
+        let read_i16 = |index: usize| i16::from_le_bytes([block[index], block[index + 1]]) as i32;
+
+        let mut channels: Vec<MsChannel> = (0..num_channels)
+            .map(|channel| {
+                let (c1, c2) = coefficients
+                    .get(block[channel] as usize)
+                    .copied()
+                    .unwrap_or((0, 0));
+
+                MsChannel {
+                    coefficients: (c1 as i32, c2 as i32),
+                    delta: read_i16(num_channels + channel * 2),
+                    sample1: read_i16(3 * num_channels + channel * 2),
+                    sample2: read_i16(5 * num_channels + channel * 2),
+                }
+            })
+            .collect();
+
+        for state in channels.iter() {
+            result.push(state.sample2 as i16);
+        }
+        for state in channels.iter() {
+            result.push(state.sample1 as i16);
+        }
+
+        let mut channel = 0;
+
+        for byte in &block[header_size..] {
+            for nibble in [byte >> 4, byte & 0x0f] {
+                result.push(channels[channel].decode(nibble));
+                channel = (channel + 1) % num_channels;
+            }
+        }
+
+        // an odd channel count can leave the last frame of a block incomplete
+        result.truncate(result.len() - (result.len() - start) % num_channels);
+        truncate_block(&mut result, start, num_channels, samples_per_block);
+    }
+
+    result
+}
+
+fn truncate_block(
+    result: &mut Vec<i16>,
+    start: usize,
+    num_channels: usize,
+    samples_per_block: usize,
+) {
+    if samples_per_block > 0 {
+        result.truncate(result.len().min(start + samples_per_block * num_channels));
+    }
+}
+
+pub fn i16_vec_to_f32_vec(samples: &[i16]) -> Vec<f32> {
+    samples
+        .iter()
+        .map(|sample| *sample as f32 * (1.0 / 32768.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        decode_ima_adpcm, decode_ms_adpcm, ImaChannel, MsChannel, IMA_STEP_TABLE,
+        MS_STANDARD_COEFFICIENTS,
+    };
+
+    #[test]
+    fn test_decode_ima_adpcm_mono_block() {
+        let block = [0x10, 0x00, 0x00, 0x00, 0x77, 0x00, 0x88, 0x08];
+
+        let result = decode_ima_adpcm(&block, 1, 8, 9);
+
+        // the header sample followed by 8 samples from the 4 data bytes
+        assert_eq!(result, vec![16, 27, 57, 61, 64, 61, 58, 56, 58]);
+    }
+
+    #[test]
+    fn test_decode_ima_adpcm_stereo_interleaving() {
+        let mut block = vec![0x64, 0x00, 0x00, 0x00, 0x9c, 0xff, 0x00, 0x00];
+        block.extend_from_slice(&[0x00; 8]);
+
+        let result = decode_ima_adpcm(&block, 2, 16, 9);
+
+        assert_eq!(result.len(), 18);
+        assert_eq!(result[0], 100);
+        assert_eq!(result[1], -100);
+        // a zero nibble adds step >> 3 with step 7 at index 0
+        assert_eq!(result[2], 100);
+        assert_eq!(result[3], -100);
+    }
+
+    #[test]
+    fn test_decode_ima_adpcm_round_trip() {
+        let samples = sine_wave_i16(2 * 505 + 17);
+        let encoded = encode_ima_adpcm(&samples, 256);
+
+        let result = decode_ima_adpcm(&encoded, 1, 256, 505);
+
+        assert!(result.len() >= samples.len());
+        assert_close(&result[..samples.len()], &samples, 1500);
+    }
+
+    #[test]
+    fn test_decode_ima_adpcm_samples_per_block() {
+        let block = [0x10, 0x00, 0x00, 0x00, 0x77, 0x00, 0x88, 0x08];
+
+        let result = decode_ima_adpcm(&block, 1, 8, 5);
+
+        assert_eq!(result, vec![16, 27, 57, 61, 64]);
+    }
+
+    #[test]
+    fn test_decode_ms_adpcm_mono_block() {
+        let block = [
+            0x00, // coefficient index
+            0x10, 0x00, // delta
+            0x64, 0x00, // sample1
+            0x32, 0x00, // sample2
+            0x10, 0xf0,
+        ];
+
+        let result = decode_ms_adpcm(&block, 1, 9, 6, &MS_STANDARD_COEFFICIENTS);
+
+        assert_eq!(result, vec![50, 100, 116, 116, 100, 100]);
+    }
+
+    #[test]
+    fn test_decode_ms_adpcm_maximal_delta() {
+        let mut block = vec![
+            0x00, // coefficient index
+            0xff, 0x7f, // delta
+            0x00, 0x00, // sample1
+            0x00, 0x00, // sample2
+        ];
+        // -8 triples the delta with every sample
+        block.extend_from_slice(&[0x88; 32]);
+
+        let result = decode_ms_adpcm(&block, 1, block.len(), 0, &MS_STANDARD_COEFFICIENTS);
+
+        assert_eq!(result.len(), 66);
+        assert!(result[2..].iter().all(|sample| *sample == i16::MIN));
+    }
+
+    #[test]
+    fn test_decode_ms_adpcm_round_trip() {
+        let samples = sine_wave_i16(3 * 500 + 2);
+        let encoded = encode_ms_adpcm(&samples, 256);
+
+        let result = decode_ms_adpcm(&encoded, 1, 256, 500, &MS_STANDARD_COEFFICIENTS);
+
+        assert!(result.len() >= samples.len());
+        assert_close(&result[..samples.len()], &samples, 1500);
+    }
+
+    fn sine_wave_i16(length: usize) -> Vec<i16> {
+        (0..length)
+            .map(|i| ((i as f32 * 0.05).sin() * 20000.0) as i16)
+            .collect()
+    }
+
+    fn assert_close(actual: &[i16], expected: &[i16], tolerance: i32) {
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!(
+                (*a as i32 - *e as i32).abs() <= tolerance,
+                "sample {}: {} is not close to {}",
+                i,
+                a,
+                e
+            );
+        }
+    }
+
+    // Straightforward mono encoders, using the decoder state to stay in sync.
+    fn encode_ima_adpcm(samples: &[i16], block_align: usize) -> Vec<u8> {
+        let samples_per_block = (block_align - 4) * 2 + 1;
+        let mut result = Vec::new();
+        let mut step_index = 48;
+
+        for block in samples.chunks(samples_per_block) {
+            let mut state = ImaChannel {
+                predictor: block[0] as i32,
+                step_index,
+            };
+
+            result.extend_from_slice(&block[0].to_le_bytes());
+            result.extend_from_slice(&[step_index as u8, 0]);
+
+            let mut nibbles: Vec<u8> = block[1..]
+                .iter()
+                .map(|sample| {
+                    let step = IMA_STEP_TABLE[state.step_index as usize];
+                    let diff = *sample as i32 - state.predictor;
+                    let magnitude = ((diff.abs() * 4) / step).min(7) as u8;
+                    let nibble = if diff < 0 { magnitude | 8 } else { magnitude };
+                    state.decode(nibble);
+                    nibble
+                })
+                .collect();
+
+            nibbles.resize(samples_per_block - 1, 0);
+            step_index = state.step_index;
+
+            for pair in nibbles.chunks(2) {
+                result.push(pair[0] | (pair[1] << 4));
+            }
+        }
+
+        result
+    }
+
+    fn encode_ms_adpcm(samples: &[i16], block_align: usize) -> Vec<u8> {
+        let samples_per_block = (block_align - 7) * 2 + 2;
+        let mut result = Vec::new();
+
+        for block in samples.chunks(samples_per_block) {
+            let sample2 = block[0];
+            let sample1 = *block.get(1).unwrap_or(&0);
+            let mut state = MsChannel {
+                coefficients: (512, -256),
+                delta: 16,
+                sample1: sample1 as i32,
+                sample2: sample2 as i32,
+            };
+
+            result.push(1);
+            result.extend_from_slice(&16i16.to_le_bytes());
+            result.extend_from_slice(&sample1.to_le_bytes());
+            result.extend_from_slice(&sample2.to_le_bytes());
+
+            let mut nibbles: Vec<u8> = block
+                .iter()
+                .skip(2)
+                .map(|sample| {
+                    let predictor = (state.sample1 * 512 - state.sample2 * 256) >> 8;
+                    let nibble =
+                        ((*sample as i32 - predictor) / state.delta).clamp(-8, 7) as u8 & 0x0f;
+                    state.decode(nibble);
+                    nibble
+                })
+                .collect();
+
+            nibbles.resize(samples_per_block - 2, 0);
+
+            for pair in nibbles.chunks(2) {
+                result.push((pair[0] << 4) | pair[1]);
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,434 @@
+use crate::audio::cue::Markers;
+use crate::audio::riff_info::{InfoTag, RiffInfo};
+use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveError, RiffWaveFormat};
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::io::Read;
+
+// AIFF 1.3 and the AIFF-C draft of 1991.
+// https://www.loc.gov/preservation/digital/formats/fdd/fdd000005.shtml
+#[derive(PartialEq, Debug, Clone)]
+pub enum AiffError {
+    // The stream is not a FORM container of AIFF or AIFF-C data.
+    NotAiff,
+    // A chunk is cut off at the end of the stream.
+    Truncated { offset: usize },
+    // A chunk required to read the audio data (COMM or SSND) is missing.
+    MissingChunk(String),
+    // An AIFF-C compression type other than NONE, sowt, fl32, fl64, ulaw and
+    // alaw.
+    UnsupportedCompression(String),
+    UnsupportedChannels(i16),
+    UnsupportedBitsPerSample(i16),
+    // A sample rate below 1 Hz, not a number or too high to express the byte
+    // rate of the wave in 32 bits.
+    UnsupportedSampleRate(f64),
+    // Reading from the underlying stream failed.
+    Io(io::ErrorKind),
+}
+
+impl Display for AiffError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AiffError::NotAiff => write!(f, "Not an AIFF or AIFF-C file"),
+            AiffError::Truncated { offset } => {
+                write!(f, "AIFF file truncated at offset {}", offset)
+            }
+            AiffError::MissingChunk(chunk_id) => write!(f, "Missing '{}' chunk", chunk_id),
+            AiffError::UnsupportedCompression(compression_type) => {
+                write!(f, "Unsupported AIFF-C compression: {}", compression_type)
+            }
+            AiffError::UnsupportedChannels(num_channels) => {
+                write!(f, "Unsupported number of channels: {}", num_channels)
+            }
+            AiffError::UnsupportedBitsPerSample(bits_per_sample) => {
+                write!(
+                    f,
+                    "Unsupported bits per sample for AIFF: {}",
+                    bits_per_sample
+                )
+            }
+            AiffError::UnsupportedSampleRate(sample_rate) => {
+                write!(f, "Unsupported sample rate: {}", sample_rate)
+            }
+            AiffError::Io(kind) => write!(f, "Error reading audio: {}", kind),
+        }
+    }
+}
+
+impl Error for AiffError {}
+
+impl From<io::Error> for AiffError {
+    fn from(error: io::Error) -> Self {
+        AiffError::Io(error.kind())
+    }
+}
+
+// The common chunk, describing the sample data.
+struct AiffCommon {
+    format: RiffWaveFormat,
+    num_frames: u32,
+    // Samples of the sowt compression are stored little-endian.
+    little_endian: bool,
+}
+
+impl AiffCommon {
+    fn new(body: &[u8], is_aifc: bool, offset: usize) -> Result<Self, AiffError> {
+        let truncated = AiffError::Truncated { offset };
+        let fields = body.get(0..18).ok_or(truncated.clone())?;
+
+        let num_channels = i16::from_be_bytes([fields[0], fields[1]]);
+        let num_frames = u32::from_be_bytes(fields[2..6].try_into().unwrap());
+        let sample_size = i16::from_be_bytes([fields[6], fields[7]]);
+        let sample_rate = extended_to_f64(fields[8..18].try_into().unwrap());
+
+        let compression_type = match is_aifc {
+            true => body.get(18..22).ok_or(truncated)?,
+            false => b"NONE",
+        };
+
+        if num_channels < 1 {
+            return Err(AiffError::UnsupportedChannels(num_channels));
+        }
+        // also false for NaN
+        if !(1.0..=u32::MAX as f64).contains(&sample_rate.round()) {
+            return Err(AiffError::UnsupportedSampleRate(sample_rate));
+        }
+
+        let (audio_format, bits_per_sample, little_endian) = match compression_type {
+            // QuickTime writes twos for uncompressed big-endian samples as well
+            b"NONE" | b"twos" | b"sowt" => match sample_size {
+                // samples are stored left aligned in whole bytes like valid bits
+                // in a wave container
+                1..=32 => (
+                    AudioFormat::Pcm,
+                    (sample_size as u16).div_ceil(8) * 8,
+                    compression_type == b"sowt" && sample_size > 8,
+                ),
+                _ => return Err(AiffError::UnsupportedBitsPerSample(sample_size)),
+            },
+            b"fl32" | b"FL32" => (AudioFormat::IeeeFloat, 32, false),
+            b"fl64" | b"FL64" => (AudioFormat::IeeeFloat, 64, false),
+            // the sample size of the compressed formats is that of the
+            // decoded samples, i.e. 16
+            b"ulaw" | b"ULAW" => (AudioFormat::MuLaw, 8, false),
+            b"alaw" | b"ALAW" => (AudioFormat::ALaw, 8, false),
+            _ => {
+                return Err(AiffError::UnsupportedCompression(
+                    String::from_utf8_lossy(compression_type).to_string(),
+                ))
+            }
+        };
+
+        let format = RiffWaveFormat::uncompressed(
+            audio_format,
+            Channels::new(num_channels as u16),
+            sample_rate.round() as u32,
+            bits_per_sample,
+        )
+        .map_err(|error| match error {
+            RiffWaveError::UnsupportedChannels(_) => AiffError::UnsupportedChannels(num_channels),
+            _ => AiffError::UnsupportedSampleRate(sample_rate),
+        })?;
+
+        Ok(AiffCommon {
+            format,
+            num_frames,
+            little_endian,
+        })
+    }
+}
+
+// Decodes a complete AIFF or AIFF-C file into a wave of the same sample format.
+// The name, author, copyright and annotation chunks become its LIST/INFO
+// metadata.
+pub fn decode<R: Read>(mut reader: R) -> Result<RiffWave, AiffError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let is_aifc = match bytes.get(0..12) {
+        Some([b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F']) => false,
+        Some([b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'C']) => true,
+        _ => return Err(AiffError::NotAiff),
+    };
+
+    // the FORM chunk may be followed by padding or garbage
+    let form_size = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let end = bytes.len().min(form_size.saturating_add(8));
+
+    let mut common = None;
+    let mut sound_data: Option<&[u8]> = None;
+    let mut info = RiffInfo::default();
+    let mut position = 12;
+
+    while position + 8 <= end {
+        let chunk_id = &bytes[position..position + 4];
+        let size = u32::from_be_bytes(bytes[position + 4..position + 8].try_into().unwrap());
+        let body = bytes
+            .get(position + 8..position + 8 + size as usize)
+            .ok_or(AiffError::Truncated { offset: position })?;
+
+        match chunk_id {
+            b"COMM" => common = Some(AiffCommon::new(body, is_aifc, position)?),
+            b"SSND" => {
+                // the samples start after the offset and block size fields and
+                // the number of bytes given by the offset
+                let data_offset = body
+                    .get(0..4)
+                    .map(|offset| u32::from_be_bytes(offset.try_into().unwrap()) as usize)
+                    .ok_or(AiffError::Truncated { offset: position })?;
+                sound_data = Some(
+                    body.get(8 + data_offset..)
+                        .ok_or(AiffError::Truncated { offset: position })?,
+                );
+            }
+            b"NAME" => info.set(InfoTag::Title, text(body)),
+            b"AUTH" => info.set(InfoTag::Artist, text(body)),
+            b"(c) " => info.set(InfoTag::Copyright, text(body)),
+            b"ANNO" => info.set(InfoTag::Comment, text(body)),
+            _ => {}
+        }
+
+        // chunks are padded to an even size
+        position += 8 + size as usize + size as usize % 2;
+    }
+
+    let common = common.ok_or(AiffError::MissingChunk("COMM".to_string()))?;
+    let sound_data = match sound_data {
+        Some(sound_data) => sound_data,
+        None if common.num_frames == 0 => &[],
+        None => return Err(AiffError::MissingChunk("SSND".to_string())),
+    };
+
+    let length = sound_data
+        .len()
+        .min(common.num_frames as usize * common.format.block_align as usize);
+    let sound_data = &sound_data[..length - length % common.format.block_align as usize];
+
+    let data = match common.little_endian {
+        true => sound_data.to_vec(),
+        false => common.format.data_from_big_endian(sound_data),
+    };
+
+    let mut riff_wave = RiffWave {
+        size: 0,
+        format: common.format,
+        data,
+        info,
+        bext: None,
+        markers: Markers::default(),
+        warnings: Vec::new(),
+    };
+    riff_wave.size = riff_wave.riff_size();
+
+    Ok(riff_wave)
+}
+
+// The sample rate is stored as an 80 bit IEEE 754 extended precision number:
+// a sign bit, a 15 bit exponent and a 64 bit mantissa with an explicit integer
+// bit.
+fn extended_to_f64(bytes: &[u8; 10]) -> f64 {
+    let exponent = (u16::from_be_bytes([bytes[0], bytes[1]]) & 0x7FFF) as i32;
+    let mantissa = u64::from_be_bytes(bytes[2..10].try_into().unwrap());
+
+    if exponent == 0 && mantissa == 0 {
+        return 0.0;
+    }
+
+    let value = mantissa as f64 * 2f64.powi(exponent - 16383 - 63);
+
+    match bytes[0] & 0x80 {
+        0 => value,
+        _ => -value,
+    }
+}
+
+// The text chunks hold plain ASCII, some writers terminate it with a zero.
+fn text(body: &[u8]) -> String {
+    String::from_utf8_lossy(body)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::aiff::{decode, extended_to_f64, AiffError};
+    use crate::audio::g711;
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{AudioFormat, Channels, RiffWaveReader};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    const AIFF: &str = "./test_data/audio/aiff/pcm_s16be_8k_mono.aiff";
+
+    // The first 8000 samples of the wave, which all AIFF files are made of.
+    fn wave_samples() -> Vec<f32> {
+        RiffWaveReader::new(
+            File::open("./test_data/audio/riff_wave/pcm_s16le_8k_mono.wav").unwrap(),
+        )
+        .and_then(RiffWaveReader::read_wave)
+        .unwrap()
+        .data_as_f32()[..8000]
+            .to_vec()
+    }
+
+    #[test]
+    fn test_decode_aiff() {
+        let testee = decode(File::open(AIFF).unwrap()).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(testee.format.num_channels, Channels::Mono);
+        assert_eq!(testee.format.sample_rate, 8000);
+        assert_eq!(testee.format.bits_per_sample, 16);
+        assert_eq!(testee.frames(), 8000);
+        assert_eq!(testee.data_as_f32(), wave_samples());
+        assert_eq!(testee.info.get(InfoTag::Title), Some("Mars and the Moon"));
+        assert_eq!(testee.info.get(InfoTag::Artist), Some("gwaggli"));
+    }
+
+    #[test]
+    fn test_decode_aiff_stereo_24bit() {
+        // left is the wave shifted to 24 bit, right is its inverted half
+        let testee =
+            decode(File::open("./test_data/audio/aiff/pcm_s24be_8k_stereo.aiff").unwrap()).unwrap();
+
+        assert_eq!(testee.format.num_channels, Channels::Stereo);
+        assert_eq!(testee.format.bits_per_sample, 24);
+        assert_eq!(testee.frames(), 4000);
+
+        let channels = testee.channels_as_f32();
+        assert_eq!(channels[0], wave_samples()[..4000]);
+        assert!(channels[0]
+            .iter()
+            .zip(&channels[1])
+            .all(|(left, right)| *right == -left / 2.0));
+    }
+
+    #[test]
+    fn test_decode_aifc() {
+        let sowt =
+            decode(File::open("./test_data/audio/aiff/pcm_s16le_8k_mono.aifc").unwrap()).unwrap();
+        assert_eq!(sowt.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(sowt.data_as_f32(), wave_samples());
+
+        let fl32 =
+            decode(File::open("./test_data/audio/aiff/f32be_8k_mono.aifc").unwrap()).unwrap();
+        assert_eq!(fl32.format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(fl32.format.bits_per_sample, 32);
+        assert_eq!(fl32.data_as_f32(), wave_samples());
+
+        // the sound data chunk precedes the common chunk
+        let ulaw =
+            decode(File::open("./test_data/audio/aiff/mulaw_8k_mono.aifc").unwrap()).unwrap();
+        assert_eq!(ulaw.format.audio_format, AudioFormat::MuLaw);
+        assert_eq!(ulaw.format.bits_per_sample, 8);
+        assert_eq!(ulaw.frames(), 8000);
+        assert_eq!(
+            ulaw.data_as_f32(),
+            wave_samples()
+                .iter()
+                .map(|sample| {
+                    let sample = (sample * 32768.0) as i16;
+                    g711::mulaw_to_i16(g711::i16_to_mulaw(sample)) as f32 / 32768.0
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_decode_8bit() {
+        // 8 bit samples are signed, unlike in wave files
+        let mut bytes = b"FORM\0\0\0\x32AIFFCOMM\0\0\0\x12\0\x01\0\0\0\x04\0\x08".to_vec();
+        bytes.extend_from_slice(&[0x40, 0x0B, 0xFA, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(b"SSND\0\0\0\x0c\0\0\0\0\0\0\0\0");
+        bytes.extend_from_slice(&[0x80, 0xC0, 0x00, 0x40]);
+
+        let testee = decode(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(testee.format.bits_per_sample, 8);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, -0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let bytes = std::fs::read(AIFF).unwrap();
+
+        assert_eq!(
+            decode(Cursor::new(bytes[..1000].to_vec())).err(),
+            Some(AiffError::Truncated { offset: 80 })
+        );
+
+        let mut compression = std::fs::read("./test_data/audio/aiff/f32be_8k_mono.aifc").unwrap();
+        let at = compression.windows(4).position(|id| id == b"fl32").unwrap();
+        compression[at..at + 4].copy_from_slice(b"ima4");
+        assert_eq!(
+            decode(Cursor::new(compression)).err(),
+            Some(AiffError::UnsupportedCompression("ima4".to_string()))
+        );
+
+        // the fields of the common chunk follow its id and size
+        let comm = bytes.windows(4).position(|id| id == b"COMM").unwrap() + 8;
+
+        let mut channels = bytes.clone();
+        channels[comm..comm + 2].copy_from_slice(&0i16.to_be_bytes());
+        assert_eq!(
+            decode(Cursor::new(channels)).err(),
+            Some(AiffError::UnsupportedChannels(0))
+        );
+
+        // the frames of 32767 24 bit channels do not fit into a block align
+        let mut channels = bytes.clone();
+        channels[comm..comm + 2].copy_from_slice(&32767i16.to_be_bytes());
+        channels[comm + 6..comm + 8].copy_from_slice(&24i16.to_be_bytes());
+        assert_eq!(
+            decode(Cursor::new(channels)).err(),
+            Some(AiffError::UnsupportedChannels(32767))
+        );
+
+        for (extended, sample_rate) in [
+            ([0; 10], 0.0),
+            // 2^32, above the highest 32 bit sample rate
+            ([0x40, 0x1F, 0x80, 0, 0, 0, 0, 0, 0, 0], 4294967296.0),
+            // 2^31 Hz 16 bit mono overflows the 32 bit byte rate
+            ([0x40, 0x1E, 0x80, 0, 0, 0, 0, 0, 0, 0], 2147483648.0),
+        ] {
+            let mut rate = bytes.clone();
+            rate[comm + 8..comm + 18].copy_from_slice(&extended);
+            assert_eq!(
+                decode(Cursor::new(rate)).err(),
+                Some(AiffError::UnsupportedSampleRate(sample_rate))
+            );
+        }
+
+        // an infinite exponent without mantissa is not a number
+        let mut rate = bytes.clone();
+        rate[comm + 8..comm + 18].copy_from_slice(&[0x7F, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            decode(Cursor::new(rate)).err(),
+            Some(AiffError::UnsupportedSampleRate(sample_rate)) if sample_rate.is_nan()
+        ));
+
+        assert_eq!(
+            decode(Cursor::new(b"FORM\0\0\0\x04AIFF".to_vec())).err(),
+            Some(AiffError::MissingChunk("COMM".to_string()))
+        );
+        assert_eq!(
+            decode(Cursor::new(b"RIFF\0\0\0\x04WAVE".to_vec())).err(),
+            Some(AiffError::NotAiff)
+        );
+    }
+
+    #[test]
+    fn test_extended_to_f64() {
+        assert_eq!(
+            extended_to_f64(&[0x40, 0x0B, 0xFA, 0, 0, 0, 0, 0, 0, 0]),
+            8000.0
+        );
+        assert_eq!(
+            extended_to_f64(&[0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]),
+            44100.0
+        );
+        assert_eq!(extended_to_f64(&[0; 10]), 0.0);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::audio::cue::Markers;
+use crate::audio::riff_info::{InfoTag, RiffInfo};
+use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveError, RiffWaveFormat};
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::io::Read;
+
+// Sun/NeXT audio files: a header of big-endian 32 bit fields, an optional
+// annotation and the samples.
+// https://www.loc.gov/preservation/digital/formats/fdd/fdd000532.shtml
+#[derive(PartialEq, Debug, Clone)]
+pub enum AuError {
+    // The stream does not start with the ".snd" magic number.
+    NotAu,
+    // The header is cut off or its data offset points into the header.
+    InvalidHeader,
+    // An encoding other than µ-law, A-law, linear PCM and IEEE float.
+    UnsupportedEncoding(u32),
+    UnsupportedChannels(u32),
+    // A sample rate of 0 or too high to express the byte rate of the wave in
+    // 32 bits.
+    UnsupportedSampleRate(u32),
+    // Reading from the underlying stream failed.
+    Io(io::ErrorKind),
+}
+
+impl Display for AuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuError::NotAu => write!(f, "Not a Sun/NeXT audio file"),
+            AuError::InvalidHeader => write!(f, "Invalid Sun/NeXT audio header"),
+            AuError::UnsupportedEncoding(encoding) => {
+                write!(f, "Unsupported Sun/NeXT audio encoding: {}", encoding)
+            }
+            AuError::UnsupportedChannels(num_channels) => {
+                write!(f, "Unsupported number of channels: {}", num_channels)
+            }
+            AuError::UnsupportedSampleRate(sample_rate) => {
+                write!(f, "Unsupported sample rate: {}", sample_rate)
+            }
+            AuError::Io(kind) => write!(f, "Error reading audio: {}", kind),
+        }
+    }
+}
+
+impl Error for AuError {}
+
+impl From<io::Error> for AuError {
+    fn from(error: io::Error) -> Self {
+        AuError::Io(error.kind())
+    }
+}
+
+const HEADER_SIZE: usize = 24;
+
+// Written as data size by streaming writers, which do not know it upfront.
+const UNKNOWN_DATA_SIZE: u32 = 0xFFFFFFFF;
+
+// Decodes a complete .au file into a wave of the same sample format. The
+// annotation becomes the comment of its LIST/INFO metadata.
+pub fn decode<R: Read>(mut reader: R) -> Result<RiffWave, AuError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.get(0..4) != Some(b".snd") {
+        return Err(AuError::NotAu);
+    }
+
+    let header = bytes.get(0..HEADER_SIZE).ok_or(AuError::InvalidHeader)?;
+    let field =
+        |index: usize| u32::from_be_bytes(header[index * 4..index * 4 + 4].try_into().unwrap());
+    let data_offset = field(1) as usize;
+    let data_size = field(2);
+    let encoding = field(3);
+    let sample_rate = field(4);
+    let num_channels = field(5);
+
+    let (audio_format, bits_per_sample) = match encoding {
+        1 => (AudioFormat::MuLaw, 8),
+        2 => (AudioFormat::Pcm, 8),
+        3 => (AudioFormat::Pcm, 16),
+        4 => (AudioFormat::Pcm, 24),
+        5 => (AudioFormat::Pcm, 32),
+        6 => (AudioFormat::IeeeFloat, 32),
+        7 => (AudioFormat::IeeeFloat, 64),
+        27 => (AudioFormat::ALaw, 8),
+        _ => return Err(AuError::UnsupportedEncoding(encoding)),
+    };
+
+    if num_channels == 0 || num_channels > u16::MAX as u32 {
+        return Err(AuError::UnsupportedChannels(num_channels));
+    }
+    if sample_rate == 0 {
+        return Err(AuError::UnsupportedSampleRate(sample_rate));
+    }
+
+    let annotation = bytes
+        .get(HEADER_SIZE..data_offset)
+        .ok_or(AuError::InvalidHeader)?;
+
+    let mut info = RiffInfo::default();
+    let annotation = String::from_utf8_lossy(annotation);
+    let annotation = annotation.trim_end_matches('\0');
+    if !annotation.is_empty() {
+        info.set(InfoTag::Comment, annotation.to_string());
+    }
+
+    let format = RiffWaveFormat::uncompressed(
+        audio_format,
+        Channels::new(num_channels as u16),
+        sample_rate,
+        bits_per_sample,
+    )
+    .map_err(|error| match error {
+        RiffWaveError::UnsupportedChannels(_) => AuError::UnsupportedChannels(num_channels),
+        _ => AuError::UnsupportedSampleRate(sample_rate),
+    })?;
+
+    // files cut off while recording are read as far as they go
+    let mut data = &bytes[data_offset..];
+    if data_size != UNKNOWN_DATA_SIZE {
+        data = &data[..data.len().min(data_size as usize)];
+    }
+    let data = &data[..data.len() - data.len() % format.block_align as usize];
+
+    let mut riff_wave = RiffWave {
+        size: 0,
+        data: format.data_from_big_endian(data),
+        format,
+        info,
+        bext: None,
+        markers: Markers::default(),
+        warnings: Vec::new(),
+    };
+    riff_wave.size = riff_wave.riff_size();
+
+    Ok(riff_wave)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::au::{decode, AuError};
+    use crate::audio::g711;
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{AudioFormat, Channels, RiffWaveReader};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    const PCM: &str = "./test_data/audio/au/pcm_s16be_8k_mono.au";
+
+    // The first 8000 samples of the wave, which all .au files are made of.
+    fn wave_samples() -> Vec<f32> {
+        RiffWaveReader::new(
+            File::open("./test_data/audio/riff_wave/pcm_s16le_8k_mono.wav").unwrap(),
+        )
+        .and_then(RiffWaveReader::read_wave)
+        .unwrap()
+        .data_as_f32()[..8000]
+            .to_vec()
+    }
+
+    #[test]
+    fn test_decode_pcm() {
+        let testee = decode(File::open(PCM).unwrap()).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(testee.format.num_channels, Channels::Mono);
+        assert_eq!(testee.format.sample_rate, 8000);
+        assert_eq!(testee.format.bits_per_sample, 16);
+        assert_eq!(testee.data_as_f32(), wave_samples());
+        assert_eq!(testee.info.get(InfoTag::Comment), Some("Mars and the Moon"));
+    }
+
+    #[test]
+    fn test_decode_alaw() {
+        let testee = decode(File::open("./test_data/audio/au/alaw_8k_mono.au").unwrap()).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::ALaw);
+        assert_eq!(testee.frames(), 8000);
+        assert_eq!(testee.info.get(InfoTag::Comment), None);
+        assert_eq!(
+            testee.data_as_f32(),
+            wave_samples()
+                .iter()
+                .map(|sample| {
+                    let sample = (sample * 32768.0) as i16;
+                    g711::alaw_to_i16(g711::i16_to_alaw(sample)) as f32 / 32768.0
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_decode_unknown_data_size() {
+        // 8 bit samples are signed, unlike in wave files
+        let mut bytes = b".snd\0\0\0\x18\xff\xff\xff\xff\0\0\0\x02\0\0\x1f\x40\0\0\0\x02".to_vec();
+        bytes.extend_from_slice(&[0x80, 0xC0, 0x00, 0x40, 0x7F]);
+
+        let testee = decode(Cursor::new(bytes)).unwrap();
+
+        assert_eq!(testee.format.num_channels, Channels::Stereo);
+        assert_eq!(testee.frames(), 2);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, -0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let bytes = std::fs::read(PCM).unwrap();
+
+        let mut encoding = bytes.clone();
+        encoding[15] = 23;
+        assert_eq!(
+            decode(Cursor::new(encoding)).err(),
+            Some(AuError::UnsupportedEncoding(23))
+        );
+
+        let mut offset = bytes.clone();
+        offset[7] = 8;
+        assert_eq!(
+            decode(Cursor::new(offset)).err(),
+            Some(AuError::InvalidHeader)
+        );
+
+        // the frames of 32768 16 bit channels do not fit into a block align
+        let mut channels = bytes.clone();
+        channels[20..24].copy_from_slice(&32768u32.to_be_bytes());
+        assert_eq!(
+            decode(Cursor::new(channels)).err(),
+            Some(AuError::UnsupportedChannels(32768))
+        );
+
+        let mut channels = bytes.clone();
+        channels[20..24].copy_from_slice(&0u32.to_be_bytes());
+        assert_eq!(
+            decode(Cursor::new(channels)).err(),
+            Some(AuError::UnsupportedChannels(0))
+        );
+
+        for rate in [0u32, 3_000_000_000] {
+            let mut sample_rate = bytes.clone();
+            sample_rate[16..20].copy_from_slice(&rate.to_be_bytes());
+            assert_eq!(
+                decode(Cursor::new(sample_rate)).err(),
+                Some(AuError::UnsupportedSampleRate(rate))
+            );
+        }
+
+        assert_eq!(
+            decode(Cursor::new(bytes[..20].to_vec())).err(),
+            Some(AuError::InvalidHeader)
+        );
+        assert_eq!(
+            decode(Cursor::new(b"FORM".to_vec())).err(),
+            Some(AuError::NotAu)
+        );
+    }
+}
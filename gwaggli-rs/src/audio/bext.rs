@@ -0,0 +1,242 @@
+use crate::audio::riff_info::decode_text;
+use crate::audio::riff_wave::RiffWaveError;
+use std::time::Duration;
+
+// The broadcast audio extension chunk of Broadcast Wave files.
+// https://tech.ebu.ch/docs/tech/tech3285.pdf
+#[derive(PartialEq, Debug, Clone)]
+pub struct BroadcastExtension {
+    pub description: String,
+    pub originator: String,
+    pub originator_reference: String,
+    // yyyy:mm:dd, although other separators are common
+    pub origination_date: String,
+    // hh:mm:ss, although other separators are common
+    pub origination_time: String,
+    // The number of samples since midnight of the first sample of the file.
+    pub time_reference: u64,
+    pub version: u16,
+    pub umid: [u8; 64],
+    // Present from version 2 on.
+    pub loudness: Option<BroadcastLoudness>,
+    pub coding_history: String,
+}
+
+// The loudness of the file according to EBU R 128, in LUFS, LU and dBTP.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BroadcastLoudness {
+    pub integrated: f32,
+    pub range: f32,
+    pub max_true_peak: f32,
+    pub max_momentary: f32,
+    pub max_short_term: f32,
+}
+
+// The size of the chunk without the coding history.
+const FIXED_SIZE: usize = 602;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+impl BroadcastExtension {
+    pub fn new(bytes: &[u8]) -> Result<Self, RiffWaveError> {
+        if bytes.len() < FIXED_SIZE {
+            return Err(RiffWaveError::TruncatedHeader {
+                chunk_id: "bext".to_string(),
+                expected: FIXED_SIZE,
+                actual: bytes.len(),
+            });
+        }
+
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let loudness_at = |offset: usize| u16_at(offset) as i16 as f32 / 100.0;
+
+        let version = u16_at(346);
+
+        Ok(BroadcastExtension {
+            description: decode_text(&bytes[0..256]),
+            originator: decode_text(&bytes[256..288]),
+            originator_reference: decode_text(&bytes[288..320]),
+            origination_date: decode_text(&bytes[320..330]),
+            origination_time: decode_text(&bytes[330..338]),
+            time_reference: u32_at(338) as u64 | (u32_at(342) as u64) << 32,
+            version,
+            umid: bytes[348..412].try_into().unwrap(),
+            loudness: match version {
+                0 | 1 => None,
+                _ => Some(BroadcastLoudness {
+                    integrated: loudness_at(412),
+                    range: loudness_at(414),
+                    max_true_peak: loudness_at(416),
+                    max_momentary: loudness_at(418),
+                    max_short_term: loudness_at(420),
+                }),
+            },
+            coding_history: decode_text(&bytes[FIXED_SIZE..]),
+        })
+    }
+
+    // The time of day of the given position in the file, which starts at the
+    // time reference. Wraps around at midnight.
+    pub fn time_of_day(&self, sample_rate: u32, position: Duration) -> Duration {
+        let start = Duration::from_nanos(
+            ((self.time_reference as u128 * 1_000_000_000) / sample_rate.max(1) as u128
+                % (SECONDS_PER_DAY as u128 * 1_000_000_000)) as u64,
+        );
+        let time = start + position;
+
+        Duration::new(time.as_secs() % SECONDS_PER_DAY, time.subsec_nanos())
+    }
+
+    // The complete bext chunk including its header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(FIXED_SIZE + self.coding_history.len());
+
+        encode_text(&mut body, &self.description, 256);
+        encode_text(&mut body, &self.originator, 32);
+        encode_text(&mut body, &self.originator_reference, 32);
+        encode_text(&mut body, &self.origination_date, 10);
+        encode_text(&mut body, &self.origination_time, 8);
+        body.extend_from_slice(&self.time_reference.to_le_bytes());
+        body.extend_from_slice(&self.version.to_le_bytes());
+        body.extend_from_slice(&self.umid);
+
+        if let Some(loudness) = &self.loudness {
+            for value in [
+                loudness.integrated,
+                loudness.range,
+                loudness.max_true_peak,
+                loudness.max_momentary,
+                loudness.max_short_term,
+            ] {
+                body.extend_from_slice(&((value * 100.0).round() as i16).to_le_bytes());
+            }
+        }
+
+        body.resize(FIXED_SIZE, 0);
+        body.extend_from_slice(self.coding_history.as_bytes());
+
+        let mut bytes = b"bext".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+
+        bytes
+    }
+}
+
+// Writes text into a zero padded field of a fixed length, cutting it off if it
+// is too long.
+fn encode_text(bytes: &mut Vec<u8>, text: &str, length: usize) {
+    let start = bytes.len();
+    bytes.extend(text.bytes().take(length));
+    bytes.resize(start + length, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::bext::{BroadcastExtension, BroadcastLoudness};
+    use crate::audio::riff_wave::RiffWaveError;
+    use std::time::Duration;
+
+    #[test]
+    fn test_new() {
+        let mut bytes = vec![0; 602];
+        bytes[0..9].copy_from_slice(b"Interview");
+        bytes[256..262].copy_from_slice(b"Studio");
+        bytes[320..330].copy_from_slice(b"2023-10-01");
+        bytes[330..338].copy_from_slice(b"14:30:00");
+        bytes[338..342].copy_from_slice(&2_610_000_000u32.to_le_bytes());
+        bytes[342..346].copy_from_slice(&1u32.to_le_bytes());
+        bytes[346..348].copy_from_slice(&2u16.to_le_bytes());
+        bytes[412..414].copy_from_slice(&(-2300i16).to_le_bytes());
+        bytes[414..416].copy_from_slice(&550i16.to_le_bytes());
+        bytes.extend_from_slice(b"A=PCM,F=48000,W=24,M=mono\r\n");
+
+        let testee = BroadcastExtension::new(&bytes).unwrap();
+
+        assert_eq!(testee.description, "Interview");
+        assert_eq!(testee.originator, "Studio");
+        assert_eq!(testee.originator_reference, "");
+        assert_eq!(testee.origination_date, "2023-10-01");
+        assert_eq!(testee.origination_time, "14:30:00");
+        assert_eq!(testee.time_reference, 6_904_967_296);
+        assert_eq!(testee.version, 2);
+        assert_eq!(
+            testee.loudness,
+            Some(BroadcastLoudness {
+                integrated: -23.0,
+                range: 5.5,
+                max_true_peak: 0.0,
+                max_momentary: 0.0,
+                max_short_term: 0.0,
+            })
+        );
+        assert_eq!(testee.coding_history, "A=PCM,F=48000,W=24,M=mono\r\n");
+    }
+
+    #[test]
+    fn test_new_version_1_without_loudness() {
+        let mut bytes = vec![0; 602];
+        bytes[346] = 1;
+        bytes[412] = 0xff;
+
+        let testee = BroadcastExtension::new(&bytes).unwrap();
+
+        assert_eq!(testee.loudness, None);
+    }
+
+    #[test]
+    fn test_new_truncated() {
+        assert_eq!(
+            BroadcastExtension::new(&[0; 600]),
+            Err(RiffWaveError::TruncatedHeader {
+                chunk_id: "bext".to_string(),
+                expected: 602,
+                actual: 600,
+            })
+        );
+    }
+
+    #[test]
+    fn test_time_of_day() {
+        let mut testee = BroadcastExtension::new(&[0; 602]).unwrap();
+        // 14:30:00 at 48kHz
+        testee.time_reference = 52_200 * 48_000;
+
+        assert_eq!(
+            testee.time_of_day(48_000, Duration::from_millis(1500)),
+            Duration::from_millis(52_201_500)
+        );
+        assert_eq!(
+            testee.time_of_day(48_000, Duration::from_secs(10 * 60 * 60)),
+            Duration::from_secs(1800)
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_round_trip() {
+        let mut testee = BroadcastExtension::new(&[0; 602]).unwrap();
+        testee.description = "Morning show".to_string();
+        testee.time_reference = 1 << 33;
+        testee.version = 2;
+        testee.loudness = Some(BroadcastLoudness {
+            integrated: -23.0,
+            range: 7.25,
+            max_true_peak: -1.5,
+            max_momentary: -18.0,
+            max_short_term: -20.0,
+        });
+        testee.coding_history = "A=PCM".to_string();
+
+        let bytes = testee.to_bytes();
+
+        assert_eq!(&bytes[0..8], b"bext\x5f\x02\x00\x00");
+        assert_eq!(bytes.len(), 8 + 608);
+        assert_eq!(BroadcastExtension::new(&bytes[8..]).unwrap(), testee);
+    }
+}
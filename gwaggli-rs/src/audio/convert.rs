@@ -0,0 +1,458 @@
+use crate::audio::cue::{Marker, Markers};
+use crate::audio::downmix::Downmix;
+use crate::audio::resample::{resample, ResampleQuality};
+use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveError, RiffWaveFormat};
+use clap::ValueEnum;
+use std::fmt::Display;
+use std::time::Duration;
+
+// The encoding of the samples of a converted file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SampleFormat {
+    // Signed integers, unsigned for 8 bit.
+    Pcm,
+    // IEEE floating point numbers.
+    Float,
+    // G.711 A-law, always 8 bit.
+    Alaw,
+    // G.711 µ-law, always 8 bit.
+    Mulaw,
+}
+
+impl Display for SampleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SampleFormat::Pcm => write!(f, "pcm"),
+            SampleFormat::Float => write!(f, "float"),
+            SampleFormat::Alaw => write!(f, "alaw"),
+            SampleFormat::Mulaw => write!(f, "mulaw"),
+        }
+    }
+}
+
+impl SampleFormat {
+    // The format the samples of the given audio format are written in if no
+    // other one is chosen. ADPCM can only be read, so it becomes PCM.
+    fn of(audio_format: AudioFormat) -> Self {
+        match audio_format {
+            AudioFormat::IeeeFloat => SampleFormat::Float,
+            AudioFormat::ALaw => SampleFormat::Alaw,
+            AudioFormat::MuLaw => SampleFormat::Mulaw,
+            AudioFormat::Pcm | AudioFormat::MsAdpcm | AudioFormat::ImaAdpcm => SampleFormat::Pcm,
+        }
+    }
+
+    fn audio_format(&self) -> AudioFormat {
+        match self {
+            SampleFormat::Pcm => AudioFormat::Pcm,
+            SampleFormat::Float => AudioFormat::IeeeFloat,
+            SampleFormat::Alaw => AudioFormat::ALaw,
+            SampleFormat::Mulaw => AudioFormat::MuLaw,
+        }
+    }
+
+    pub fn default_bits_per_sample(&self) -> u16 {
+        match self {
+            SampleFormat::Pcm => 16,
+            SampleFormat::Float => 32,
+            SampleFormat::Alaw | SampleFormat::Mulaw => 8,
+        }
+    }
+
+    fn supports(&self, bits_per_sample: u16) -> bool {
+        match self {
+            SampleFormat::Pcm => matches!(bits_per_sample, 8 | 16 | 24 | 32),
+            SampleFormat::Float => matches!(bits_per_sample, 32 | 64),
+            SampleFormat::Alaw | SampleFormat::Mulaw => bits_per_sample == 8,
+        }
+    }
+}
+
+// The target of a conversion. Properties which are None are taken from the
+// converted file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+    pub sample_format: Option<SampleFormat>,
+    pub bits_per_sample: Option<u16>,
+    // The part of the file to keep, from its start to its end by default.
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+    // How the channels are combined when converting to mono.
+    pub downmix: Downmix,
+    pub resample_quality: ResampleQuality,
+}
+
+impl Default for Conversion {
+    fn default() -> Self {
+        Conversion {
+            sample_rate: None,
+            channels: None,
+            sample_format: None,
+            bits_per_sample: None,
+            start: None,
+            end: None,
+            downmix: Downmix::Average,
+            resample_quality: ResampleQuality::Medium,
+        }
+    }
+}
+
+impl Conversion {
+    // The format of the converted file. The bit depth is kept as long as the
+    // sample format is, otherwise it is the default of the new sample format.
+    pub fn format_for(&self, source: &RiffWaveFormat) -> Result<RiffWaveFormat, RiffWaveError> {
+        let source_sample_format = SampleFormat::of(source.audio_format);
+        let sample_format = self.sample_format.unwrap_or(source_sample_format);
+        let bits_per_sample = match self.bits_per_sample {
+            Some(bits_per_sample) => bits_per_sample,
+            None if sample_format == source_sample_format
+                && sample_format.supports(source.bits_per_sample) =>
+            {
+                source.bits_per_sample
+            }
+            None => sample_format.default_bits_per_sample(),
+        };
+
+        if !sample_format.supports(bits_per_sample) {
+            return Err(RiffWaveError::UnsupportedBitsPerSample {
+                audio_format: sample_format.audio_format(),
+                bits_per_sample,
+            });
+        }
+
+        let num_channels = match self.channels {
+            Some(0) => return Err(RiffWaveError::UnsupportedChannels(0)),
+            Some(count) => Channels::new(count),
+            None => source.num_channels,
+        };
+
+        RiffWaveFormat::uncompressed(
+            sample_format.audio_format(),
+            num_channels,
+            self.sample_rate.unwrap_or(source.sample_rate),
+            bits_per_sample,
+        )
+    }
+
+    // Converts the wave into a new one. The metadata is kept, with the time
+    // reference and the markers moved along with the trimmed and resampled
+    // frames. Markers are clipped to the kept part, those entirely outside of it
+    // are dropped.
+    pub fn apply(&self, riff_wave: &RiffWave) -> Result<RiffWave, RiffWaveError> {
+        let source = &riff_wave.format;
+        let format = self.format_for(source)?;
+
+        let frames = riff_wave.frames();
+        let start = self
+            .start
+            .map_or(0, |start| frames_of(start, source.sample_rate));
+        let end = self
+            .end
+            .map_or(frames, |end| frames_of(end, source.sample_rate))
+            .clamp(start.min(frames), frames);
+        let start = start.min(end);
+
+        let from_channels = source.num_channels.count() as usize;
+        let to_channels = format.num_channels.count() as usize;
+        let samples = riff_wave.data_as_f32();
+        let samples = samples
+            .get(start as usize * from_channels..end as usize * from_channels)
+            .unwrap_or_default();
+
+        let samples = remix(samples, from_channels, to_channels, self.downmix);
+        let samples = match source.sample_rate == format.sample_rate {
+            true => samples,
+            false => resample(
+                &samples,
+                to_channels,
+                source.sample_rate,
+                format.sample_rate,
+                self.resample_quality,
+            ),
+        };
+
+        let (from_rate, to_rate) = (source.sample_rate, format.sample_rate);
+        let rescale = |position: u64| {
+            (position as u128 * to_rate as u128 / from_rate.max(1) as u128).min(u64::MAX as u128)
+                as u64
+        };
+
+        let mut markers = Markers::default();
+        for marker in riff_wave.markers.iter() {
+            // a region starting before the kept part is clipped to its start
+            let marker_end = marker.position.saturating_add(marker.length.unwrap_or(0));
+            let position = marker.position.max(start);
+
+            if position >= end || (marker.position < start && marker_end <= start) {
+                continue;
+            }
+
+            markers.push(Marker {
+                position: rescale(position - start),
+                length: marker
+                    .length
+                    .map(|_| rescale(marker_end.min(end) - position)),
+                ..marker.clone()
+            });
+        }
+
+        let mut converted = RiffWave::from_f32(format, &samples)?;
+        converted.info = riff_wave.info.clone();
+        converted.bext = riff_wave.bext.clone().map(|mut bext| {
+            bext.time_reference = rescale(bext.time_reference.saturating_add(start));
+            bext
+        });
+        converted.markers = markers;
+        converted.size = converted.riff_size();
+
+        Ok(converted)
+    }
+}
+
+fn frames_of(time: Duration, sample_rate: u32) -> u64 {
+    (time.as_nanos() * sample_rate as u128 / 1_000_000_000) as u64
+}
+
+// Maps interleaved samples to another number of channels. Mixing down to mono
+// uses the downmix, mono is copied to every channel, otherwise the first
+// channels are kept and missing ones are silent.
+fn remix(samples: &[f32], from_channels: usize, to_channels: usize, downmix: Downmix) -> Vec<f32> {
+    if from_channels == to_channels {
+        return samples.to_vec();
+    }
+
+    if to_channels == 1 {
+        return downmix.apply(samples, from_channels);
+    }
+
+    samples
+        .chunks_exact(from_channels)
+        .flat_map(|frame| {
+            (0..to_channels).map(move |channel| match from_channels {
+                1 => frame[0],
+                _ => frame.get(channel).copied().unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::bext::BroadcastExtension;
+    use crate::audio::convert::{Conversion, SampleFormat};
+    use crate::audio::cue::Marker;
+    use crate::audio::downmix::Downmix;
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{
+        AudioFormat, Channels, RiffWave, RiffWaveError, RiffWaveFormat, RiffWaveReader,
+    };
+    use std::time::Duration;
+
+    fn stereo_wave() -> RiffWave {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Stereo, 48000, 24).unwrap();
+        let samples: Vec<f32> = (0..48000).flat_map(|_| [0.5, -0.25]).collect();
+
+        RiffWave::from_f32(format, &samples).unwrap()
+    }
+
+    #[test]
+    fn test_whisper_layout() {
+        let testee = Conversion {
+            sample_rate: Some(16000),
+            channels: Some(1),
+            sample_format: Some(SampleFormat::Pcm),
+            bits_per_sample: Some(16),
+            ..Conversion::default()
+        };
+
+        let actual = testee.apply(&stereo_wave()).unwrap();
+
+        assert_eq!(actual.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(actual.format.num_channels, Channels::Mono);
+        assert_eq!(actual.format.sample_rate, 16000);
+        assert_eq!(actual.format.bits_per_sample, 16);
+        assert_eq!(actual.format.byte_rate, 32000);
+        assert_eq!(actual.frames(), 16000);
+        assert!((actual.data_as_f32()[8000] - 0.125).abs() < 1e-3);
+
+        let bytes = actual.to_bytes();
+        assert_eq!(
+            RiffWaveReader::new(bytes.as_slice())
+                .and_then(RiffWaveReader::read_wave)
+                .unwrap()
+                .frames(),
+            16000
+        );
+    }
+
+    #[test]
+    fn test_keeps_unchosen_properties() {
+        let actual = Conversion::default().apply(&stereo_wave()).unwrap();
+
+        assert_eq!(actual.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(actual.format.num_channels, Channels::Stereo);
+        assert_eq!(actual.format.sample_rate, 48000);
+        assert_eq!(actual.format.bits_per_sample, 24);
+        assert_eq!(actual.data, stereo_wave().data);
+
+        let testee = Conversion {
+            sample_format: Some(SampleFormat::Float),
+            ..Conversion::default()
+        };
+
+        assert_eq!(
+            testee
+                .format_for(&stereo_wave().format)
+                .unwrap()
+                .bits_per_sample,
+            32
+        );
+    }
+
+    #[test]
+    fn test_channel_layouts() {
+        let mono = Conversion {
+            channels: Some(1),
+            downmix: Downmix::Right,
+            ..Conversion::default()
+        }
+        .apply(&stereo_wave())
+        .unwrap();
+
+        assert!(mono.data_as_f32().iter().all(|s| (s + 0.25).abs() < 1e-6));
+
+        let quad = Conversion {
+            channels: Some(4),
+            ..Conversion::default()
+        };
+
+        assert_eq!(
+            quad.apply(&mono).unwrap().data_as_f32()[..4],
+            [-0.25, -0.25, -0.25, -0.25]
+        );
+        assert_eq!(
+            quad.apply(&stereo_wave()).unwrap().data_as_f32()[..4],
+            [0.5, -0.25, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_trim_moves_metadata() {
+        let mut riff_wave = stereo_wave();
+        riff_wave.info.set(InfoTag::Title, "Standup".to_string());
+        let mut bext = BroadcastExtension::new(&[0; 602]).unwrap();
+        bext.time_reference = 48000 * 60;
+        riff_wave.bext = Some(bext);
+        for (id, position, length) in [(1, 12000, 24000), (2, 36000, 24000), (3, 0, 18000)] {
+            riff_wave.markers.push(Marker {
+                id,
+                position,
+                length: Some(length),
+                ..Marker::default()
+            });
+        }
+        let testee = Conversion {
+            sample_rate: Some(16000),
+            start: Some(Duration::from_millis(250)),
+            end: Some(Duration::from_millis(500)),
+            ..Conversion::default()
+        };
+
+        let actual = testee.apply(&riff_wave).unwrap();
+
+        assert_eq!(actual.frames(), 4000);
+        assert_eq!(actual.info.get(InfoTag::Title), Some("Standup"));
+        assert_eq!(actual.bext.unwrap().time_reference, 16000 * 60 + 4000);
+
+        riff_wave.bext.as_mut().unwrap().time_reference = u64::MAX;
+        let saturated = testee.apply(&riff_wave).unwrap().bext.unwrap();
+        assert_eq!(saturated.time_reference, u64::MAX / 3);
+        assert_eq!(
+            actual.markers.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                Marker {
+                    id: 1,
+                    position: 0,
+                    length: Some(4000),
+                    ..Marker::default()
+                },
+                Marker {
+                    id: 3,
+                    position: 0,
+                    length: Some(2000),
+                    ..Marker::default()
+                }
+            ]
+        );
+
+        // markers before the kept part without a length are dropped
+        riff_wave.markers = Default::default();
+        riff_wave.markers.push(Marker {
+            id: 4,
+            position: 6000,
+            ..Marker::default()
+        });
+        assert!(testee.apply(&riff_wave).unwrap().markers.is_empty());
+    }
+
+    #[test]
+    fn test_trim_beyond_end() {
+        let testee = Conversion {
+            start: Some(Duration::from_secs(5)),
+            ..Conversion::default()
+        };
+
+        assert_eq!(testee.apply(&stereo_wave()).unwrap().frames(), 0);
+    }
+
+    #[test]
+    fn test_unsupported_target() {
+        let testee = Conversion {
+            sample_format: Some(SampleFormat::Alaw),
+            bits_per_sample: Some(16),
+            ..Conversion::default()
+        };
+
+        assert_eq!(
+            testee.apply(&stereo_wave()).err(),
+            Some(RiffWaveError::UnsupportedBitsPerSample {
+                audio_format: AudioFormat::ALaw,
+                bits_per_sample: 16,
+            })
+        );
+
+        let testee = Conversion {
+            channels: Some(0),
+            ..Conversion::default()
+        };
+
+        assert_eq!(
+            testee.apply(&stereo_wave()).err(),
+            Some(RiffWaveError::UnsupportedChannels(0))
+        );
+
+        // block align and byte rate must fit into their 16 and 32 bits
+        let testee = Conversion {
+            channels: Some(40000),
+            ..Conversion::default()
+        };
+
+        assert_eq!(
+            testee.format_for(&stereo_wave().format).err(),
+            Some(RiffWaveError::UnsupportedChannels(40000))
+        );
+
+        let testee = Conversion {
+            sample_rate: Some(3_000_000_000),
+            ..Conversion::default()
+        };
+
+        assert_eq!(
+            testee.format_for(&stereo_wave().format).err(),
+            Some(RiffWaveError::UnsupportedSampleRate(3_000_000_000))
+        );
+    }
+}
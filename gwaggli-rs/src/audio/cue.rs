@@ -0,0 +1,410 @@
+use crate::audio::riff_info::decode_text;
+use crate::audio::riff_wave::RiffWaveError;
+
+// A cue point of the cue chunk, with its labels from the associated data list.
+// https://www.recordingblogs.com/wiki/cue-chunk-of-a-wave-file
+// https://www.recordingblogs.com/wiki/associated-data-list-chunk-of-a-wave-file
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Marker {
+    pub id: u32,
+    // The first frame of the marker within the data chunk.
+    pub position: u64,
+    // The number of frames of a marked region (ltxt), None for a single point.
+    pub length: Option<u64>,
+    // labl
+    pub label: Option<String>,
+    // note
+    pub note: Option<String>,
+    // The text of a marked region (ltxt).
+    pub text: Option<String>,
+}
+
+// A range of frames to be processed on its own.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Region {
+    pub start: u64,
+    pub end: u64,
+    pub label: String,
+}
+
+// The markers of the cue chunk and the LIST/adtl chunk, sorted by id.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Markers {
+    markers: Vec<Marker>,
+    // The adtl texts of cue points not parsed (yet), as the list may precede
+    // the cue chunk.
+    uncued: Vec<Marker>,
+}
+
+impl Markers {
+    // Parses the body of a cue chunk.
+    pub fn parse_cue(&mut self, bytes: &[u8]) -> Result<(), RiffWaveError> {
+        let count = bytes
+            .get(0..4)
+            .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize);
+        let expected = count.map_or(4, |count| 4 + count.saturating_mul(24));
+
+        if bytes.len() < expected {
+            return Err(RiffWaveError::TruncatedHeader {
+                chunk_id: "cue ".to_string(),
+                expected,
+                actual: bytes.len(),
+            });
+        }
+
+        for point in bytes[4..expected].chunks_exact(24) {
+            let id = u32_at(point, 0);
+
+            if let Ok(index) = self.uncued.binary_search_by_key(&id, |marker| marker.id) {
+                let marker = self.uncued.remove(index);
+                self.push(marker);
+            }
+
+            // the sample offset, relative to the chunk the point is in
+            get_or_insert(&mut self.markers, id).position = u32_at(point, 20) as u64;
+        }
+
+        Ok(())
+    }
+
+    // Parses the body of a LIST chunk of type adtl, starting after the list
+    // type. The offset is the position of the body in the file.
+    pub fn parse_adtl(&mut self, offset: u64, bytes: &[u8]) -> Result<(), RiffWaveError> {
+        let mut position = 0;
+
+        while position < bytes.len() {
+            let header = bytes.get(position..position + 12).ok_or_else(|| {
+                RiffWaveError::TruncatedHeader {
+                    chunk_id: "adtl".to_string(),
+                    expected: 12,
+                    actual: bytes.len() - position,
+                }
+            })?;
+            let id: [u8; 4] = header[0..4].try_into().unwrap();
+            let size = u32_at(header, 4) as usize;
+            let body = bytes
+                .get(position + 8..)
+                .and_then(|rest| rest.get(..size))
+                .filter(|body| body.len() >= 4)
+                .ok_or_else(|| RiffWaveError::ChunkOverrun {
+                    chunk_id: String::from_utf8_lossy(&id).to_string(),
+                    offset: offset as usize + position,
+                    size: size as u64,
+                    available: bytes.len() - position - 8,
+                })?;
+
+            let marker_id = u32_at(body, 0);
+            let marker = match self
+                .markers
+                .binary_search_by_key(&marker_id, |marker| marker.id)
+            {
+                Ok(index) => &mut self.markers[index],
+                Err(_) => get_or_insert(&mut self.uncued, marker_id),
+            };
+
+            match &id {
+                b"labl" => marker.label = Some(decode_text(&body[4..])),
+                b"note" => marker.note = Some(decode_text(&body[4..])),
+                b"ltxt" if body.len() >= 20 => {
+                    marker.length = Some(u32_at(body, 4) as u64);
+                    marker.text = Some(decode_text(&body[20..])).filter(|text| !text.is_empty());
+                }
+                _ => (),
+            }
+
+            position += 8 + size + size % 2;
+        }
+
+        Ok(())
+    }
+
+    // Drops the adtl texts without a cue point, returning their ids.
+    pub fn take_uncued(&mut self) -> Vec<u32> {
+        self.uncued.drain(..).map(|marker| marker.id).collect()
+    }
+
+    pub fn push(&mut self, marker: Marker) {
+        let id = marker.id;
+        *get_or_insert(&mut self.markers, id) = marker;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Marker> {
+        self.markers.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    // Splits the given number of frames into regions at the markers. A region
+    // either has the length of its marker or extends to the next marker.
+    // Frames before the first marker are not part of any region.
+    pub fn regions(&self, frames: u64) -> Vec<Region> {
+        let mut markers: Vec<&Marker> = self
+            .markers
+            .iter()
+            .filter(|marker| marker.position < frames)
+            .collect();
+        markers.sort_by_key(|marker| marker.position);
+
+        markers
+            .iter()
+            .enumerate()
+            .map(|(i, marker)| {
+                let next = markers
+                    .get(i + 1)
+                    .map_or(frames, |next| next.position.max(marker.position + 1));
+                let end = match marker.length {
+                    Some(length) if length > 0 => (marker.position + length).min(frames),
+                    _ => next,
+                };
+
+                Region {
+                    start: marker.position,
+                    end,
+                    label: marker
+                        .label
+                        .clone()
+                        .or_else(|| marker.text.clone())
+                        .or_else(|| marker.note.clone())
+                        .unwrap_or_else(|| format!("Marker {}", marker.id)),
+                }
+            })
+            .collect()
+    }
+
+    // The complete cue chunk and LIST/adtl chunk including their headers, or
+    // nothing if there are no markers.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let mut cue = (self.markers.len() as u32).to_le_bytes().to_vec();
+        let mut adtl = b"adtl".to_vec();
+
+        for (i, marker) in self.markers.iter().enumerate() {
+            cue.extend_from_slice(&marker.id.to_le_bytes());
+            cue.extend_from_slice(&(i as u32).to_le_bytes());
+            cue.extend_from_slice(b"data");
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(&(marker.position as u32).to_le_bytes());
+
+            if let Some(length) = marker.length {
+                let mut body = [0; 16].to_vec();
+                body[0..4].copy_from_slice(&(length as u32).to_le_bytes());
+                body[4..8].copy_from_slice(b"rgn ");
+                if let Some(text) = &marker.text {
+                    body.extend_from_slice(text.as_bytes());
+                    body.push(0);
+                }
+                push_adtl_chunk(&mut adtl, b"ltxt", marker.id, &body);
+            }
+
+            for (id, text) in [(b"labl", &marker.label), (b"note", &marker.note)] {
+                if let Some(text) = text {
+                    let mut body = text.as_bytes().to_vec();
+                    body.push(0);
+                    push_adtl_chunk(&mut adtl, id, marker.id, &body);
+                }
+            }
+        }
+
+        let mut bytes = b"cue ".to_vec();
+        bytes.extend_from_slice(&(cue.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&cue);
+
+        if adtl.len() > 4 {
+            bytes.extend_from_slice(b"LIST");
+            bytes.extend_from_slice(&(adtl.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&adtl);
+        }
+
+        bytes
+    }
+}
+
+fn get_or_insert(markers: &mut Vec<Marker>, id: u32) -> &mut Marker {
+    let index = match markers.binary_search_by_key(&id, |marker| marker.id) {
+        Ok(index) => index,
+        Err(index) => {
+            markers.insert(
+                index,
+                Marker {
+                    id,
+                    ..Marker::default()
+                },
+            );
+            index
+        }
+    };
+
+    &mut markers[index]
+}
+
+fn push_adtl_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], marker_id: u32, body: &[u8]) {
+    let size = 4 + body.len();
+
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(size as u32).to_le_bytes());
+    bytes.extend_from_slice(&marker_id.to_le_bytes());
+    bytes.extend_from_slice(body);
+
+    if size % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::cue::{Marker, Markers, Region};
+    use crate::audio::riff_wave::RiffWaveError;
+
+    #[test]
+    fn test_parse_cue_and_adtl() {
+        let mut cue = 2u32.to_le_bytes().to_vec();
+        for (id, position) in [(2u32, 16000u32), (1, 0)] {
+            cue.extend_from_slice(&id.to_le_bytes());
+            cue.extend_from_slice(&0u32.to_le_bytes());
+            cue.extend_from_slice(b"data");
+            cue.extend_from_slice(&[0; 8]);
+            cue.extend_from_slice(&position.to_le_bytes());
+        }
+        let adtl = b"labl\x09\x00\x00\x00\x01\x00\x00\x00Anna\x00\x00\
+note\x06\x00\x00\x00\x02\x00\x00\x00Q\x00\
+ltxt\x14\x00\x00\x00\x02\x00\x00\x00\x80\x3e\x00\x00rgn \x00\x00\x00\x00\x00\x00\x00\x00";
+        let mut testee = Markers::default();
+
+        testee.parse_adtl(0, adtl).unwrap();
+        testee.parse_cue(&cue).unwrap();
+
+        assert_eq!(
+            testee.iter().cloned().collect::<Vec<_>>(),
+            vec![
+                Marker {
+                    id: 1,
+                    position: 0,
+                    label: Some("Anna".to_string()),
+                    ..Marker::default()
+                },
+                Marker {
+                    id: 2,
+                    position: 16000,
+                    length: Some(16000),
+                    note: Some("Q".to_string()),
+                    ..Marker::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_adtl_without_cue_point() {
+        let mut testee = Markers::default();
+
+        testee
+            .parse_adtl(0, b"labl\x06\x00\x00\x00\x03\x00\x00\x00A\x00")
+            .unwrap();
+
+        assert!(testee.is_empty());
+        assert!(testee.regions(100).is_empty());
+        assert_eq!(testee.take_uncued(), vec![3]);
+        assert_eq!(testee, Markers::default());
+    }
+
+    #[test]
+    fn test_parse_cue_truncated() {
+        let mut testee = Markers::default();
+
+        assert_eq!(
+            testee.parse_cue(&[0x02, 0x00, 0x00, 0x00, 0x01]),
+            Err(RiffWaveError::TruncatedHeader {
+                chunk_id: "cue ".to_string(),
+                expected: 52,
+                actual: 5,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_adtl_overrun() {
+        let mut testee = Markers::default();
+
+        assert_eq!(
+            testee.parse_adtl(40, b"labl\x20\x00\x00\x00\x01\x00\x00\x00"),
+            Err(RiffWaveError::ChunkOverrun {
+                chunk_id: "labl".to_string(),
+                offset: 40,
+                size: 32,
+                available: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn test_regions() {
+        let mut testee = Markers::default();
+        for (id, position, length, label) in [
+            (1, 100, None, Some("Intro")),
+            (2, 400, Some(50), None),
+            (3, 1000, None, Some("Ignored")),
+        ] {
+            testee.push(Marker {
+                id,
+                position,
+                length,
+                label: label.map(str::to_string),
+                ..Marker::default()
+            });
+        }
+
+        assert_eq!(
+            testee.regions(800),
+            vec![
+                Region {
+                    start: 100,
+                    end: 400,
+                    label: "Intro".to_string(),
+                },
+                Region {
+                    start: 400,
+                    end: 450,
+                    label: "Marker 2".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_round_trip() {
+        let mut testee = Markers::default();
+        testee.push(Marker {
+            id: 7,
+            position: 48000,
+            length: Some(96000),
+            label: Some("Bob".to_string()),
+            note: Some("Laughs".to_string()),
+            text: Some("Answer".to_string()),
+        });
+        testee.push(Marker {
+            id: 3,
+            position: 10,
+            ..Marker::default()
+        });
+
+        let bytes = testee.to_bytes();
+        let cue_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        let mut parsed = Markers::default();
+        parsed.parse_cue(&bytes[8..8 + cue_size]).unwrap();
+        parsed.parse_adtl(0, &bytes[8 + cue_size + 12..]).unwrap();
+
+        assert_eq!(&bytes[8 + cue_size..8 + cue_size + 4], b"LIST");
+        assert_eq!(parsed, testee);
+        assert!(Markers::default().to_bytes().is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+use crate::audio::aiff::AiffError;
+use crate::audio::au::AuError;
+use crate::audio::flac::FlacError;
+use crate::audio::mpeg::MpegError;
+use crate::audio::ogg::OggError;
+use crate::audio::riff_wave::{ParseMode, RiffWave, RiffWaveError, RiffWaveReader};
+use crate::audio::{aiff, au, flac, mpeg, ogg};
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::io::{Chain, Cursor, Read};
+
+// The file formats audio can be read from, recognized by their first bytes.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Container {
+    // RIFF/WAVE, RF64 and BW64.
+    RiffWave,
+    // AIFF and AIFF-C.
+    Aiff,
+    // Sun/NeXT audio.
+    Au,
+    Flac,
+    // Ogg Opus and Ogg Vorbis.
+    Ogg,
+    // MP3 and AAC, as elementary streams or in MP4.
+    Mpeg,
+}
+
+impl Container {
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic.get(0..4)? {
+            b"RIFF" | b"RF64" | b"BW64" => Some(Container::RiffWave),
+            b"FORM" => Some(Container::Aiff),
+            b".snd" => Some(Container::Au),
+            b"fLaC" => Some(Container::Flac),
+            b"OggS" => Some(Container::Ogg),
+            // an ID3v2 tag or the sync word of an MP3 or ADTS frame
+            [b'I', b'D', b'3', _] => Some(Container::Mpeg),
+            [0xFF, second, _, _] if second & 0xE0 == 0xE0 => Some(Container::Mpeg),
+            _ if magic.get(4..8) == Some(b"ftyp") => Some(Container::Mpeg),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Container {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Container::RiffWave => write!(f, "RIFF/WAVE"),
+            Container::Aiff => write!(f, "AIFF"),
+            Container::Au => write!(f, "Sun/NeXT audio"),
+            Container::Flac => write!(f, "FLAC"),
+            Container::Ogg => write!(f, "Ogg"),
+            Container::Mpeg => write!(f, "MPEG"),
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum DecodeError {
+    RiffWave(RiffWaveError),
+    Aiff(AiffError),
+    Au(AuError),
+    Flac(FlacError),
+    Ogg(OggError),
+    Mpeg(MpegError),
+    // Reading the first bytes to detect the container failed.
+    Io(io::ErrorKind),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::RiffWave(error) => write!(f, "{}", error),
+            DecodeError::Aiff(error) => write!(f, "{}", error),
+            DecodeError::Au(error) => write!(f, "{}", error),
+            DecodeError::Flac(error) => write!(f, "{}", error),
+            DecodeError::Ogg(error) => write!(f, "{}", error),
+            DecodeError::Mpeg(error) => write!(f, "{}", error),
+            DecodeError::Io(kind) => write!(f, "Error reading audio: {}", kind),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<RiffWaveError> for DecodeError {
+    fn from(error: RiffWaveError) -> Self {
+        DecodeError::RiffWave(error)
+    }
+}
+
+impl From<AiffError> for DecodeError {
+    fn from(error: AiffError) -> Self {
+        DecodeError::Aiff(error)
+    }
+}
+
+impl From<AuError> for DecodeError {
+    fn from(error: AuError) -> Self {
+        DecodeError::Au(error)
+    }
+}
+
+impl From<FlacError> for DecodeError {
+    fn from(error: FlacError) -> Self {
+        DecodeError::Flac(error)
+    }
+}
+
+impl From<OggError> for DecodeError {
+    fn from(error: OggError) -> Self {
+        DecodeError::Ogg(error)
+    }
+}
+
+impl From<MpegError> for DecodeError {
+    fn from(error: MpegError) -> Self {
+        DecodeError::Mpeg(error)
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(error: io::Error) -> Self {
+        DecodeError::Io(error.kind())
+    }
+}
+
+// MP4 files are recognized by the "ftyp" box following its size.
+const MAGIC_LENGTH: usize = 8;
+
+// A file opened by its container. RIFF/WAVE is read incrementally, the other
+// containers are decoded completely.
+pub enum Input<R: Read> {
+    Stream(RiffWaveReader<Chain<Cursor<Vec<u8>>, R>>),
+    Decoded(Container, RiffWave),
+}
+
+// Detects the container of a file by its first bytes and opens it. Unknown
+// containers are read as RIFF/WAVE, which reports them as unsupported. The
+// parse mode applies to RIFF/WAVE only.
+pub fn open<R: Read>(mut reader: R, mode: ParseMode) -> Result<Input<R>, DecodeError> {
+    let mut magic = Vec::with_capacity(MAGIC_LENGTH);
+    reader
+        .by_ref()
+        .take(MAGIC_LENGTH as u64)
+        .read_to_end(&mut magic)?;
+
+    let container = Container::detect(&magic).unwrap_or(Container::RiffWave);
+    let reader = Cursor::new(magic).chain(reader);
+
+    let riff_wave = match container {
+        Container::RiffWave => return Ok(Input::Stream(RiffWaveReader::with_mode(reader, mode)?)),
+        Container::Aiff => aiff::decode(reader)?,
+        Container::Au => au::decode(reader)?,
+        Container::Flac => flac::decode(reader)?,
+        Container::Ogg => ogg::decode(reader)?,
+        Container::Mpeg => mpeg::decode(reader)?,
+    };
+
+    Ok(Input::Decoded(container, riff_wave))
+}
+
+// Reads a complete file of any supported container into a wave.
+pub fn decode<R: Read>(reader: R, mode: ParseMode) -> Result<RiffWave, DecodeError> {
+    match open(reader, mode)? {
+        Input::Stream(reader) => Ok(reader.read_wave()?),
+        Input::Decoded(_, riff_wave) => Ok(riff_wave),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::aiff::AiffError;
+    use crate::audio::au::AuError;
+    use crate::audio::decode::{decode, open, Container, DecodeError, Input};
+    use crate::audio::flac::FlacError;
+    use crate::audio::ogg::OggError;
+    use crate::audio::riff_wave::{ParseMode, RiffWaveError};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Container::detect(b"RIFF\0\0\0\0WAVE"),
+            Some(Container::RiffWave)
+        );
+        assert_eq!(Container::detect(b"BW64"), Some(Container::RiffWave));
+        assert_eq!(
+            Container::detect(b"FORM\0\0\0\0AIFC"),
+            Some(Container::Aiff)
+        );
+        assert_eq!(Container::detect(b".snd"), Some(Container::Au));
+        assert_eq!(Container::detect(b"fLaC\0"), Some(Container::Flac));
+        assert_eq!(Container::detect(b"OggS"), Some(Container::Ogg));
+        assert_eq!(Container::detect(b"ID3\x04"), Some(Container::Mpeg));
+        assert_eq!(
+            Container::detect(&[0xFF, 0xFB, 0x94, 0x04]),
+            Some(Container::Mpeg)
+        );
+        assert_eq!(
+            Container::detect(b"\0\0\0\x20ftypM4A "),
+            Some(Container::Mpeg)
+        );
+        assert_eq!(Container::detect(b"\0\0\0\x20moov"), None);
+        assert_eq!(Container::detect(b"MThd"), None);
+        assert_eq!(Container::detect(b"fLa"), None);
+    }
+
+    #[test]
+    fn test_open() {
+        let wave = open(
+            File::open("./test_data/audio/riff_wave/pcm_s16le_8k_mono.wav").unwrap(),
+            ParseMode::Strict,
+        )
+        .unwrap();
+        let flac = open(
+            File::open("./test_data/audio/flac/pcm_s16le_8k_mono.flac").unwrap(),
+            ParseMode::Strict,
+        )
+        .unwrap();
+
+        assert!(matches!(wave, Input::Stream(reader) if reader.format.sample_rate == 8000));
+        assert!(matches!(flac, Input::Decoded(Container::Flac, _)));
+    }
+
+    #[test]
+    fn test_decode() {
+        let wave = decode(
+            File::open("./test_data/audio/riff_wave/pcm_s16le_8k_mono.wav").unwrap(),
+            ParseMode::Strict,
+        )
+        .unwrap();
+        let flac = decode(
+            File::open("./test_data/audio/flac/pcm_s16le_8k_mono.flac").unwrap(),
+            ParseMode::Strict,
+        )
+        .unwrap();
+
+        let aiff = decode(
+            File::open("./test_data/audio/aiff/pcm_s16be_8k_mono.aiff").unwrap(),
+            ParseMode::Strict,
+        )
+        .unwrap();
+        let au = decode(
+            File::open("./test_data/audio/au/pcm_s16be_8k_mono.au").unwrap(),
+            ParseMode::Strict,
+        )
+        .unwrap();
+
+        assert_eq!(flac.data, wave.data);
+        assert_eq!(aiff.data, wave.data[..16000]);
+        assert_eq!(au.data, wave.data[..16000]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(
+            decode(Cursor::new(b"MThd".to_vec()), ParseMode::Strict).err(),
+            Some(DecodeError::RiffWave(RiffWaveError::UnsupportedContainer(
+                "MThd".to_string()
+            )))
+        );
+        assert_eq!(
+            decode(
+                Cursor::new(b"FORM\0\0\0\x048SVX".to_vec()),
+                ParseMode::Strict
+            )
+            .err(),
+            Some(DecodeError::Aiff(AiffError::NotAiff))
+        );
+        assert_eq!(
+            decode(Cursor::new(b".snd".to_vec()), ParseMode::Strict).err(),
+            Some(DecodeError::Au(AuError::InvalidHeader))
+        );
+        assert_eq!(
+            decode(Cursor::new(b"fLaC".to_vec()), ParseMode::Strict).err(),
+            Some(DecodeError::Flac(FlacError::Truncated { offset: 4 }))
+        );
+        assert_eq!(
+            decode(Cursor::new(b"OggS".to_vec()), ParseMode::Strict).err(),
+            Some(DecodeError::Ogg(OggError::Truncated { offset: 0 }))
+        );
+    }
+}
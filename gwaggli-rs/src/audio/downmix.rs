@@ -0,0 +1,117 @@
+use crate::audio::riff_wave::deinterleave;
+use clap::ValueEnum;
+use std::fmt::Display;
+
+// How the channels of a recording are combined into a single one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Downmix {
+    // The mean of all channels.
+    Average,
+    // The first channel only.
+    Left,
+    // The second channel only.
+    Right,
+    // The channel with the most energy, f.e. the microphone next to the speaker.
+    Loudest,
+}
+
+impl Display for Downmix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Downmix::Average => write!(f, "average"),
+            Downmix::Left => write!(f, "left"),
+            Downmix::Right => write!(f, "right"),
+            Downmix::Loudest => write!(f, "loudest"),
+        }
+    }
+}
+
+impl Downmix {
+    // Whether each frame is mixed on its own, so a recording can be mixed block
+    // by block while it is read.
+    pub fn is_per_frame(&self) -> bool {
+        *self != Downmix::Loudest
+    }
+
+    // Combines interleaved samples into mono samples. Mono samples are returned
+    // as they are.
+    pub fn apply(&self, samples: &[f32], num_channels: usize) -> Vec<f32> {
+        if num_channels <= 1 {
+            return samples.to_vec();
+        }
+
+        match self {
+            Downmix::Average => samples
+                .chunks_exact(num_channels)
+                .map(|frame| frame.iter().sum::<f32>() / num_channels as f32)
+                .collect(),
+            Downmix::Left => select_channel(samples, num_channels, 0),
+            Downmix::Right => select_channel(samples, num_channels, 1),
+            Downmix::Loudest => {
+                let channels = deinterleave(samples, num_channels);
+                let loudest = channels
+                    .iter()
+                    .map(|channel| channel.iter().map(|s| s * s).sum::<f32>())
+                    .enumerate()
+                    .fold((0, f32::MIN), |loudest, (index, energy)| {
+                        if energy > loudest.1 {
+                            (index, energy)
+                        } else {
+                            loudest
+                        }
+                    })
+                    .0;
+
+                channels.into_iter().nth(loudest).unwrap_or_default()
+            }
+        }
+    }
+}
+
+fn select_channel(samples: &[f32], num_channels: usize, channel: usize) -> Vec<f32> {
+    samples
+        .chunks_exact(num_channels)
+        .map(|frame| frame[channel])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::downmix::Downmix;
+
+    const STEREO: [f32; 6] = [0.5, -0.25, 0.25, 0.25, -1.0, 0.0];
+
+    #[test]
+    fn test_average() {
+        assert_eq!(Downmix::Average.apply(&STEREO, 2), vec![0.125, 0.25, -0.5]);
+        assert_eq!(
+            Downmix::Average.apply(&[0.5, 0.25, 0.75, 0.5], 4),
+            vec![0.5]
+        );
+    }
+
+    #[test]
+    fn test_left_and_right() {
+        assert_eq!(Downmix::Left.apply(&STEREO, 2), vec![0.5, 0.25, -1.0]);
+        assert_eq!(Downmix::Right.apply(&STEREO, 2), vec![-0.25, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn test_loudest() {
+        assert_eq!(Downmix::Loudest.apply(&STEREO, 2), vec![0.5, 0.25, -1.0]);
+        assert_eq!(
+            Downmix::Loudest.apply(&[0.0, 0.1, 0.9, 0.0, -0.1, -0.9], 3),
+            vec![0.9, -0.9]
+        );
+    }
+
+    #[test]
+    fn test_mono_is_unchanged() {
+        assert_eq!(Downmix::Right.apply(&[0.5, 0.25], 1), vec![0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_incomplete_frame_is_dropped() {
+        assert_eq!(Downmix::Average.apply(&[0.5, 0.5, 1.0], 2), vec![0.5]);
+    }
+}
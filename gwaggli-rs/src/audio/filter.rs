@@ -0,0 +1,442 @@
+use std::f64::consts::PI;
+use std::fmt::Display;
+
+// A second order IIR filter in direct form I, with the coefficients normalized
+// by a0. https://www.w3.org/TR/audio-eq-cookbook/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    // x[n-1], x[n-2], y[n-1], y[n-2]
+    state: [f64; 4],
+}
+
+impl Biquad {
+    pub fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Biquad {
+            b,
+            a,
+            state: [0.0; 4],
+        }
+    }
+
+    pub fn high_pass(sample_rate: u32, cutoff: f32, q: f32) -> Self {
+        let (cos, alpha) = cookbook_terms(sample_rate, cutoff, q);
+        let a0 = 1.0 + alpha;
+
+        Biquad::new(
+            [
+                (1.0 + cos) / 2.0 / a0,
+                -(1.0 + cos) / a0,
+                (1.0 + cos) / 2.0 / a0,
+            ],
+            [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        )
+    }
+
+    pub fn notch(sample_rate: u32, frequency: f32, q: f32) -> Self {
+        let (cos, alpha) = cookbook_terms(sample_rate, frequency, q);
+        let a0 = 1.0 + alpha;
+
+        Biquad::new(
+            [1.0 / a0, -2.0 * cos / a0, 1.0 / a0],
+            [-2.0 * cos / a0, (1.0 - alpha) / a0],
+        )
+    }
+
+    pub fn filter(&mut self, x: f64) -> f64 {
+        let [x1, x2, y1, y2] = self.state;
+        let y = self.b[0] * x + self.b[1] * x1 + self.b[2] * x2 - self.a[0] * y1 - self.a[1] * y2;
+
+        self.state = [x, x1, y, y1];
+
+        y
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        samples
+            .iter_mut()
+            .for_each(|s| *s = self.filter(*s as f64) as f32);
+    }
+}
+
+fn cookbook_terms(sample_rate: u32, frequency: f32, q: f32) -> (f64, f64) {
+    let omega = 2.0 * PI * frequency as f64 / sample_rate.max(1) as f64;
+
+    (omega.cos(), omega.sin() / (2.0 * q as f64))
+}
+
+// Q of the high-pass, without resonance at the cutoff.
+const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+// Q of the hum notches, about 2Hz wide at 50Hz.
+const HUM_Q: f32 = 30.0;
+
+// The number of harmonics of the mains frequency that are removed.
+const HUM_HARMONICS: u32 = 4;
+
+// The frames of the noise reduction and the length of the quiet region the
+// noise profile is learned from.
+const FRAME_LENGTH: usize = 512;
+const NOISE_PROFILE_SECONDS: f32 = 0.5;
+
+// How much of the noise is subtracted, and how much of the signal is kept at
+// least to avoid the warbling artifacts of empty frequency bins.
+const OVER_SUBTRACTION: f32 = 2.0;
+const SPECTRAL_FLOOR: f32 = 0.05;
+
+// The filters for speech clean-up, applied in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Filters {
+    // The cutoff of a high-pass against rumble, in Hz.
+    pub high_pass: Option<f32>,
+    // The mains frequency of hum to remove with its harmonics, usually 50 or 60Hz.
+    pub hum: Option<f32>,
+    // Spectral subtraction of the noise of the quietest part of the recording.
+    pub noise_reduction: bool,
+}
+
+impl Display for Filters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut steps = Vec::new();
+
+        if let Some(cutoff) = self.high_pass {
+            steps.push(format!("high-pass at {}Hz", cutoff));
+        }
+        if let Some(frequency) = self.hum {
+            steps.push(format!("{}Hz hum removal", frequency));
+        }
+        if self.noise_reduction {
+            steps.push("noise reduction".to_string());
+        }
+
+        write!(f, "{}", steps.join(", "))
+    }
+}
+
+impl Filters {
+    pub fn is_empty(&self) -> bool {
+        *self == Filters::default()
+    }
+
+    pub fn apply(&self, samples: &mut [f32], sample_rate: u32) {
+        let nyquist = sample_rate as f32 / 2.0;
+
+        if let Some(cutoff) = self
+            .high_pass
+            .filter(|cutoff| *cutoff > 0.0 && *cutoff < nyquist)
+        {
+            Biquad::high_pass(sample_rate, cutoff, BUTTERWORTH_Q).process(samples);
+        }
+
+        if let Some(frequency) = self.hum.filter(|frequency| *frequency > 0.0) {
+            (1..=HUM_HARMONICS)
+                .map(|harmonic| frequency * harmonic as f32)
+                .filter(|frequency| *frequency < nyquist)
+                .for_each(|frequency| {
+                    Biquad::notch(sample_rate, frequency, HUM_Q).process(samples)
+                });
+        }
+
+        if self.noise_reduction {
+            reduce_noise(samples, sample_rate);
+        }
+    }
+}
+
+// Spectral subtraction with half overlapping Hann windowed frames, which add
+// up to the original signal again. The frames start half a frame before the
+// samples and end after them, so that every sample is covered by two windows.
+// https://en.wikipedia.org/wiki/Noise_reduction#Spectral_subtraction
+fn reduce_noise(samples: &mut [f32], sample_rate: u32) {
+    let hop = FRAME_LENGTH / 2;
+
+    if samples.len() < FRAME_LENGTH {
+        return;
+    }
+
+    let window: Vec<f32> = (0..FRAME_LENGTH)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME_LENGTH as f32).cos())
+        .collect();
+    let noise = noise_profile(samples, &window, sample_rate);
+    // the second half of the previous frame, which is added to the next one
+    let mut overlap = vec![0.0; hop];
+
+    for start in (0..samples.len() + hop).step_by(hop) {
+        let position = |i: usize| (start + i).checked_sub(hop);
+        let mut spectrum = spectrum(
+            (0..FRAME_LENGTH).map(|i| {
+                position(i)
+                    .and_then(|position| samples.get(position))
+                    .map_or(0.0, |sample| *sample)
+            }),
+            &window,
+        );
+
+        for (bin, noise) in spectrum.iter_mut().zip(&noise) {
+            let magnitude = (bin.0 * bin.0 + bin.1 * bin.1).sqrt();
+
+            if magnitude > 0.0 {
+                let reduced =
+                    (magnitude - OVER_SUBTRACTION * noise).max(SPECTRAL_FLOOR * magnitude);
+                bin.0 *= reduced / magnitude;
+                bin.1 *= reduced / magnitude;
+            }
+        }
+
+        fft(&mut spectrum, true);
+
+        // samples are only overwritten once the frames reading them are done
+        for (i, (re, _)) in spectrum[..hop].iter().enumerate() {
+            if let Some(sample) = position(i).and_then(|position| samples.get_mut(position)) {
+                *sample = overlap[i] + re;
+            }
+        }
+        overlap = spectrum[hop..].iter().map(|(re, _)| *re).collect();
+    }
+}
+
+// The mean magnitude of each frequency bin in the quietest stretch of frames.
+// Only the frames of that stretch are transformed, the others are compared by
+// their windowed energy, which is proportional to that of their spectrum.
+fn noise_profile(samples: &[f32], window: &[f32], sample_rate: u32) -> Vec<f32> {
+    let hop = FRAME_LENGTH / 2;
+    let frames = (samples.len() - FRAME_LENGTH) / hop + 1;
+    let length = ((sample_rate as f32 * NOISE_PROFILE_SECONDS) as usize / hop).clamp(1, frames);
+    let frame = |index: usize| {
+        samples[index * hop..index * hop + FRAME_LENGTH]
+            .iter()
+            .copied()
+    };
+
+    let energies: Vec<f32> = (0..frames)
+        .map(|index| {
+            frame(index)
+                .zip(window)
+                .map(|(s, w)| (s * w) * (s * w))
+                .sum()
+        })
+        .collect();
+    let quietest = (0..=frames - length)
+        .min_by(|a, b| {
+            let energy = |start: usize| energies[start..start + length].iter().sum::<f32>();
+            energy(*a).total_cmp(&energy(*b))
+        })
+        .unwrap_or(0);
+
+    let mut profile = vec![0.0; FRAME_LENGTH];
+
+    for index in quietest..quietest + length {
+        for (mean, (re, im)) in profile.iter_mut().zip(spectrum(frame(index), window)) {
+            *mean += (re * re + im * im).sqrt() / length as f32;
+        }
+    }
+
+    profile
+}
+
+// The spectrum of a frame of FRAME_LENGTH samples after applying the window.
+fn spectrum(frame: impl Iterator<Item = f32>, window: &[f32]) -> Vec<(f32, f32)> {
+    let mut spectrum: Vec<(f32, f32)> = frame.zip(window).map(|(s, w)| (s * w, 0.0)).collect();
+    fft(&mut spectrum, false);
+
+    spectrum
+}
+
+// An iterative radix-2 FFT of complex numbers (re, im). The length must be a
+// power of two. The inverse transform is scaled by 1 / n.
+fn fft(data: &mut [(f32, f32)], inverse: bool) {
+    let n = data.len();
+    let mut j = 0;
+
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+
+    while length <= n {
+        let angle = match inverse {
+            true => 2.0 * PI / length as f64,
+            false => -2.0 * PI / length as f64,
+        };
+
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (re, im) = data[start + k + length / 2];
+                let odd = (
+                    re * cos as f32 - im * sin as f32,
+                    re * sin as f32 + im * cos as f32,
+                );
+                let even = data[start + k];
+
+                data[start + k] = (even.0 + odd.0, even.1 + odd.1);
+                data[start + k + length / 2] = (even.0 - odd.0, even.1 - odd.1);
+            }
+        }
+
+        length <<= 1;
+    }
+
+    if inverse {
+        data.iter_mut().for_each(|(re, im)| {
+            *re /= n as f32;
+            *im /= n as f32;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::filter::{fft, Biquad, Filters};
+    use rand::{Rng, SeedableRng};
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn sine(frequency: f32, amplitude: f32, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|i| {
+                amplitude
+                    * (2.0 * std::f32::consts::PI * frequency * i as f32 / SAMPLE_RATE as f32).sin()
+            })
+            .collect()
+    }
+
+    fn noise(amplitude: f32, length: usize) -> Vec<f32> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+
+        (0..length)
+            .map(|_| rng.gen_range(-amplitude..=amplitude))
+            .collect()
+    }
+
+    // The root mean square, skipping the first second while the filters settle.
+    fn rms(samples: &[f32]) -> f32 {
+        let samples = &samples[SAMPLE_RATE as usize..];
+
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_high_pass() {
+        let mut rumble = sine(30.0, 0.5, 32000);
+        let mut speech = sine(1000.0, 0.5, 32000);
+
+        Biquad::high_pass(SAMPLE_RATE, 100.0, 0.707).process(&mut rumble);
+        Biquad::high_pass(SAMPLE_RATE, 100.0, 0.707).process(&mut speech);
+
+        assert!(rms(&rumble) < 0.05, "{}", rms(&rumble));
+        assert!((rms(&speech) - 0.3536).abs() < 0.005, "{}", rms(&speech));
+    }
+
+    #[test]
+    fn test_hum_removal() {
+        let speech = sine(440.0, 0.3, 32000);
+        let mut samples: Vec<f32> = sine(50.0, 0.3, 32000)
+            .iter()
+            .zip(sine(150.0, 0.1, 32000))
+            .zip(&speech)
+            .map(|((hum, harmonic), speech)| hum + harmonic + speech)
+            .collect();
+        let testee = Filters {
+            hum: Some(50.0),
+            ..Filters::default()
+        };
+
+        testee.apply(&mut samples, SAMPLE_RATE);
+
+        let residual: Vec<f32> = samples.iter().zip(&speech).map(|(s, e)| s - e).collect();
+        assert!(rms(&residual) < 0.02, "{}", rms(&residual));
+    }
+
+    #[test]
+    fn test_noise_reduction() {
+        // one second of noise only, then a tone in the same noise
+        let noise = noise(0.05, 48000);
+        let tone: Vec<f32> = vec![0.0; 16000]
+            .into_iter()
+            .chain(sine(440.0, 0.3, 32000))
+            .collect();
+        let mut samples: Vec<f32> = tone.iter().zip(&noise).map(|(t, n)| t + n).collect();
+        let testee = Filters {
+            noise_reduction: true,
+            ..Filters::default()
+        };
+
+        testee.apply(&mut samples, SAMPLE_RATE);
+
+        let before: Vec<f32> = tone.iter().zip(&noise).map(|(t, n)| t + n - t).collect();
+        let after: Vec<f32> = samples.iter().zip(&tone).map(|(s, t)| s - t).collect();
+        assert!(
+            rms(&after) < rms(&before) / 2.0,
+            "{} {}",
+            rms(&after),
+            rms(&before)
+        );
+        assert!((rms(&samples) - 0.212).abs() < 0.03, "{}", rms(&samples));
+    }
+
+    #[test]
+    fn test_noise_reduction_edges() {
+        let noise = noise(0.05, 16000);
+        let mut samples = noise.clone();
+        let testee = Filters {
+            noise_reduction: true,
+            ..Filters::default()
+        };
+
+        testee.apply(&mut samples, SAMPLE_RATE);
+
+        // the first and last half frame are filtered like all others
+        let energy = |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>();
+        for range in [0..256, 15744..16000] {
+            assert!(
+                energy(&samples[range.clone()]) < energy(&noise[range.clone()]) / 4.0,
+                "{:?}",
+                range
+            );
+        }
+    }
+
+    #[test]
+    fn test_fft_round_trip() {
+        let samples = sine(1000.0, 1.0, 16);
+        let mut data: Vec<(f32, f32)> = samples.iter().map(|s| (*s, 0.0)).collect();
+
+        fft(&mut data, false);
+
+        // 1kHz is bin 1 of 16 at 16kHz
+        assert!((data[1].1 + 8.0).abs() < 1e-4);
+        assert!(data[2].0.abs() < 1e-4);
+
+        fft(&mut data, true);
+
+        for (actual, expected) in data.iter().zip(samples) {
+            assert!((actual.0 - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let testee = Filters {
+            high_pass: Some(80.0),
+            hum: Some(60.0),
+            noise_reduction: true,
+        };
+
+        assert_eq!(
+            testee.to_string(),
+            "high-pass at 80Hz, 60Hz hum removal, noise reduction"
+        );
+    }
+}
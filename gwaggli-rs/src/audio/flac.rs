@@ -0,0 +1,950 @@
+use crate::audio::cue::Markers;
+use crate::audio::riff_info::{InfoTag, RiffInfo};
+use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveFormat};
+use md5::{Digest, Md5};
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::io::Read;
+
+// https://www.rfc-editor.org/rfc/rfc9639
+#[derive(PartialEq, Debug, Clone)]
+pub enum FlacError {
+    // The stream does not start with the "fLaC" marker.
+    NotFlac,
+    // A metadata block or frame is cut off at the end of the stream.
+    Truncated { offset: usize },
+    // The STREAMINFO block is not the first metadata block.
+    MissingStreamInfo,
+    // A frame or subframe uses a reserved value or contradicts the STREAMINFO.
+    InvalidFrame { offset: usize },
+    // The checksum of a frame header (CRC-8) or a frame (CRC-16) does not match.
+    CrcMismatch { offset: usize },
+    // The decoded samples do not match the MD5 signature of the STREAMINFO.
+    Md5Mismatch,
+    UnsupportedBitsPerSample(u16),
+    // Reading from the underlying stream failed.
+    Io(io::ErrorKind),
+}
+
+impl Display for FlacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlacError::NotFlac => write!(f, "Not a FLAC stream"),
+            FlacError::Truncated { offset } => {
+                write!(f, "FLAC stream truncated at offset {}", offset)
+            }
+            FlacError::MissingStreamInfo => write!(f, "Missing FLAC STREAMINFO block"),
+            FlacError::InvalidFrame { offset } => {
+                write!(f, "Invalid FLAC frame at offset {}", offset)
+            }
+            FlacError::CrcMismatch { offset } => {
+                write!(f, "CRC mismatch of the FLAC frame at offset {}", offset)
+            }
+            FlacError::Md5Mismatch => {
+                write!(
+                    f,
+                    "The decoded FLAC samples do not match their MD5 signature"
+                )
+            }
+            FlacError::UnsupportedBitsPerSample(bits_per_sample) => {
+                write!(
+                    f,
+                    "Unsupported bits per sample for FLAC: {}",
+                    bits_per_sample
+                )
+            }
+            FlacError::Io(kind) => write!(f, "Error reading audio: {}", kind),
+        }
+    }
+}
+
+impl Error for FlacError {}
+
+impl From<io::Error> for FlacError {
+    fn from(error: io::Error) -> Self {
+        FlacError::Io(error.kind())
+    }
+}
+
+// The STREAMINFO metadata block, describing the whole stream.
+#[derive(PartialEq, Debug, Clone)]
+pub struct FlacStreamInfo {
+    pub sample_rate: u32,
+    pub num_channels: u16,
+    pub bits_per_sample: u16,
+    // The number of frames (samples per channel), 0 if unknown.
+    pub total_frames: u64,
+    // The MD5 signature of the unencoded samples, all zero if unknown.
+    pub md5: [u8; 16],
+}
+
+impl FlacStreamInfo {
+    fn new(bytes: &[u8], offset: usize) -> Result<Self, FlacError> {
+        let mut reader = BitReader::new(bytes, offset);
+
+        // minimum and maximum block size and frame size
+        reader.read(80)?;
+        let sample_rate = reader.read(20)? as u32;
+        let num_channels = reader.read(3)? as u16 + 1;
+        let bits_per_sample = reader.read(5)? as u16 + 1;
+        let total_frames = reader.read(36)?;
+        let md5 = bytes
+            .get(18..34)
+            .ok_or(FlacError::Truncated {
+                offset: offset + bytes.len(),
+            })?
+            .try_into()
+            .unwrap();
+
+        if bits_per_sample < 4 {
+            return Err(FlacError::UnsupportedBitsPerSample(bits_per_sample));
+        }
+
+        Ok(FlacStreamInfo {
+            sample_rate,
+            num_channels,
+            bits_per_sample,
+            total_frames,
+            md5,
+        })
+    }
+}
+
+const STREAMINFO: u8 = 0;
+const VORBIS_COMMENT: u8 = 4;
+
+// Decodes a complete FLAC stream into a PCM wave of the same bit depth, rounded
+// up to whole bytes. The Vorbis comments become its LIST/INFO metadata.
+pub fn decode<R: Read>(mut reader: R) -> Result<RiffWave, FlacError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.get(0..4) != Some(b"fLaC") {
+        return Err(FlacError::NotFlac);
+    }
+
+    let mut stream_info = None;
+    let mut info = RiffInfo::default();
+    let mut position = 4;
+
+    loop {
+        let header = bytes
+            .get(position..position + 4)
+            .ok_or(FlacError::Truncated { offset: position })?;
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let size = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let body = bytes
+            .get(position + 4..position + 4 + size)
+            .ok_or(FlacError::Truncated { offset: position })?;
+
+        match block_type {
+            STREAMINFO if position == 4 => {
+                stream_info = Some(FlacStreamInfo::new(body, position + 4)?)
+            }
+            VORBIS_COMMENT => info = parse_vorbis_comment(body),
+            _ => {}
+        }
+
+        position += 4 + size;
+
+        if is_last {
+            break;
+        }
+    }
+
+    let stream_info = stream_info.ok_or(FlacError::MissingStreamInfo)?;
+    let num_channels = stream_info.num_channels as usize;
+    let bits_per_sample = stream_info.bits_per_sample as u32;
+    let bytes_per_sample = bits_per_sample.div_ceil(8) as usize;
+
+    let mut samples: Vec<i32> = Vec::new();
+    let mut md5 = Md5::new();
+
+    while position < bytes.len()
+        && (stream_info.total_frames == 0
+            || ((samples.len() / num_channels) as u64) < stream_info.total_frames)
+    {
+        let (frame, next) = decode_frame(&bytes, position, &stream_info)?;
+
+        for sample in &frame {
+            md5.update(&sample.to_le_bytes()[..bytes_per_sample]);
+        }
+
+        samples.extend(frame);
+        position = next;
+    }
+
+    let frames = (samples.len() / num_channels) as u64;
+    if stream_info.total_frames > 0 && frames != stream_info.total_frames {
+        return Err(FlacError::Truncated { offset: position });
+    }
+
+    if stream_info.md5 != [0; 16] && md5.finalize()[..] != stream_info.md5 {
+        return Err(FlacError::Md5Mismatch);
+    }
+
+    // the samples are stored left aligned like valid bits in a wave container
+    let container_bits = bytes_per_sample as u32 * 8;
+    let shift = container_bits - bits_per_sample;
+    let data: Vec<u8> = match container_bits {
+        // 8 bit wave samples are unsigned, i.e. offset by 128
+        8 => samples
+            .iter()
+            .map(|sample| (sample << shift) as i8 as u8 ^ 0x80)
+            .collect(),
+        _ => samples
+            .iter()
+            .flat_map(|sample| {
+                (sample << shift)
+                    .to_le_bytes()
+                    .into_iter()
+                    .take(bytes_per_sample)
+            })
+            .collect(),
+    };
+
+    let mut riff_wave = RiffWave {
+        size: 0,
+        format: RiffWaveFormat::uncompressed(
+            AudioFormat::Pcm,
+            Channels::new(stream_info.num_channels),
+            stream_info.sample_rate,
+            container_bits as u16,
+        )
+        // STREAMINFO limits a stream to 8 channels of 32 bits below 2^20 Hz
+        .expect("FLAC formats fit into a wave format"),
+        data,
+        info,
+        bext: None,
+        markers: Markers::default(),
+        warnings: Vec::new(),
+    };
+    riff_wave.size = riff_wave.riff_size();
+
+    Ok(riff_wave)
+}
+
+// Vorbis comments, as used by FLAC, Vorbis and Opus, mapped to the INFO tags
+// with the same meaning. Others are ignored, as are malformed comments.
+// https://www.xiph.org/vorbis/doc/v-comment.html
+pub fn parse_vorbis_comment(bytes: &[u8]) -> RiffInfo {
+    let mut info = RiffInfo::default();
+    let length_at = |position: usize| {
+        bytes
+            .get(position..position + 4)
+            .map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize)
+    };
+
+    let Some(vendor_length) = length_at(0) else {
+        return info;
+    };
+    let mut position = 4 + vendor_length;
+    let count = length_at(position).unwrap_or(0);
+    position += 4;
+
+    for _ in 0..count {
+        let Some(comment) =
+            length_at(position).and_then(|length| bytes.get(position + 4..position + 4 + length))
+        else {
+            break;
+        };
+        position += 4 + comment.len();
+
+        let comment = String::from_utf8_lossy(comment);
+        let Some((field, value)) = comment.split_once('=') else {
+            continue;
+        };
+
+        let tag = match field.to_ascii_uppercase().as_str() {
+            "TITLE" => InfoTag::Title,
+            "ARTIST" => InfoTag::Artist,
+            "ALBUM" => InfoTag::Album,
+            "DATE" => InfoTag::CreationDate,
+            "COMMENT" | "DESCRIPTION" => InfoTag::Comment,
+            "COPYRIGHT" => InfoTag::Copyright,
+            "GENRE" => InfoTag::Genre,
+            "ENCODER" => InfoTag::Software,
+            "TRACKNUMBER" => InfoTag::TrackNumber,
+            _ => continue,
+        };
+
+        info.set(tag, value.to_string());
+    }
+
+    info
+}
+
+const BLOCK_SIZES: [u32; 16] = [
+    0, 192, 576, 1152, 2304, 4608, 0, 0, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768,
+];
+
+const BITS_PER_SAMPLE: [u32; 8] = [0, 8, 12, 0, 16, 20, 24, 32];
+
+// Decodes the frame at the given offset into interleaved samples and returns
+// them with the offset of the next frame.
+fn decode_frame(
+    bytes: &[u8],
+    offset: usize,
+    stream_info: &FlacStreamInfo,
+) -> Result<(Vec<i32>, usize), FlacError> {
+    let invalid = FlacError::InvalidFrame { offset };
+    let header = bytes
+        .get(offset..offset + 4)
+        .ok_or(FlacError::Truncated { offset })?;
+
+    // 14 bit sync code, a reserved bit and the blocking strategy
+    if header[0] != 0xFF || header[1] & 0xFE != 0xF8 || header[3] & 0x01 != 0 {
+        return Err(invalid);
+    }
+
+    let block_size_code = header[2] >> 4;
+    let sample_rate_code = header[2] & 0x0F;
+    let channel_assignment = header[3] >> 4;
+    let bits_per_sample = stream_info.bits_per_sample as u32;
+    match header[3] >> 1 & 0x07 {
+        0 => {}
+        code if BITS_PER_SAMPLE[code as usize] == bits_per_sample => {}
+        _ => return Err(invalid),
+    }
+
+    let mut reader = BitReader::new(&bytes[offset..], offset);
+    reader.read(32)?;
+
+    // the frame or sample number, coded like UTF-8
+    let first = reader.read(8)?;
+    let continuation_bytes = match (first as u8).leading_ones() {
+        0 => 0,
+        length @ 2..=7 => length - 1,
+        _ => return Err(invalid),
+    };
+    reader.read(8 * continuation_bytes)?;
+
+    let block_size = match block_size_code {
+        0 => return Err(invalid),
+        6 => reader.read(8)? as u32 + 1,
+        7 => reader.read(16)? as u32 + 1,
+        code => BLOCK_SIZES[code as usize],
+    } as usize;
+
+    match sample_rate_code {
+        12 => reader.read(8)?,
+        13 | 14 => reader.read(16)?,
+        15 => return Err(invalid),
+        _ => 0,
+    };
+
+    let header_length = reader.byte_position();
+    let crc = reader.read(8)? as u8;
+    if crc8(&bytes[offset..offset + header_length]) != crc {
+        return Err(FlacError::CrcMismatch { offset });
+    }
+
+    // frames do not store their length, but the CRC-16 verifies it before the
+    // subframes are decoded
+    let verified_length = frame_length(&bytes[offset..], reader.byte_position());
+    if let Some(length) = verified_length {
+        reader.limit(length - 2);
+    }
+
+    let num_channels = stream_info.num_channels as usize;
+    let channels = match channel_assignment {
+        0..=7 if channel_assignment as usize + 1 == num_channels => (0..num_channels)
+            .map(|_| decode_subframe(&mut reader, block_size, bits_per_sample))
+            .collect::<Result<Vec<_>, _>>(),
+        8..=10 if num_channels == 2 => {
+            // the side channel has one bit more
+            let side_first = channel_assignment == 9;
+            decode_subframe(&mut reader, block_size, bits_per_sample + side_first as u32).and_then(
+                |first| {
+                    decode_subframe(
+                        &mut reader,
+                        block_size,
+                        bits_per_sample + !side_first as u32,
+                    )
+                    .map(|second| decorrelate(channel_assignment, first, second))
+                },
+            )
+        }
+        _ => return Err(invalid),
+    };
+    reader.align();
+
+    let (channels, frame_length) = match verified_length {
+        Some(length) => match channels {
+            Ok(channels) if reader.byte_position() + 2 == length => (channels, length - 2),
+            _ => return Err(invalid),
+        },
+        // a frame followed by neither another frame nor the end of the stream,
+        // which is decoded to tell whether it is cut off
+        None => {
+            let channels = match channels {
+                Err(error @ FlacError::Truncated { .. }) => return Err(error),
+                Err(_) => return Err(FlacError::CrcMismatch { offset }),
+                Ok(channels) => channels,
+            };
+            let frame_length = reader.byte_position();
+            let crc = reader.read(16)? as u16;
+            if crc16(&bytes[offset..offset + frame_length]) != crc {
+                return Err(FlacError::CrcMismatch { offset });
+            }
+
+            (channels, frame_length)
+        }
+    };
+
+    let samples = (0..block_size)
+        .flat_map(|i| channels.iter().map(move |channel| channel[i] as i32))
+        .collect();
+
+    Ok((samples, offset + frame_length + 2))
+}
+
+// Restores left and right from the stereo decorrelation of the encoder.
+fn decorrelate(channel_assignment: u8, first: Vec<i64>, second: Vec<i64>) -> Vec<Vec<i64>> {
+    match channel_assignment {
+        // left and side
+        8 => {
+            let right = first.iter().zip(&second).map(|(l, s)| l - s).collect();
+            vec![first, right]
+        }
+        // side and right
+        9 => {
+            let left = first.iter().zip(&second).map(|(s, r)| s + r).collect();
+            vec![left, second]
+        }
+        // mid and side, with the bit lost by halving the mid restored from side
+        _ => {
+            let (left, right) = first
+                .iter()
+                .zip(&second)
+                .map(|(m, s)| {
+                    let mid = (m << 1) | (s & 1);
+                    ((mid + s) >> 1, (mid - s) >> 1)
+                })
+                .unzip();
+            vec![left, right]
+        }
+    }
+}
+
+// The coefficients of the fixed predictors of order 0 to 4.
+const FIXED_COEFFICIENTS: [&[i64]; 5] = [&[], &[1], &[2, -1], &[3, -3, 1], &[4, -6, 4, -1]];
+
+fn decode_subframe(
+    reader: &mut BitReader,
+    block_size: usize,
+    bits_per_sample: u32,
+) -> Result<Vec<i64>, FlacError> {
+    let invalid = FlacError::InvalidFrame {
+        offset: reader.offset(),
+    };
+
+    if reader.read(1)? != 0 {
+        return Err(invalid);
+    }
+
+    let subframe_type = reader.read(6)?;
+    let wasted_bits = match reader.read(1)? {
+        1 => reader.read_unary()? + 1,
+        _ => 0,
+    };
+
+    if wasted_bits >= bits_per_sample {
+        return Err(invalid);
+    }
+
+    let bits_per_sample = bits_per_sample - wasted_bits;
+
+    let mut samples = match subframe_type {
+        0 => vec![reader.read_signed(bits_per_sample)?; block_size],
+        1 => (0..block_size)
+            .map(|_| reader.read_signed(bits_per_sample))
+            .collect::<Result<_, _>>()?,
+        8..=12 => {
+            let coefficients = FIXED_COEFFICIENTS[subframe_type as usize - 8];
+            let mut samples =
+                read_warm_up(reader, coefficients.len(), block_size, bits_per_sample)?;
+
+            read_residual(reader, coefficients.len(), block_size, &mut samples)?;
+            predict(&mut samples, coefficients, 0).ok_or(invalid.clone())?;
+
+            samples
+        }
+        32..=63 => {
+            let order = subframe_type as usize - 31;
+            let mut samples = read_warm_up(reader, order, block_size, bits_per_sample)?;
+
+            let precision = match reader.read(4)? {
+                0x0F => return Err(invalid),
+                precision => precision as u32 + 1,
+            };
+            let shift = match reader.read_signed(5)? {
+                shift if shift < 0 => return Err(invalid),
+                shift => shift as u32,
+            };
+            let coefficients: Vec<i64> = (0..order)
+                .map(|_| reader.read_signed(precision))
+                .collect::<Result<_, _>>()?;
+
+            read_residual(reader, order, block_size, &mut samples)?;
+            predict(&mut samples, &coefficients, shift).ok_or(invalid.clone())?;
+
+            samples
+        }
+        _ => return Err(invalid),
+    };
+
+    // predictions of corrupt frames can leave the range of the samples
+    let limit = 1i64 << (bits_per_sample - 1);
+    if samples
+        .iter()
+        .any(|sample| !(-limit..limit).contains(sample))
+    {
+        return Err(invalid);
+    }
+
+    if wasted_bits > 0 {
+        samples
+            .iter_mut()
+            .for_each(|sample| *sample <<= wasted_bits);
+    }
+
+    Ok(samples)
+}
+
+fn read_warm_up(
+    reader: &mut BitReader,
+    order: usize,
+    block_size: usize,
+    bits_per_sample: u32,
+) -> Result<Vec<i64>, FlacError> {
+    if order > block_size {
+        return Err(FlacError::InvalidFrame {
+            offset: reader.offset(),
+        });
+    }
+
+    let mut samples = Vec::with_capacity(block_size);
+    for _ in 0..order {
+        samples.push(reader.read_signed(bits_per_sample)?);
+    }
+
+    Ok(samples)
+}
+
+// Appends the Rice coded residual following the warm up samples of a predictor
+// of the given order.
+fn read_residual(
+    reader: &mut BitReader,
+    order: usize,
+    block_size: usize,
+    samples: &mut Vec<i64>,
+) -> Result<(), FlacError> {
+    let invalid = FlacError::InvalidFrame {
+        offset: reader.offset(),
+    };
+
+    let parameter_bits = match reader.read(2)? {
+        0 => 4,
+        1 => 5,
+        _ => return Err(invalid),
+    };
+    let escape = (1 << parameter_bits) - 1;
+    let partition_order = reader.read(4)?;
+    let partition_size = block_size >> partition_order;
+
+    if partition_size << partition_order != block_size || partition_size < order {
+        return Err(invalid);
+    }
+
+    for partition in 0..1 << partition_order {
+        let count = match partition {
+            0 => partition_size - order,
+            _ => partition_size,
+        };
+
+        match reader.read(parameter_bits)? {
+            parameter if parameter == escape => {
+                let bits = reader.read(5)? as u32;
+                for _ in 0..count {
+                    samples.push(reader.read_signed(bits)?);
+                }
+            }
+            parameter => {
+                for _ in 0..count {
+                    let value = (reader.read_unary()? as u64)
+                        .checked_mul(1 << parameter)
+                        .ok_or(invalid.clone())?
+                        | reader.read(parameter as u32)?;
+                    samples.push((value >> 1) as i64 ^ -((value & 1) as i64));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Turns the residual after the warm up samples into samples by adding the
+// prediction from the previous samples. None if the prediction overflows.
+fn predict(samples: &mut [i64], coefficients: &[i64], shift: u32) -> Option<()> {
+    for i in coefficients.len()..samples.len() {
+        let mut prediction = 0i64;
+
+        for (j, coefficient) in coefficients.iter().enumerate() {
+            prediction = prediction.checked_add(coefficient.checked_mul(samples[i - 1 - j])?)?;
+        }
+
+        samples[i] = samples[i].checked_add(prediction >> shift)?;
+    }
+
+    Some(())
+}
+
+// The length of the frame at the start of the bytes including its CRC-16: the
+// first end after the header whose CRC-16 matches and which is followed by the
+// next frame or the end of the stream.
+fn frame_length(bytes: &[u8], header_length: usize) -> Option<usize> {
+    let mut crc = crc16(bytes.get(..header_length)?);
+
+    for end in header_length..bytes.len().saturating_sub(1) {
+        let followed = match &bytes[end + 2..] {
+            [] => true,
+            [0xFF, second, ..] => second & 0xFE == 0xF8,
+            _ => false,
+        };
+
+        if followed && u16::from_be_bytes([bytes[end], bytes[end + 1]]) == crc {
+            return Some(end + 2);
+        }
+
+        crc = crc16_update(crc, bytes[end]);
+    }
+
+    None
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => crc << 1 ^ 0x07,
+        })
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0, |crc, byte| crc16_update(crc, *byte))
+}
+
+fn crc16_update(crc: u16, byte: u8) -> u16 {
+    (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| match crc & 0x8000 {
+        0 => crc << 1,
+        _ => crc << 1 ^ 0x8005,
+    })
+}
+
+// Reads big endian bit fields, most significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    // The offset of the bytes in the stream, for errors.
+    offset: usize,
+    bit_position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8], offset: usize) -> Self {
+        BitReader {
+            bytes,
+            offset,
+            bit_position: 0,
+        }
+    }
+
+    fn offset(&self) -> usize {
+        self.offset + self.bit_position / 8
+    }
+
+    fn byte_position(&self) -> usize {
+        self.bit_position / 8
+    }
+
+    fn read(&mut self, bits: u32) -> Result<u64, FlacError> {
+        if self.bit_position + bits as usize > self.bytes.len() * 8 {
+            return Err(FlacError::Truncated {
+                offset: self.offset + self.bytes.len(),
+            });
+        }
+
+        let mut value = 0u64;
+        let mut remaining = bits;
+
+        while remaining > 0 {
+            let byte = self.bytes[self.bit_position / 8];
+            let available = 8 - (self.bit_position % 8) as u32;
+            let take = available.min(remaining);
+            let bits = (byte >> (available - take)) & ((1u16 << take) - 1) as u8;
+
+            value = value << take | bits as u64;
+            self.bit_position += take as usize;
+            remaining -= take;
+        }
+
+        Ok(value)
+    }
+
+    // A two's complement number of the given width.
+    fn read_signed(&mut self, bits: u32) -> Result<i64, FlacError> {
+        if bits == 0 {
+            return Ok(0);
+        }
+
+        let value = self.read(bits)?;
+
+        Ok(((value << (64 - bits)) as i64) >> (64 - bits))
+    }
+
+    // The number of zero bits up to the next one bit.
+    fn read_unary(&mut self) -> Result<u32, FlacError> {
+        let mut zeros = 0;
+
+        loop {
+            let byte = *self
+                .bytes
+                .get(self.bit_position / 8)
+                .ok_or(FlacError::Truncated {
+                    offset: self.offset + self.bytes.len(),
+                })?;
+            let remaining = byte << (self.bit_position % 8);
+
+            if remaining == 0 {
+                let skipped = 8 - (self.bit_position % 8);
+                zeros += skipped as u32;
+                self.bit_position += skipped;
+                continue;
+            }
+
+            let leading = remaining.leading_zeros();
+            zeros += leading;
+            self.bit_position += leading as usize + 1;
+
+            return Ok(zeros);
+        }
+    }
+
+    // Ends the bytes after the given length, reading beyond fails as truncated.
+    fn limit(&mut self, length: usize) {
+        self.bytes = &self.bytes[..length.min(self.bytes.len())];
+    }
+
+    fn align(&mut self) {
+        self.bit_position = self.bit_position.div_ceil(8) * 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::flac::{crc16, crc8, decode, parse_vorbis_comment, FlacError};
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{AudioFormat, Channels, RiffWaveReader};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    const MONO: &str = "./test_data/audio/flac/pcm_s16le_8k_mono.flac";
+    const STEREO: &str = "./test_data/audio/flac/pcm_s24le_8k_stereo.flac";
+
+    fn wave_samples() -> Vec<f32> {
+        RiffWaveReader::new(
+            File::open("./test_data/audio/riff_wave/pcm_s16le_8k_mono.wav").unwrap(),
+        )
+        .and_then(RiffWaveReader::read_wave)
+        .unwrap()
+        .data_as_f32()
+    }
+
+    #[test]
+    fn test_decode_mono() {
+        let testee = decode(File::open(MONO).unwrap()).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(testee.format.num_channels, Channels::Mono);
+        assert_eq!(testee.format.sample_rate, 8000);
+        assert_eq!(testee.format.bits_per_sample, 16);
+        assert_eq!(testee.frames(), 132007);
+        assert_eq!(testee.data_as_f32(), wave_samples());
+        assert_eq!(testee.info.get(InfoTag::Title), Some("Mars and the Moon"));
+        assert_eq!(testee.info.get(InfoTag::Artist), Some("gwaggli"));
+    }
+
+    #[test]
+    fn test_decode_stereo_24bit() {
+        // left is the wave shifted to 24 bit, right is its inverted half, coded
+        // with each of the stereo decorrelations
+        let testee = decode(File::open(STEREO).unwrap()).unwrap();
+
+        assert_eq!(testee.format.num_channels, Channels::Stereo);
+        assert_eq!(testee.format.bits_per_sample, 24);
+        assert_eq!(testee.frames(), 20000);
+
+        let channels = testee.channels_as_f32();
+        assert_eq!(channels[0], wave_samples()[..20000]);
+        assert!(channels[0]
+            .iter()
+            .zip(&channels[1])
+            .all(|(left, right)| *right == -left / 2.0));
+    }
+
+    #[test]
+    fn test_verify_checksums() {
+        let bytes = std::fs::read(MONO).unwrap();
+
+        let mut md5 = bytes.clone();
+        md5[30] ^= 0xFF;
+        assert_eq!(decode(Cursor::new(md5)).err(), Some(FlacError::Md5Mismatch));
+
+        // the CRC-16 of the last frame
+        let mut frame = bytes.clone();
+        frame[bytes.len() - 1] ^= 0x01;
+        assert!(matches!(
+            decode(Cursor::new(frame)).err(),
+            Some(FlacError::CrcMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_streams() {
+        let bytes = std::fs::read(MONO).unwrap();
+
+        assert_eq!(
+            decode(Cursor::new(b"RIFF".to_vec())).err(),
+            Some(FlacError::NotFlac)
+        );
+        assert!(matches!(
+            decode(Cursor::new(bytes[..bytes.len() - 1000].to_vec())).err(),
+            Some(FlacError::Truncated { .. })
+        ));
+        assert!(matches!(
+            decode(Cursor::new(bytes[..20].to_vec())).err(),
+            Some(FlacError::Truncated { offset: 4 })
+        ));
+    }
+
+    // Appends the lowest bits of the value, most significant first.
+    fn push_bits(bits: &mut Vec<bool>, value: u64, count: u32) {
+        bits.extend(
+            (0..count)
+                .rev()
+                .map(|i| value.checked_shr(i).unwrap_or(0) & 1 == 1),
+        );
+    }
+
+    // Packs the bits into bytes, padding the last with zeros.
+    fn to_bytes(bits: &[bool]) -> Vec<u8> {
+        bits.chunks(8)
+            .map(|byte| {
+                byte.iter()
+                    .enumerate()
+                    .fold(0, |value, (i, bit)| value | (*bit as u8) << (7 - i))
+            })
+            .collect()
+    }
+
+    // A mono 16 bit stream of a single frame of 16 samples, which has the given
+    // bits per sample code and a LPC subframe of order 1 with the given
+    // coefficient, starting at the highest sample with a residual of zero.
+    fn lpc_stream(bits_per_sample_code: u64, coefficient: u64) -> Vec<u8> {
+        let mut stream_info = Vec::new();
+        push_bits(&mut stream_info, 16, 16);
+        push_bits(&mut stream_info, 16, 16);
+        push_bits(&mut stream_info, 0, 48);
+        push_bits(&mut stream_info, 8000, 20);
+        push_bits(&mut stream_info, 0, 3);
+        push_bits(&mut stream_info, 15, 5);
+        push_bits(&mut stream_info, 16, 36);
+        push_bits(&mut stream_info, 0, 128);
+
+        let mut bytes = b"fLaC\x80\x00\x00\x22".to_vec();
+        bytes.extend(to_bytes(&stream_info));
+
+        let mut header = Vec::new();
+        push_bits(&mut header, 0xFFF8, 16);
+        // block size in 8 bits after the header, sample rate from STREAMINFO
+        push_bits(&mut header, 0x60, 8);
+        push_bits(&mut header, bits_per_sample_code << 1, 8);
+        // frame number and block size - 1
+        push_bits(&mut header, 0, 8);
+        push_bits(&mut header, 15, 8);
+        let mut frame = to_bytes(&header);
+        frame.push(crc8(&frame));
+
+        let mut subframe = Vec::new();
+        push_bits(&mut subframe, 0x40, 8);
+        push_bits(&mut subframe, 0x7FFF, 16);
+        // precision of 15 bits, shift and coefficient
+        push_bits(&mut subframe, 14, 4);
+        push_bits(&mut subframe, 0, 5);
+        push_bits(&mut subframe, coefficient, 15);
+        // a single partition with rice parameter 0
+        push_bits(&mut subframe, 0, 10);
+        push_bits(&mut subframe, 0xFFFF, 15);
+        frame.extend(to_bytes(&subframe));
+        frame.extend(crc16(&frame).to_be_bytes());
+
+        bytes.extend(frame);
+        bytes
+    }
+
+    #[test]
+    fn test_corrupt_frames() {
+        let testee = decode(Cursor::new(lpc_stream(0, 1))).unwrap();
+        assert_eq!(testee.data_as_f32(), vec![i16::MAX as f32 / 32768.0; 16]);
+        assert!(decode(Cursor::new(lpc_stream(4, 1))).is_ok());
+
+        // the predictions overflow from the second sample on
+        assert!(matches!(
+            decode(Cursor::new(lpc_stream(0, 0x3FFF))).err(),
+            Some(FlacError::InvalidFrame { .. })
+        ));
+
+        // the CRC-16 is checked before the subframes are decoded
+        let mut bytes = lpc_stream(0, 0x3FFF);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0x01;
+        assert!(matches!(
+            decode(Cursor::new(bytes)).err(),
+            Some(FlacError::CrcMismatch { .. })
+        ));
+
+        // 8 bits per sample in the frame header, but 16 in STREAMINFO
+        assert!(matches!(
+            decode(Cursor::new(lpc_stream(1, 1))).err(),
+            Some(FlacError::InvalidFrame { .. })
+        ));
+    }
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc16(b"123456789"), 0xFEE8);
+    }
+
+    #[test]
+    fn test_parse_vorbis_comment() {
+        let mut bytes = 3u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(b"abc");
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        for comment in ["title=Standup", "NOEQUALS", "Date=2024-01-06"] {
+            bytes.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(comment.as_bytes());
+        }
+
+        let actual = parse_vorbis_comment(&bytes);
+
+        assert_eq!(actual.get(InfoTag::Title), Some("Standup"));
+        assert_eq!(actual.get(InfoTag::CreationDate), Some("2024-01-06"));
+        assert_eq!(actual.iter().count(), 2);
+        assert!(parse_vorbis_comment(&bytes[..20]).is_empty());
+    }
+}
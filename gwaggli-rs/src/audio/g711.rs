@@ -0,0 +1,130 @@
+// ITU-T G.711 companding as used by WAVE format tags 6 (A-law) and 7 (µ-law).
+// https://www.itu.int/rec/T-REC-G.711
+//
+// Both laws compress a linear 16 bit sample into 8 bits. The decoded values are
+// scaled like 16 bit PCM, so they can be normalized by the same factor.
+
+const MU_LAW_BIAS: i16 = 0x84;
+const MU_LAW_CLIP: i16 = 32635;
+
+pub fn alaw_to_i16(value: u8) -> i16 {
+    let value = value ^ 0x55;
+    let segment = (value & 0x70) >> 4;
+    let mut sample = ((value & 0x0f) as i16) << 4;
+
+    match segment {
+        0 => sample += 8,
+        1 => sample += 0x108,
+        _ => sample = (sample + 0x108) << (segment - 1),
+    }
+
+    if value & 0x80 != 0 {
+        sample
+    } else {
+        -sample
+    }
+}
+
+pub fn mulaw_to_i16(value: u8) -> i16 {
+    let value = !value;
+    let exponent = (value & 0x70) >> 4;
+    let mantissa = (value & 0x0f) as i16;
+    let sample = (((mantissa << 3) + MU_LAW_BIAS) << exponent) - MU_LAW_BIAS;
+
+    if value & 0x80 != 0 {
+        -sample
+    } else {
+        sample
+    }
+}
+
+pub fn i16_to_alaw(sample: i16) -> u8 {
+    let (sign, magnitude) = if sample >= 0 {
+        (0x80, sample)
+    } else {
+        (0x00, (-(sample as i32) - 1) as i16)
+    };
+
+    let value = if magnitude < 256 {
+        (magnitude >> 4) as u8
+    } else {
+        let exponent = 15 - magnitude.leading_zeros() as i16 - 7;
+        let mantissa = (magnitude >> (exponent + 3)) & 0x0f;
+        ((exponent << 4) | mantissa) as u8
+    };
+
+    (value | sign) ^ 0x55
+}
+
+pub fn i16_to_mulaw(sample: i16) -> u8 {
+    let sign = if sample < 0 { 0x80 } else { 0x00 };
+    let magnitude = (sample as i32).abs().min(MU_LAW_CLIP as i32) as i16 + MU_LAW_BIAS;
+
+    let exponent = (15 - magnitude.leading_zeros() as i16 - 7).max(0);
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0f;
+
+    !(sign | ((exponent << 4) | mantissa) as u8)
+}
+
+pub fn alaw_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    array
+        .iter()
+        .map(|value| alaw_to_i16(*value) as f32 * (1.0 / 32768.0))
+        .collect()
+}
+
+pub fn mulaw_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    array
+        .iter()
+        .map(|value| mulaw_to_i16(*value) as f32 * (1.0 / 32768.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alaw_to_i16, i16_to_alaw, i16_to_mulaw, mulaw_to_i16};
+
+    #[test]
+    fn test_alaw_to_i16() {
+        assert_eq!(alaw_to_i16(0xd5), 8);
+        assert_eq!(alaw_to_i16(0x55), -8);
+        assert_eq!(alaw_to_i16(0xaa), 32256);
+        assert_eq!(alaw_to_i16(0x2a), -32256);
+    }
+
+    #[test]
+    fn test_mulaw_to_i16() {
+        assert_eq!(mulaw_to_i16(0xff), 0);
+        assert_eq!(mulaw_to_i16(0x7f), 0);
+        assert_eq!(mulaw_to_i16(0x80), 32124);
+        assert_eq!(mulaw_to_i16(0x00), -32124);
+    }
+
+    #[test]
+    fn test_alaw_round_trip() {
+        for value in 0..=255u8 {
+            assert_eq!(i16_to_alaw(alaw_to_i16(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_mulaw_round_trip() {
+        for value in 0..=255u8 {
+            // 0x7f is negative zero and encodes back to positive zero
+            let expected = if value == 0x7f { 0xff } else { value };
+            assert_eq!(i16_to_mulaw(mulaw_to_i16(value)), expected);
+        }
+    }
+
+    #[test]
+    fn test_quantization_error() {
+        for sample in (i16::MIN..=i16::MAX).step_by(7) {
+            let alaw = alaw_to_i16(i16_to_alaw(sample)) as i32;
+            let mulaw = mulaw_to_i16(i16_to_mulaw(sample)) as i32;
+
+            // the step size of the largest segment is 1024
+            assert!((alaw - sample as i32).abs() <= 1024, "A-law {}", sample);
+            assert!((mulaw - sample as i32).abs() <= 1024, "µ-law {}", sample);
+        }
+    }
+}
@@ -0,0 +1,510 @@
+use crate::audio::bext::BroadcastExtension;
+use crate::audio::cue::Markers;
+use crate::audio::decode::{open, Container, DecodeError, Input};
+use crate::audio::riff_info::RiffInfo;
+use crate::audio::riff_wave::{
+    AudioFormat, Chunk, ParseMode, RiffWave, RiffWaveError, RiffWaveFormat, RiffWaveReader,
+};
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::Read;
+
+// Everything known about a file. The samples of RIFF/WAVE files are not kept
+// in memory.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct AudioInfo {
+    pub container: String,
+    pub audio_format: String,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    pub valid_bits_per_sample: u16,
+    pub block_align: u16,
+    pub byte_rate: u32,
+    pub frames: u64,
+    pub duration_seconds: f64,
+    pub chunks: Vec<ChunkInfo>,
+    pub metadata: Vec<MetadataEntry>,
+    pub levels: Vec<Levels>,
+    // Parts of the file not conforming to the specification, which were
+    // recovered from or are tolerated by the reader.
+    pub violations: Vec<String>,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ChunkInfo {
+    pub id: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+pub struct MetadataEntry {
+    pub tag: String,
+    pub value: String,
+}
+
+// The levels of a channel in dBFS, None for a silent channel.
+#[derive(Serialize, PartialEq, Debug)]
+pub struct Levels {
+    pub peak_db: Option<f32>,
+    pub rms_db: Option<f32>,
+}
+
+// The number of frames decoded at once.
+const FRAMES_PER_BUFFER: usize = 1 << 16;
+
+impl AudioInfo {
+    // Reads a file of any supported container in the given parse mode.
+    // RIFF/WAVE files are read incrementally and described with their chunks,
+    // the violations of the format are listed in lenient mode and an error in
+    // strict mode. Other containers are decoded completely and have no chunks.
+    pub fn read<R: Read>(reader: R, mode: ParseMode) -> Result<Self, DecodeError> {
+        match open(reader, mode)? {
+            Input::Stream(reader) => Ok(AudioInfo::read_stream(reader)?),
+            Input::Decoded(container, riff_wave) => Ok(AudioInfo::of_wave(container, &riff_wave)),
+        }
+    }
+
+    fn read_stream<R: Read>(mut reader: RiffWaveReader<R>) -> Result<Self, RiffWaveError> {
+        let mut meter = LevelMeter::new(reader.format.num_channels.count() as usize);
+
+        loop {
+            let samples = reader.read_frames(FRAMES_PER_BUFFER)?;
+
+            if samples.is_empty() {
+                break;
+            }
+
+            meter.push(&samples);
+        }
+
+        reader.read_remaining_chunks()?;
+
+        let format = &reader.format;
+        let frames = meter.frames;
+        let mut violations: Vec<String> = reader.warnings.iter().map(|w| w.to_string()).collect();
+        violations.extend(format_violations(format));
+
+        if let Some(ds64) = &reader.ds64 {
+            if ds64.sample_count != frames {
+                violations.push(format!(
+                    "The ds64 sample count of {} does not match the {} frames of the data",
+                    ds64.sample_count, frames
+                ));
+            }
+        }
+
+        let riff = &reader.chunks()[0];
+        if riff.size + 8 != reader.position() {
+            violations.push(format!(
+                "The {} size of {} bytes does not match the file length of {} bytes",
+                riff.id,
+                riff.size,
+                reader.position()
+            ));
+        }
+
+        Ok(AudioInfo {
+            container: format!("{}/WAVE", riff.id),
+            audio_format: format.audio_format.to_string(),
+            channels: format.num_channels.count(),
+            sample_rate: format.sample_rate,
+            bits_per_sample: format.bits_per_sample,
+            valid_bits_per_sample: format.valid_bits_per_sample(),
+            block_align: format.block_align,
+            byte_rate: format.byte_rate,
+            frames,
+            duration_seconds: frames as f64 / format.sample_rate.max(1) as f64,
+            chunks: reader
+                .chunks()
+                .iter()
+                .map(|Chunk { id, offset, size }| ChunkInfo {
+                    id: id.clone(),
+                    offset: *offset,
+                    size: *size,
+                })
+                .collect(),
+            metadata: metadata(reader.info(), reader.bext(), reader.markers()),
+            levels: meter.levels(),
+            violations,
+        })
+    }
+
+    // Describes a wave decoded from another container, with the warnings of
+    // its decoder as violations.
+    fn of_wave(container: Container, riff_wave: &RiffWave) -> Self {
+        let format = &riff_wave.format;
+        let mut meter = LevelMeter::new(format.num_channels.count() as usize);
+        meter.push(&riff_wave.data_as_f32());
+
+        let mut violations: Vec<String> =
+            riff_wave.warnings.iter().map(|w| w.to_string()).collect();
+        violations.extend(format_violations(format));
+
+        AudioInfo {
+            container: container.to_string(),
+            audio_format: format.audio_format.to_string(),
+            channels: format.num_channels.count(),
+            sample_rate: format.sample_rate,
+            bits_per_sample: format.bits_per_sample,
+            valid_bits_per_sample: format.valid_bits_per_sample(),
+            block_align: format.block_align,
+            byte_rate: format.byte_rate,
+            frames: meter.frames,
+            duration_seconds: meter.frames as f64 / format.sample_rate.max(1) as f64,
+            chunks: Vec::new(),
+            metadata: metadata(&riff_wave.info, riff_wave.bext.as_ref(), &riff_wave.markers),
+            levels: meter.levels(),
+            violations,
+        }
+    }
+}
+
+// The peak and the sum of squares of each channel, over interleaved samples
+// pushed in whole frames.
+struct LevelMeter {
+    frames: u64,
+    peaks: Vec<f32>,
+    sums: Vec<f64>,
+}
+
+impl LevelMeter {
+    fn new(num_channels: usize) -> Self {
+        LevelMeter {
+            frames: 0,
+            peaks: vec![0.0; num_channels],
+            sums: vec![0.0; num_channels],
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        let num_channels = self.peaks.len();
+
+        for frame in samples.chunks_exact(num_channels) {
+            for (channel, sample) in frame.iter().enumerate() {
+                self.peaks[channel] = self.peaks[channel].max(sample.abs());
+                self.sums[channel] += (*sample as f64) * (*sample as f64);
+            }
+        }
+
+        self.frames += (samples.len() / num_channels) as u64;
+    }
+
+    fn levels(&self) -> Vec<Levels> {
+        self.peaks
+            .iter()
+            .zip(&self.sums)
+            .map(|(peak, sum)| Levels {
+                peak_db: to_db(*peak as f64),
+                rms_db: to_db((sum / self.frames.max(1) as f64).sqrt()),
+            })
+            .collect()
+    }
+}
+
+// The LIST/INFO tags, the fields of a bext chunk and the markers.
+fn metadata(
+    info: &RiffInfo,
+    bext: Option<&BroadcastExtension>,
+    markers: &Markers,
+) -> Vec<MetadataEntry> {
+    let mut metadata: Vec<MetadataEntry> = info
+        .iter()
+        .map(|(tag, value)| MetadataEntry {
+            tag: tag.to_string(),
+            value: value.clone(),
+        })
+        .collect();
+
+    if let Some(bext) = bext {
+        for (tag, value) in [
+            ("Description", &bext.description),
+            ("Originator", &bext.originator),
+            ("Originator reference", &bext.originator_reference),
+            ("Origination date", &bext.origination_date),
+            ("Origination time", &bext.origination_time),
+            ("Coding history", &bext.coding_history),
+        ] {
+            if !value.is_empty() {
+                metadata.push(MetadataEntry {
+                    tag: tag.to_string(),
+                    value: value.trim_end().to_string(),
+                });
+            }
+        }
+
+        metadata.push(MetadataEntry {
+            tag: "Time reference".to_string(),
+            value: bext.time_reference.to_string(),
+        });
+    }
+
+    for marker in markers.iter() {
+        metadata.push(MetadataEntry {
+            tag: format!("Marker {}", marker.id),
+            value: match &marker.label {
+                Some(label) => format!("{} at frame {}", label, marker.position),
+                None => format!("at frame {}", marker.position),
+            },
+        });
+    }
+
+    metadata
+}
+
+// Inconsistent fields of the fmt chunk, which the reader does not rely on.
+fn format_violations(format: &RiffWaveFormat) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    let is_uncompressed = !matches!(
+        format.audio_format,
+        AudioFormat::MsAdpcm | AudioFormat::ImaAdpcm
+    );
+    let expected_block_align =
+        format.num_channels.count() as u32 * format.bits_per_sample.div_ceil(8) as u32;
+
+    if is_uncompressed && format.block_align as u32 != expected_block_align {
+        violations.push(format!(
+            "The block align is {} bytes, but {} bytes for {} {}bit",
+            format.block_align, expected_block_align, format.num_channels, format.bits_per_sample
+        ));
+    }
+
+    let expected_byte_rate = match is_uncompressed {
+        true => format.sample_rate as u64 * format.block_align as u64,
+        false => {
+            let frames_per_block = format.frames_per_block().max(1) as u64;
+            format.sample_rate as u64 * format.block_align as u64 / frames_per_block
+        }
+    };
+
+    if format.byte_rate as u64 != expected_byte_rate {
+        violations.push(format!(
+            "The byte rate is {}B/s, but {}B/s for the sample rate and block align",
+            format.byte_rate, expected_byte_rate
+        ));
+    }
+
+    violations
+}
+
+fn to_db(amplitude: f64) -> Option<f32> {
+    Some((20.0 * amplitude.log10()) as f32).filter(|db| db.is_finite())
+}
+
+impl Display for AudioInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format_db = |db: Option<f32>| db.map_or("-inf".to_string(), |db| format!("{:.1}", db));
+
+        writeln!(f, "Container: {}", self.container)?;
+        writeln!(f, "Format: {}", self.audio_format)?;
+        writeln!(f, "Channels: {}", self.channels)?;
+        writeln!(f, "Sample rate: {}Hz", self.sample_rate)?;
+        match self.valid_bits_per_sample == self.bits_per_sample {
+            true => writeln!(f, "Bit depth: {}bit", self.bits_per_sample)?,
+            false => writeln!(
+                f,
+                "Bit depth: {}bit in {}bit",
+                self.valid_bits_per_sample, self.bits_per_sample
+            )?,
+        }
+        writeln!(f, "Block align: {}B", self.block_align)?;
+        writeln!(f, "Byte rate: {}B/s", self.byte_rate)?;
+        writeln!(
+            f,
+            "Duration: {:.3}s ({} frames)",
+            self.duration_seconds, self.frames
+        )?;
+
+        // only RIFF/WAVE files are described by their chunks
+        if !self.chunks.is_empty() {
+            writeln!(f, "Chunks:")?;
+            for chunk in &self.chunks {
+                writeln!(
+                    f,
+                    "  '{}' at offset {}, {} bytes",
+                    chunk.id, chunk.offset, chunk.size
+                )?;
+            }
+        }
+
+        if !self.metadata.is_empty() {
+            writeln!(f, "Metadata:")?;
+            for entry in &self.metadata {
+                writeln!(f, "  {}: {}", entry.tag, entry.value)?;
+            }
+        }
+
+        writeln!(f, "Levels:")?;
+        for (channel, levels) in self.levels.iter().enumerate() {
+            writeln!(
+                f,
+                "  Channel {}: peak {}dBFS, RMS {}dBFS",
+                channel + 1,
+                format_db(levels.peak_db),
+                format_db(levels.rms_db)
+            )?;
+        }
+
+        match self.violations.is_empty() {
+            true => write!(f, "Violations: none"),
+            false => {
+                write!(f, "Violations:")?;
+                for violation in &self.violations {
+                    write!(f, "\n  {}", violation)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::decode::DecodeError;
+    use crate::audio::inspect::{AudioInfo, ChunkInfo, Levels};
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{
+        AudioFormat, Channels, ParseMode, RiffWave, RiffWaveError, RiffWaveFormat,
+    };
+    use std::fs::File;
+    use std::io::Cursor;
+
+    fn stereo_wave() -> Vec<u8> {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Stereo, 8000, 16).unwrap();
+        let samples: Vec<f32> = (0..8000).flat_map(|_| [0.5, 0.0]).collect();
+        let mut riff_wave = RiffWave::from_f32(format, &samples).unwrap();
+        riff_wave.info.set(InfoTag::Title, "Standup".to_string());
+
+        riff_wave.to_bytes()
+    }
+
+    #[test]
+    fn test_read() {
+        let testee = AudioInfo::read(Cursor::new(stereo_wave()), ParseMode::Lenient).unwrap();
+
+        assert_eq!(testee.container, "RIFF/WAVE");
+        assert_eq!(testee.audio_format, "PCM");
+        assert_eq!(testee.channels, 2);
+        assert_eq!(testee.sample_rate, 8000);
+        assert_eq!(testee.bits_per_sample, 16);
+        assert_eq!(testee.frames, 8000);
+        assert_eq!(testee.duration_seconds, 1.0);
+        assert_eq!(
+            testee.chunks,
+            vec![
+                ChunkInfo {
+                    id: "RIFF".to_string(),
+                    offset: 0,
+                    size: 32064,
+                },
+                ChunkInfo {
+                    id: "fmt ".to_string(),
+                    offset: 12,
+                    size: 16,
+                },
+                ChunkInfo {
+                    id: "LIST".to_string(),
+                    offset: 36,
+                    size: 20,
+                },
+                ChunkInfo {
+                    id: "data".to_string(),
+                    offset: 64,
+                    size: 32000,
+                },
+            ]
+        );
+        assert_eq!(testee.metadata[0].value, "Standup");
+        assert!((testee.levels[0].peak_db.unwrap() + 6.02).abs() < 0.01);
+        assert!((testee.levels[0].rms_db.unwrap() + 6.02).abs() < 0.01);
+        assert_eq!(
+            testee.levels[1],
+            Levels {
+                peak_db: None,
+                rms_db: None,
+            }
+        );
+        assert!(testee.violations.is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut bytes = stereo_wave();
+        // byte rate
+        bytes[28..32].copy_from_slice(&1000u32.to_le_bytes());
+        // RIFF size
+        bytes[4..8].copy_from_slice(&40000u32.to_le_bytes());
+        bytes.truncate(bytes.len() - 2);
+
+        let testee = AudioInfo::read(Cursor::new(bytes.clone()), ParseMode::Lenient).unwrap();
+
+        assert_eq!(testee.violations.len(), 3);
+        assert!(testee.violations[0].contains("'data'"));
+        assert_eq!(
+            testee.violations[1],
+            "The byte rate is 1000B/s, but 32000B/s for the sample rate and block align"
+        );
+        assert_eq!(
+            testee.violations[2],
+            "The RIFF size of 40000 bytes does not match the file length of 32070 bytes"
+        );
+        assert!(matches!(
+            AudioInfo::read(Cursor::new(bytes), ParseMode::Strict),
+            Err(DecodeError::RiffWave(RiffWaveError::ChunkOverrun { .. }))
+        ));
+    }
+
+    #[test]
+    fn test_ds64_violations() {
+        let wave = stereo_wave();
+        let mut bytes = b"RF64\xff\xff\xff\xffWAVEds64\x1c\x00\x00\x00".to_vec();
+        bytes.extend_from_slice(&(wave.len() as u64 + 28).to_le_bytes());
+        bytes.extend_from_slice(&32000u64.to_le_bytes());
+        bytes.extend_from_slice(&4000u64.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&wave[12..]);
+
+        let testee = AudioInfo::read(Cursor::new(bytes), ParseMode::Lenient).unwrap();
+
+        assert_eq!(testee.container, "RF64/WAVE");
+        assert_eq!(testee.frames, 8000);
+        assert_eq!(
+            testee.violations,
+            vec!["The ds64 sample count of 4000 does not match the 8000 frames of the data"]
+        );
+    }
+
+    #[test]
+    fn test_read_decoded() {
+        let testee = AudioInfo::read(
+            File::open("./test_data/audio/flac/pcm_s16le_8k_mono.flac").unwrap(),
+            ParseMode::Lenient,
+        )
+        .unwrap();
+
+        assert_eq!(testee.container, "FLAC");
+        assert_eq!(testee.audio_format, "PCM");
+        assert_eq!(testee.channels, 1);
+        assert_eq!(testee.sample_rate, 8000);
+        assert_eq!(testee.frames, 132007);
+        assert!(testee.chunks.is_empty());
+        assert_eq!(testee.metadata[0].value, "Mars and the Moon");
+        assert!(testee.levels[0].peak_db.is_some());
+        assert!(testee.violations.is_empty());
+        assert!(!testee.to_string().contains("Chunks:"));
+    }
+
+    #[test]
+    fn test_json() {
+        let testee = AudioInfo::read(Cursor::new(stereo_wave()), ParseMode::Lenient).unwrap();
+
+        let json = serde_json::to_value(&testee).unwrap();
+
+        assert_eq!(json["sample_rate"], 8000);
+        assert_eq!(json["chunks"][1]["id"], "fmt ");
+        assert_eq!(json["levels"][1]["peak_db"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::audio::filter::Biquad;
+use std::f64::consts::PI;
+
+// The length of the gating blocks and the step between them.
+const BLOCK_MS: u64 = 400;
+const STEP_MS: u64 = 100;
+
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+// The integrated loudness of mono samples in LUFS according to ITU-R BS.1770
+// and EBU R 128, or None if the samples are too short or silent.
+// https://tech.ebu.ch/docs/tech/tech3341.pdf
+pub fn integrated_loudness(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    let weighted = k_weighting(samples, sample_rate);
+
+    let block = (sample_rate as u64 * BLOCK_MS / 1000) as usize;
+    let step = (sample_rate as u64 * STEP_MS / 1000) as usize;
+
+    if block == 0 || weighted.len() < block {
+        return None;
+    }
+
+    let powers: Vec<f64> = (0..=(weighted.len() - block) / step)
+        .map(|i| {
+            let block = &weighted[i * step..i * step + block];
+            block.iter().map(|s| s * s).sum::<f64>() / block.len() as f64
+        })
+        .filter(|power| loudness_of(*power) > ABSOLUTE_GATE)
+        .collect();
+
+    if powers.is_empty() {
+        return None;
+    }
+
+    let relative_gate = loudness_of(mean(&powers)) + RELATIVE_GATE;
+    let gated: Vec<f64> = powers
+        .into_iter()
+        .filter(|power| loudness_of(*power) > relative_gate)
+        .collect();
+
+    Some(loudness_of(mean(&gated)) as f32)
+}
+
+fn loudness_of(power: f64) -> f64 {
+    -0.691 + 10.0 * power.max(1e-20).log10()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+// The pre-filter modelling the acoustic effect of the head followed by the
+// RLB high-pass, with coefficients for any sample rate as derived by libebur128.
+fn k_weighting(samples: &[f32], sample_rate: u32) -> Vec<f64> {
+    let rate = sample_rate.max(1) as f64;
+
+    let k = (PI * 1681.974450955533 / rate).tan();
+    let q = 0.7071752369554196;
+    let vh = 10f64.powf(3.999843853973347 / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let mut shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let k = (PI * 38.13547087602444 / rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let mut high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    samples
+        .iter()
+        .map(|sample| high_pass.filter(shelf.filter(*sample as f64)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::loudness::integrated_loudness;
+
+    fn sine(sample_rate: u32, frequency: f32, amplitude: f32, seconds: f32) -> Vec<f32> {
+        (0..(sample_rate as f32 * seconds) as usize)
+            .map(|i| {
+                amplitude
+                    * (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sine_1khz() {
+        // a full scale 1kHz sine has a loudness of -3.01 LUFS per channel
+        for sample_rate in [16000, 44100, 48000] {
+            let actual = integrated_loudness(&sine(sample_rate, 1000.0, 1.0, 2.0), sample_rate);
+
+            assert!((actual.unwrap() + 3.01).abs() < 0.05, "{:?}", actual);
+        }
+
+        let actual = integrated_loudness(&sine(48000, 1000.0, 0.1, 2.0), 48000);
+
+        assert!((actual.unwrap() + 23.01).abs() < 0.05, "{:?}", actual);
+    }
+
+    #[test]
+    fn test_silence_is_gated() {
+        let mut samples = sine(16000, 1000.0, 0.1, 2.0);
+        samples.extend(vec![0.0; 16000 * 10]);
+
+        let actual = integrated_loudness(&samples, 16000);
+
+        // the blocks overlapping the end of the tone are quieter, but not gated
+        assert!((actual.unwrap() + 23.01).abs() < 0.5, "{:?}", actual);
+        assert_eq!(integrated_loudness(&[0.0; 16000], 16000), None);
+        assert_eq!(integrated_loudness(&[0.5; 100], 16000), None);
+    }
+
+    #[test]
+    fn test_low_frequencies_are_weighted_down() {
+        let actual = integrated_loudness(&sine(16000, 20.0, 1.0, 2.0), 16000).unwrap();
+
+        assert!(actual < -10.0, "{}", actual);
+    }
+}
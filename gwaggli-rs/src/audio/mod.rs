@@ -1 +1,22 @@
+pub mod adpcm;
+pub mod aiff;
+pub mod au;
+pub mod bext;
+pub mod convert;
+pub mod cue;
+pub mod decode;
+pub mod downmix;
+pub mod filter;
+pub mod flac;
+pub mod g711;
+pub mod inspect;
+pub mod loudness;
+pub mod mpeg;
+pub mod ogg;
+pub mod opus;
+pub mod preprocess;
+pub mod resample;
+pub mod riff_info;
 pub mod riff_wave;
+pub mod vad;
+pub mod vorbis;
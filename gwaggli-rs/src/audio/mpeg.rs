@@ -0,0 +1,284 @@
+use crate::audio::riff_info::{InfoTag, RiffInfo};
+use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveFormat};
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::io::{Cursor, Read};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum MpegError {
+    // The stream is neither MP3 nor AAC, or uses a feature the decoders lack.
+    Unsupported(String),
+    // The container holds no track a decoder is available for.
+    NoAudioTrack,
+    // The container or an audio frame is malformed.
+    Decode(String),
+    // Reading from the underlying stream failed.
+    Io(io::ErrorKind),
+}
+
+impl Display for MpegError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MpegError::Unsupported(message) => write!(f, "Unsupported audio: {}", message),
+            MpegError::NoAudioTrack => write!(f, "No MP3 or AAC audio track found"),
+            MpegError::Decode(message) => write!(f, "Error decoding audio: {}", message),
+            MpegError::Io(kind) => write!(f, "Error reading audio: {}", kind),
+        }
+    }
+}
+
+impl Error for MpegError {}
+
+impl From<io::Error> for MpegError {
+    fn from(error: io::Error) -> Self {
+        MpegError::Io(error.kind())
+    }
+}
+
+impl From<symphonia::core::errors::Error> for MpegError {
+    fn from(error: symphonia::core::errors::Error) -> Self {
+        use symphonia::core::errors::Error;
+
+        match error {
+            Error::IoError(error) => MpegError::Io(error.kind()),
+            Error::Unsupported(message) => MpegError::Unsupported(message.to_string()),
+            error => MpegError::Decode(error.to_string()),
+        }
+    }
+}
+
+// Decodes a complete MP3 (MPEG-1/2 Layer III) or AAC-LC stream, either as an
+// elementary stream or in an MP4/M4A container, into a wave of 32 bit floats.
+// The container and codec are probed from the content. Gapless MP3s with a
+// LAME or Info header are trimmed to their original length.
+pub fn decode<R: Read>(mut reader: R) -> Result<RiffWave, MpegError> {
+    // MP4 files may store their index after the audio, so the stream is read
+    // completely to make it seekable.
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let source = MediaSourceStream::new(Box::new(Cursor::new(bytes)), Default::default());
+    let format_options = FormatOptions {
+        enable_gapless: true,
+        ..Default::default()
+    };
+    let mut probed = symphonia::default::get_probe().format(
+        &Hint::new(),
+        source,
+        &format_options,
+        &MetadataOptions::default(),
+    )?;
+
+    let track = probed
+        .format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or(MpegError::NoAudioTrack)?;
+    let track_id = track.id;
+    let max_frames_per_packet = track.codec_params.max_frames_per_packet;
+    let track_channels = track.codec_params.channels;
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut samples = Vec::new();
+    let mut spec = None;
+
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(error))
+                if error.kind() == io::ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                spec = Some(*decoded.spec());
+                let mut buffer =
+                    SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend_from_slice(buffer.samples());
+            }
+            // a damaged frame is replaced by silence of the same length, which
+            // keeps the following frames in time
+            Err(symphonia::core::errors::Error::DecodeError(_)) => {
+                let num_channels = spec
+                    .map(|spec| spec.channels)
+                    .or(track_channels)
+                    .map_or(1, |channels| channels.count());
+                let frames = match packet.dur {
+                    0 => max_frames_per_packet.unwrap_or(0),
+                    dur => dur.saturating_sub(packet.trim_start as u64 + packet.trim_end as u64),
+                } as usize;
+                samples.resize(samples.len() + frames * num_channels, 0.0);
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+
+    let spec = spec.ok_or(MpegError::NoAudioTrack)?;
+
+    // tags in front of the stream (ID3v2) come first, those of the container
+    // (MP4 ilst) override them
+    let mut info = RiffInfo::default();
+    if let Some(revision) = probed
+        .metadata
+        .get()
+        .as_mut()
+        .and_then(|metadata| metadata.skip_to_latest())
+    {
+        add_tags(&mut info, revision);
+    }
+    if let Some(revision) = probed.format.metadata().skip_to_latest() {
+        add_tags(&mut info, revision);
+    }
+
+    let mut riff_wave = RiffWaveFormat::uncompressed(
+        AudioFormat::IeeeFloat,
+        Channels::new(spec.channels.count() as u16),
+        spec.rate,
+        32,
+    )
+    .and_then(|format| RiffWave::from_f32(format, &samples))
+    .map_err(|error| MpegError::Decode(error.to_string()))?;
+    riff_wave.info = info;
+
+    Ok(riff_wave)
+}
+
+// Maps ID3 and MP4 tags to the INFO tags with the same meaning.
+fn add_tags(info: &mut RiffInfo, revision: &MetadataRevision) {
+    for tag in revision.tags() {
+        let info_tag = match tag.std_key {
+            Some(StandardTagKey::TrackTitle) => InfoTag::Title,
+            Some(StandardTagKey::Artist) => InfoTag::Artist,
+            Some(StandardTagKey::Album) => InfoTag::Album,
+            Some(StandardTagKey::Date) => InfoTag::CreationDate,
+            Some(StandardTagKey::Comment) => InfoTag::Comment,
+            Some(StandardTagKey::Copyright) => InfoTag::Copyright,
+            Some(StandardTagKey::Genre) => InfoTag::Genre,
+            Some(StandardTagKey::Encoder) => InfoTag::Software,
+            Some(StandardTagKey::TrackNumber) => InfoTag::TrackNumber,
+            _ => continue,
+        };
+
+        info.set(info_tag, tag.value.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::mpeg::{decode, MpegError};
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave};
+    use std::f32::consts::PI;
+    use std::fs::File;
+    use std::io;
+    use std::io::Cursor;
+
+    // The difference to a sine of the given frequency and amplitude, starting
+    // at the given frame.
+    fn rms_error(samples: &[f32], frequency: f32, amplitude: f32, delay: usize) -> f32 {
+        let error = samples[delay..]
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| {
+                (sample - amplitude * (2.0 * PI * frequency * i as f32 / 48000.0).sin()).powi(2)
+            })
+            .sum::<f32>()
+            / (samples.len() - delay) as f32;
+
+        error.sqrt()
+    }
+
+    fn assert_tones(riff_wave: &RiffWave, delay: usize) {
+        // 440 Hz at 0.5 on the left and 1000 Hz at 0.25 on the right channel
+        let channels = riff_wave.channels_as_f32();
+
+        assert!(rms_error(&channels[0][..48000 + delay], 440.0, 0.5, delay) < 0.01);
+        assert!(rms_error(&channels[1][..48000 + delay], 1000.0, 0.25, delay) < 0.01);
+    }
+
+    #[test]
+    fn test_decode_mp3() {
+        let riff_wave =
+            decode(File::open("./test_data/audio/mpeg/mp3_48k_stereo.mp3").unwrap()).unwrap();
+
+        assert_eq!(riff_wave.format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(riff_wave.format.num_channels, Channels::Stereo);
+        assert_eq!(riff_wave.format.sample_rate, 48000);
+        // trimmed by the encoder delay and padding of the Info header
+        assert_eq!(riff_wave.frames(), 48000);
+        assert_eq!(riff_wave.info.get(InfoTag::Title), Some("Tones"));
+        assert_eq!(riff_wave.info.get(InfoTag::Artist), Some("gwaggli"));
+        assert_tones(&riff_wave, 0);
+    }
+
+    #[test]
+    fn test_decode_m4a() {
+        let riff_wave =
+            decode(File::open("./test_data/audio/mpeg/aac_48k_stereo.m4a").unwrap()).unwrap();
+
+        assert_eq!(riff_wave.format.num_channels, Channels::Stereo);
+        assert_eq!(riff_wave.format.sample_rate, 48000);
+        // 48 frames of 1024 samples, starting with one frame of priming
+        assert_eq!(riff_wave.frames(), 48 * 1024);
+        assert_eq!(riff_wave.info.get(InfoTag::Title), Some("Tones"));
+        assert_tones(&riff_wave, 1024);
+    }
+
+    #[test]
+    fn test_decode_adts() {
+        let riff_wave =
+            decode(File::open("./test_data/audio/mpeg/aac_48k_mono.aac").unwrap()).unwrap();
+
+        assert_eq!(riff_wave.format.num_channels, Channels::Mono);
+        assert_eq!(riff_wave.frames(), 25 * 1024);
+        assert!(rms_error(&riff_wave.data_as_f32()[..25000], 440.0, 0.5, 1024) < 0.01);
+    }
+
+    #[test]
+    fn test_decode_damaged_frame() {
+        let mut bytes = std::fs::read("./test_data/audio/mpeg/mp3_48k_stereo.mp3").unwrap();
+        // the side information of a frame in the middle of the stream
+        bytes[8495] ^= 0xff;
+
+        let riff_wave = decode(Cursor::new(bytes)).unwrap();
+
+        // replaced by silence instead of shortening the stream by 1152 frames
+        assert_eq!(riff_wave.frames(), 48000);
+        // the span of the damaged frame is silent, and nothing else
+        let samples = riff_wave.data_as_f32();
+        assert!(samples[2 * 23135..2 * (23135 + 1152)]
+            .iter()
+            .all(|sample| *sample == 0.0));
+        assert_eq!(
+            samples.iter().filter(|sample| **sample == 0.0).count(),
+            2 * 1152
+        );
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        // an ID3 tag not followed by any audio frame
+        assert_eq!(
+            decode(Cursor::new(b"ID3\x04\0\0\0\0\0\0".to_vec())).err(),
+            Some(MpegError::Io(io::ErrorKind::UnexpectedEof))
+        );
+    }
+}
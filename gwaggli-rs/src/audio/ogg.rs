@@ -0,0 +1,385 @@
+use crate::audio::riff_wave::RiffWave;
+use crate::audio::{opus, vorbis};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Display;
+use std::io;
+use std::io::Read;
+
+// https://www.rfc-editor.org/rfc/rfc3533
+#[derive(PartialEq, Debug, Clone)]
+pub enum OggError {
+    // A page does not start with the capture pattern "OggS", has an unknown
+    // version or does not continue the packet of the previous page.
+    InvalidPage { offset: u64 },
+    // The checksum of a page does not match.
+    CrcMismatch { offset: u64 },
+    // The stream ends within a page or a packet.
+    Truncated { offset: u64 },
+    // The first packet is neither an Opus nor a Vorbis identification header.
+    UnsupportedCodec,
+    // A header packet of the codec is missing or malformed.
+    InvalidHeader(String),
+    // Opus streams with more than two channels (mapping families 1 and 255).
+    UnsupportedChannelMapping(u8),
+    // The codec failed to decode an audio packet.
+    Decode(String),
+    // Reading from the underlying stream failed.
+    Io(io::ErrorKind),
+}
+
+impl Display for OggError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OggError::InvalidPage { offset } => write!(f, "Invalid Ogg page at offset {}", offset),
+            OggError::CrcMismatch { offset } => {
+                write!(f, "CRC mismatch of the Ogg page at offset {}", offset)
+            }
+            OggError::Truncated { offset } => {
+                write!(f, "Ogg stream truncated at offset {}", offset)
+            }
+            OggError::UnsupportedCodec => {
+                write!(
+                    f,
+                    "Unsupported Ogg codec, only Opus and Vorbis are supported"
+                )
+            }
+            OggError::InvalidHeader(message) => write!(f, "Invalid codec header: {}", message),
+            OggError::UnsupportedChannelMapping(family) => {
+                write!(f, "Unsupported Opus channel mapping family: {}", family)
+            }
+            OggError::Decode(message) => write!(f, "Error decoding audio: {}", message),
+            OggError::Io(kind) => write!(f, "Error reading audio: {}", kind),
+        }
+    }
+}
+
+impl Error for OggError {}
+
+impl From<io::Error> for OggError {
+    fn from(error: io::Error) -> Self {
+        OggError::Io(error.kind())
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct OggPacket {
+    pub data: Vec<u8>,
+    // The granule position of the page, set on the last packet completed on
+    // that page only. For audio this is the number of samples per channel
+    // decoded up to the end of the packet.
+    pub granule_position: Option<u64>,
+    // Set on the last packet of the logical stream.
+    pub end_of_stream: bool,
+}
+
+const CONTINUED_PACKET: u8 = 0x01;
+const END_OF_STREAM: u8 = 0x04;
+
+// Reads the packets of the first logical stream of an Ogg file page by page.
+// Pages of other multiplexed streams are skipped, a chained stream following
+// the first one is ignored.
+pub struct OggReader<R: Read> {
+    reader: R,
+    // The number of bytes read so far.
+    offset: u64,
+    serial: Option<u32>,
+    // A packet continued on the next page.
+    partial: Vec<u8>,
+    packets: VecDeque<OggPacket>,
+    finished: bool,
+}
+
+impl<R: Read> OggReader<R> {
+    pub fn new(reader: R) -> Self {
+        OggReader {
+            reader,
+            offset: 0,
+            serial: None,
+            partial: Vec::new(),
+            packets: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    pub fn read_packet(&mut self) -> Result<Option<OggPacket>, OggError> {
+        loop {
+            if let Some(packet) = self.packets.pop_front() {
+                return Ok(Some(packet));
+            }
+
+            if self.finished {
+                return Ok(None);
+            }
+
+            if let Err(error) = self.read_page() {
+                self.finished = true;
+                return Err(error);
+            }
+        }
+    }
+
+    fn read_page(&mut self) -> Result<(), OggError> {
+        let offset = self.offset;
+        let mut header = [0u8; 27];
+
+        let length = self.read_up_to(&mut header)?;
+        if length == 0 && self.serial.is_some() && self.partial.is_empty() {
+            // streams cut off without an end of stream page end here
+            self.finished = true;
+            return Ok(());
+        }
+        if length < header.len() {
+            return Err(OggError::Truncated { offset });
+        }
+
+        if &header[0..4] != b"OggS" || header[4] != 0 {
+            return Err(OggError::InvalidPage { offset });
+        }
+
+        let header_type = header[5];
+        let granule_position = u64::from_le_bytes(header[6..14].try_into().unwrap());
+        let serial = u32::from_le_bytes(header[14..18].try_into().unwrap());
+        let crc = u32::from_le_bytes(header[22..26].try_into().unwrap());
+
+        let mut lacing = vec![0u8; header[26] as usize];
+        let mut body = Vec::new();
+        if self.read_up_to(&mut lacing)? < lacing.len() {
+            return Err(OggError::Truncated { offset });
+        }
+        body.resize(lacing.iter().map(|lace| *lace as usize).sum(), 0);
+        if self.read_up_to(&mut body)? < body.len() {
+            return Err(OggError::Truncated { offset });
+        }
+
+        header[22..26].fill(0);
+        if crc32(&[&header[..], &lacing, &body].concat()) != crc {
+            return Err(OggError::CrcMismatch { offset });
+        }
+
+        if *self.serial.get_or_insert(serial) != serial {
+            return Ok(());
+        }
+
+        if (header_type & CONTINUED_PACKET != 0) == self.partial.is_empty() {
+            return Err(OggError::InvalidPage { offset });
+        }
+
+        let first_packet = self.packets.len();
+        let mut position = 0;
+
+        for lace in lacing {
+            self.partial
+                .extend_from_slice(&body[position..position + lace as usize]);
+            position += lace as usize;
+
+            // a lacing value of 255 continues the packet in the next segment
+            if lace < 255 {
+                self.packets.push_back(OggPacket {
+                    data: std::mem::take(&mut self.partial),
+                    granule_position: None,
+                    end_of_stream: false,
+                });
+            }
+        }
+
+        let is_end_of_stream = header_type & END_OF_STREAM != 0;
+
+        if let Some(last) = self.packets.iter_mut().skip(first_packet).last() {
+            // -1 marks pages on which no packet ends
+            last.granule_position = Some(granule_position).filter(|g| *g != u64::MAX);
+            last.end_of_stream = is_end_of_stream;
+        }
+
+        if is_end_of_stream {
+            self.finished = true;
+        }
+
+        Ok(())
+    }
+
+    // Fills the buffer unless the stream ends before and returns the number
+    // of bytes read.
+    fn read_up_to(&mut self, buffer: &mut [u8]) -> Result<usize, OggError> {
+        let mut length = 0;
+
+        while length < buffer.len() {
+            match self.reader.read(&mut buffer[length..]) {
+                Ok(0) => break,
+                Ok(read) => length += read,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        self.offset += length as u64;
+
+        Ok(length)
+    }
+}
+
+impl<R: Read> Iterator for OggReader<R> {
+    type Item = Result<OggPacket, OggError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_packet().transpose()
+    }
+}
+
+// Decodes a complete Ogg Opus or Ogg Vorbis stream into a wave of 32 bit
+// floats, the codec is recognized by its identification header.
+pub fn decode<R: Read>(reader: R) -> Result<RiffWave, OggError> {
+    let mut packets = OggReader::new(reader);
+    let first = packets
+        .read_packet()?
+        .ok_or(OggError::Truncated { offset: 0 })?;
+
+    if first.data.starts_with(b"OpusHead") {
+        opus::decode(&first.data, packets)
+    } else if first.data.starts_with(b"\x01vorbis") {
+        vorbis::decode(&first.data, packets)
+    } else {
+        Err(OggError::UnsupportedCodec)
+    }
+}
+
+// Drops the padding decoded from the last packet beyond the granule position
+// of the end of stream page. Streams cut off before that page are kept whole.
+pub fn trim_to_granule_position(
+    samples: &mut Vec<f32>,
+    num_channels: usize,
+    end_of_stream: Option<u64>,
+) {
+    if let Some(frames) = end_of_stream {
+        samples.truncate(samples.len().min(frames as usize * num_channels));
+    }
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+// The CRC-32 of the pages, with the polynomial 0x04C11DB7 and without
+// reflection or final XOR.
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = match crc & 0x80000000 {
+                0 => crc << 1,
+                _ => crc << 1 ^ 0x04C11DB7,
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |crc, byte| {
+        crc << 8 ^ CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::ogg::{crc32, decode, OggError, OggReader};
+    use std::io::Cursor;
+
+    const OPUS: &str = "./test_data/audio/ogg/opus_48k_stereo.opus";
+
+    #[test]
+    fn test_read_packets() {
+        let bytes = std::fs::read(OPUS).unwrap();
+
+        let packets: Vec<_> = OggReader::new(Cursor::new(bytes))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(packets.len(), 2 + 51);
+        assert!(packets[0].data.starts_with(b"OpusHead"));
+        assert_eq!(packets[0].granule_position, Some(0));
+        assert!(packets[1].data.starts_with(b"OpusTags"));
+
+        let last = packets.last().unwrap();
+        assert_eq!(last.granule_position, Some(48000 + 312));
+        assert!(last.end_of_stream);
+        assert_eq!(
+            packets.iter().filter(|packet| packet.end_of_stream).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_packet_across_pages() {
+        // a packet of 600 bytes takes three segments, the page ends after two
+        let packet: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let mut bytes = page(0x02, 0xFFFFFFFFFFFFFFFF, &[255, 255], &packet[..510]);
+        bytes.extend(page(0x05, 1234, &[90], &packet[510..]));
+
+        let packets: Vec<_> = OggReader::new(Cursor::new(bytes))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data, packet);
+        assert_eq!(packets[0].granule_position, Some(1234));
+    }
+
+    #[test]
+    fn test_invalid_pages() {
+        let bytes = std::fs::read(OPUS).unwrap();
+
+        let mut crc = bytes.clone();
+        crc[100] ^= 0x01;
+        assert_eq!(
+            OggReader::new(Cursor::new(crc)).find_map(Result::err),
+            Some(OggError::CrcMismatch { offset: 47 })
+        );
+
+        assert_eq!(
+            OggReader::new(Cursor::new(bytes[..1000].to_vec())).find_map(Result::err),
+            Some(OggError::Truncated { offset: 111 })
+        );
+
+        let continued = page(0x01, 0, &[10], &[0; 10]);
+        assert_eq!(
+            OggReader::new(Cursor::new(continued)).find_map(Result::err),
+            Some(OggError::InvalidPage { offset: 0 })
+        );
+
+        assert_eq!(
+            decode(Cursor::new(page(0x06, 0, &[8], b"\x7fFLAC\x01\x00\x01"))).err(),
+            Some(OggError::UnsupportedCodec)
+        );
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0x89A1897F);
+    }
+
+    fn page(header_type: u8, granule_position: u64, lacing: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS\0".to_vec();
+        page.push(header_type);
+        page.extend_from_slice(&granule_position.to_le_bytes());
+        page.extend_from_slice(&1u32.to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes());
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(lacing);
+        page.extend_from_slice(body);
+
+        let crc = crc32(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+
+        page
+    }
+}
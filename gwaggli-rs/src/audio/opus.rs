@@ -0,0 +1,226 @@
+use crate::audio::flac::parse_vorbis_comment;
+use crate::audio::ogg::{trim_to_granule_position, OggError, OggReader};
+use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveFormat};
+use audiopus::coder::Decoder;
+use audiopus::SampleRate;
+use std::io::Read;
+
+// Opus always decodes at 48 kHz, whatever the sample rate of the original.
+const SAMPLE_RATE: u32 = 48000;
+
+// The longest packet is 120 ms.
+const MAX_FRAMES_PER_PACKET: usize = 5760;
+
+// https://www.rfc-editor.org/rfc/rfc7845#section-5.1
+#[derive(PartialEq, Debug, Clone)]
+struct OpusHead {
+    num_channels: u8,
+    // The number of samples at 48 kHz to drop from the start.
+    pre_skip: u16,
+    // The gain to apply to the decoded audio in dB, in Q7.8 format.
+    output_gain: i16,
+}
+
+impl OpusHead {
+    fn new(bytes: &[u8]) -> Result<Self, OggError> {
+        if bytes.len() < 19 || !bytes.starts_with(b"OpusHead") {
+            return Err(OggError::InvalidHeader("OpusHead".to_string()));
+        }
+
+        // only the major version in the upper four bits breaks compatibility
+        if bytes[8] >> 4 != 0 {
+            return Err(OggError::InvalidHeader(format!(
+                "Opus version {}",
+                bytes[8]
+            )));
+        }
+
+        let num_channels = bytes[9];
+        let mapping_family = bytes[18];
+
+        // family 1 uses the Vorbis channel order, which is that of family 0 for
+        // mono and stereo as long as the channels are coded in one stream in
+        // that order, see https://www.rfc-editor.org/rfc/rfc7845#section-5.1.1.2
+        let single_stream = match mapping_family {
+            0 => true,
+            1 => {
+                let table = [1, num_channels.saturating_sub(1), 0, 1];
+                let length = 2 + num_channels.min(2) as usize;
+                bytes.get(19..19 + length) == Some(&table[..length])
+            }
+            _ => false,
+        };
+
+        if !single_stream || num_channels > 2 {
+            return Err(OggError::UnsupportedChannelMapping(mapping_family));
+        }
+        if num_channels == 0 {
+            return Err(OggError::InvalidHeader("0 channels".to_string()));
+        }
+
+        Ok(OpusHead {
+            num_channels,
+            pre_skip: u16::from_le_bytes([bytes[10], bytes[11]]),
+            output_gain: i16::from_le_bytes([bytes[16], bytes[17]]),
+        })
+    }
+}
+
+// Decodes the packets of an Ogg Opus stream following the identification
+// header into a wave of 32 bit floats at 48 kHz.
+pub fn decode<R: Read>(head: &[u8], mut packets: OggReader<R>) -> Result<RiffWave, OggError> {
+    let head = OpusHead::new(head)?;
+    let num_channels = head.num_channels as usize;
+
+    let tags = packets
+        .read_packet()?
+        .filter(|packet| packet.data.starts_with(b"OpusTags"))
+        .ok_or(OggError::InvalidHeader("OpusTags".to_string()))?;
+
+    let channels = match num_channels {
+        1 => audiopus::Channels::Mono,
+        _ => audiopus::Channels::Stereo,
+    };
+    let mut decoder = Decoder::new(SampleRate::Hz48000, channels).map_err(decode_error)?;
+
+    let mut buffer = vec![0f32; MAX_FRAMES_PER_PACKET * num_channels];
+    let mut samples = Vec::new();
+    let mut end_of_stream = None;
+
+    for packet in packets {
+        let packet = packet?;
+
+        if !packet.data.is_empty() {
+            let frames = decoder
+                .decode_float(Some(&packet.data[..]), &mut buffer, false)
+                .map_err(decode_error)?;
+
+            samples.extend_from_slice(&buffer[..frames * num_channels]);
+        }
+
+        if packet.end_of_stream {
+            end_of_stream = packet.granule_position;
+        }
+    }
+
+    // the granule positions include the pre-skip
+    let pre_skip = (head.pre_skip as usize * num_channels).min(samples.len());
+    samples.drain(..pre_skip);
+    trim_to_granule_position(
+        &mut samples,
+        num_channels,
+        end_of_stream.map(|granule| granule.saturating_sub(head.pre_skip as u64)),
+    );
+
+    if head.output_gain != 0 {
+        let gain = 10f32.powf(head.output_gain as f32 / 256.0 / 20.0);
+        samples.iter_mut().for_each(|sample| *sample *= gain);
+    }
+
+    let mut riff_wave = RiffWaveFormat::uncompressed(
+        AudioFormat::IeeeFloat,
+        Channels::new(num_channels as u16),
+        SAMPLE_RATE,
+        32,
+    )
+    .and_then(|format| RiffWave::from_f32(format, &samples))
+    .map_err(|error| OggError::Decode(error.to_string()))?;
+    riff_wave.info = parse_vorbis_comment(&tags.data[8..]);
+
+    Ok(riff_wave)
+}
+
+fn decode_error(error: audiopus::Error) -> OggError {
+    OggError::Decode(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::ogg::{decode, OggError};
+    use crate::audio::opus::OpusHead;
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{AudioFormat, Channels, RiffWaveReader};
+    use std::fs::File;
+
+    #[test]
+    fn test_decode_stereo() {
+        let riff_wave =
+            decode(File::open("./test_data/audio/ogg/opus_48k_stereo.opus").unwrap()).unwrap();
+
+        assert_eq!(riff_wave.format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(riff_wave.format.num_channels, Channels::Stereo);
+        assert_eq!(riff_wave.format.sample_rate, 48000);
+        assert_eq!(riff_wave.frames(), 48000);
+
+        let channels = riff_wave.channels_as_f32();
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+
+        // a sine of amplitude 0.5 on the left channel only
+        assert!((rms(&channels[0][4800..43200]) - 0.5 / 2f32.sqrt()).abs() < 0.02);
+        assert!(rms(&channels[1][4800..43200]) < 0.01);
+    }
+
+    #[test]
+    fn test_decode_mono() {
+        let wave = RiffWaveReader::new(
+            File::open("./test_data/audio/riff_wave/pcm_s16le_16k_mono.wav").unwrap(),
+        )
+        .and_then(RiffWaveReader::read_wave)
+        .unwrap();
+        let riff_wave =
+            decode(File::open("./test_data/audio/ogg/opus_16k_mono.opus").unwrap()).unwrap();
+
+        assert_eq!(riff_wave.format.num_channels, Channels::Mono);
+        assert_eq!(riff_wave.frames(), wave.frames() * 3);
+        assert_eq!(
+            riff_wave.info.get(InfoTag::Title),
+            Some("Mars and the Moon")
+        );
+    }
+
+    #[test]
+    fn test_opus_head() {
+        let mut head = b"OpusHead\x01\x02\x38\x01\x80\x3e\x00\x00\x00\x01\x00".to_vec();
+
+        assert_eq!(
+            OpusHead::new(&head),
+            Ok(OpusHead {
+                num_channels: 2,
+                pre_skip: 312,
+                output_gain: 256,
+            })
+        );
+
+        // a stereo stream of family 1, with one coupled stream in Vorbis order
+        head[18] = 1;
+        head.extend_from_slice(&[1, 1, 0, 1]);
+        assert_eq!(OpusHead::new(&head).unwrap().num_channels, 2);
+
+        head[21..23].copy_from_slice(&[1, 0]);
+        assert_eq!(
+            OpusHead::new(&head),
+            Err(OggError::UnsupportedChannelMapping(1))
+        );
+
+        head[9] = 3;
+        head.truncate(19);
+        head.extend_from_slice(&[2, 1, 0, 2, 1]);
+        assert_eq!(
+            OpusHead::new(&head),
+            Err(OggError::UnsupportedChannelMapping(1))
+        );
+
+        head[18] = 2;
+        assert_eq!(
+            OpusHead::new(&head),
+            Err(OggError::UnsupportedChannelMapping(2))
+        );
+
+        assert_eq!(
+            OpusHead::new(&head[..18]),
+            Err(OggError::InvalidHeader("OpusHead".to_string()))
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use crate::audio::filter::Filters;
+use crate::audio::loudness::integrated_loudness;
+use clap::ValueEnum;
+use std::fmt::Display;
+
+// How the level of a recording is adjusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Normalization {
+    // The highest sample, in dBFS.
+    Peak,
+    // The root mean square of all samples, in dBFS.
+    Rms,
+    // The integrated loudness according to EBU R 128, in LUFS.
+    Loudness,
+}
+
+impl Display for Normalization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Normalization::Peak => write!(f, "peak"),
+            Normalization::Rms => write!(f, "rms"),
+            Normalization::Loudness => write!(f, "loudness"),
+        }
+    }
+}
+
+impl Normalization {
+    pub fn default_target(&self) -> f32 {
+        match self {
+            Normalization::Peak => -1.0,
+            Normalization::Rms => -20.0,
+            Normalization::Loudness => -23.0,
+        }
+    }
+
+    fn unit(&self) -> &str {
+        match self {
+            Normalization::Loudness => "LUFS",
+            _ => "dBFS",
+        }
+    }
+
+    // The current level of the samples, or None if they are silent.
+    fn measure(&self, samples: &[f32], sample_rate: u32) -> Option<f32> {
+        let level = match self {
+            Normalization::Peak => to_db(peak(samples)),
+            Normalization::Rms => to_db(
+                (samples.iter().map(|s| s * s).sum::<f32>() / samples.len().max(1) as f32).sqrt(),
+            ),
+            Normalization::Loudness => integrated_loudness(samples, sample_rate)?,
+        };
+
+        Some(level).filter(|level| level.is_finite())
+    }
+}
+
+// The steps applied to the mono samples before the inference, in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Preprocessing {
+    pub remove_dc: bool,
+    pub filters: Filters,
+    pub trim_silence: bool,
+    // The normalization and its target level, see Normalization.
+    pub normalization: Option<(Normalization, f32)>,
+}
+
+impl Display for Preprocessing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut steps = Vec::new();
+
+        if self.remove_dc {
+            steps.push("DC offset removal".to_string());
+        }
+        if !self.filters.is_empty() {
+            steps.push(self.filters.to_string());
+        }
+        if self.trim_silence {
+            steps.push("silence trimming".to_string());
+        }
+        if let Some((normalization, target)) = self.normalization {
+            steps.push(format!(
+                "{} normalization to {} {}",
+                normalization,
+                target,
+                normalization.unit()
+            ));
+        }
+
+        write!(f, "{}", steps.join(", "))
+    }
+}
+
+// Frames quieter than this are silence when trimming.
+const SILENCE_DB: f32 = -50.0;
+
+// Kept before and after the sound when trimming, in seconds.
+const TRIM_MARGIN: f32 = 0.1;
+
+impl Preprocessing {
+    pub fn is_empty(&self) -> bool {
+        *self == Preprocessing::default()
+    }
+
+    // Processes the samples in place and returns the number of samples that
+    // were trimmed from the start.
+    pub fn apply(&self, samples: &mut Vec<f32>, sample_rate: u32) -> usize {
+        if self.remove_dc {
+            remove_dc(samples);
+        }
+
+        self.filters.apply(samples, sample_rate);
+
+        let trimmed = match self.trim_silence {
+            true => trim_silence(samples, sample_rate),
+            false => 0,
+        };
+
+        if let Some((normalization, target)) = self.normalization {
+            normalize(samples, sample_rate, normalization, target);
+        }
+
+        trimmed
+    }
+}
+
+fn remove_dc(samples: &mut [f32]) {
+    let mean = samples.iter().map(|s| *s as f64).sum::<f64>() / samples.len().max(1) as f64;
+
+    samples.iter_mut().for_each(|s| *s -= mean as f32);
+}
+
+// Removes the silence at the start and the end, unless everything is silent.
+fn trim_silence(samples: &mut Vec<f32>, sample_rate: u32) -> usize {
+    let frame_length = (sample_rate as usize / 100).max(1);
+    let is_sound = |frame: &[f32]| {
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32).sqrt();
+        to_db(rms) > SILENCE_DB
+    };
+
+    let frames: Vec<bool> = samples.chunks(frame_length).map(is_sound).collect();
+    let (Some(first), Some(last)) = (
+        frames.iter().position(|sound| *sound),
+        frames.iter().rposition(|sound| *sound),
+    ) else {
+        return 0;
+    };
+
+    let margin = (sample_rate as f32 * TRIM_MARGIN) as usize;
+    let start = (first * frame_length).saturating_sub(margin);
+    let end = ((last + 1) * frame_length + margin).min(samples.len());
+
+    samples.truncate(end);
+    samples.drain(..start);
+
+    start
+}
+
+// Scales the samples to the target level, but never beyond full scale.
+fn normalize(samples: &mut [f32], sample_rate: u32, normalization: Normalization, target: f32) {
+    let Some(level) = normalization.measure(samples, sample_rate) else {
+        return;
+    };
+
+    let gain = 10f32.powf((target - level) / 20.0).min(1.0 / peak(samples));
+
+    samples.iter_mut().for_each(|s| *s *= gain);
+}
+
+fn peak(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0, |peak, s| s.abs().max(peak))
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::filter::Filters;
+    use crate::audio::preprocess::{Normalization, Preprocessing};
+
+    fn sine(amplitude: f32, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|i| amplitude * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 16000.0).sin())
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, s| s.abs().max(peak))
+    }
+
+    #[test]
+    fn test_remove_dc() {
+        let mut samples: Vec<f32> = sine(0.5, 1600).iter().map(|s| s + 0.25).collect();
+        let testee = Preprocessing {
+            remove_dc: true,
+            ..Preprocessing::default()
+        };
+
+        testee.apply(&mut samples, 16000);
+
+        assert!(samples.iter().sum::<f32>().abs() < 1e-3);
+        assert!((peak(&samples) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_trim_silence() {
+        let mut samples = vec![0.0; 16000];
+        samples.extend(sine(0.5, 8000));
+        samples.extend(vec![0.0001; 32000]);
+        let testee = Preprocessing {
+            trim_silence: true,
+            ..Preprocessing::default()
+        };
+
+        let trimmed = testee.apply(&mut samples, 16000);
+
+        assert_eq!(trimmed, 14400);
+        assert_eq!(samples.len(), 1600 + 8000 + 1600);
+    }
+
+    #[test]
+    fn test_trim_silence_keeps_silent_recordings() {
+        let mut samples = vec![0.0; 16000];
+        let testee = Preprocessing {
+            trim_silence: true,
+            ..Preprocessing::default()
+        };
+
+        assert_eq!(testee.apply(&mut samples, 16000), 0);
+        assert_eq!(samples.len(), 16000);
+    }
+
+    #[test]
+    fn test_normalize_peak_and_rms() {
+        let mut samples = sine(0.01, 16000);
+        let testee = Preprocessing {
+            normalization: Some((Normalization::Peak, -6.0)),
+            ..Preprocessing::default()
+        };
+
+        testee.apply(&mut samples, 16000);
+
+        assert!((peak(&samples) - 0.501).abs() < 1e-3);
+
+        let testee = Preprocessing {
+            normalization: Some((Normalization::Rms, -20.0)),
+            ..Preprocessing::default()
+        };
+
+        testee.apply(&mut samples, 16000);
+
+        assert!((peak(&samples) - 0.1414).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_normalize_loudness() {
+        let mut samples = sine(0.01, 32000);
+        let testee = Preprocessing {
+            normalization: Some((Normalization::Loudness, -23.0)),
+            ..Preprocessing::default()
+        };
+
+        testee.apply(&mut samples, 16000);
+
+        assert!((peak(&samples) - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_normalize_does_not_clip() {
+        let mut samples = sine(0.1, 16000);
+        samples[100] = 0.5;
+        let testee = Preprocessing {
+            normalization: Some((Normalization::Rms, 0.0)),
+            ..Preprocessing::default()
+        };
+
+        testee.apply(&mut samples, 16000);
+
+        assert!((peak(&samples) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_display() {
+        let testee = Preprocessing {
+            remove_dc: true,
+            filters: Filters {
+                high_pass: Some(80.0),
+                ..Filters::default()
+            },
+            trim_silence: false,
+            normalization: Some((Normalization::Loudness, -23.0)),
+        };
+
+        assert_eq!(
+            testee.to_string(),
+            "DC offset removal, high-pass at 80Hz, loudness normalization to -23 LUFS"
+        );
+        assert!(Preprocessing::default().is_empty());
+    }
+}
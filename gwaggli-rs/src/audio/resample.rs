@@ -0,0 +1,410 @@
+use crate::audio::riff_wave::deinterleave;
+use clap::ValueEnum;
+use std::f64::consts::PI;
+use std::fmt::Display;
+
+// The trade-off between speed and accuracy of the resampling filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ResampleQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl Display for ResampleQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResampleQuality::Low => write!(f, "low"),
+            ResampleQuality::Medium => write!(f, "medium"),
+            ResampleQuality::High => write!(f, "high"),
+        }
+    }
+}
+
+impl ResampleQuality {
+    // The number of filter taps on each side of a sample, the beta of the
+    // Kaiser window and the cutoff relative to the lower Nyquist frequency.
+    fn filter(&self) -> (usize, f64, f64) {
+        match self {
+            ResampleQuality::Low => (8, 5.0, 0.85),
+            ResampleQuality::Medium => (16, 8.0, 0.9),
+            ResampleQuality::High => (32, 10.0, 0.94),
+        }
+    }
+}
+
+// The upper bound of filter phases; ratios needing more phases use the nearest
+// one, which is below the accuracy of the filter at this resolution.
+const MAX_PHASES: u64 = 4096;
+
+// Band limited resampling with a polyphase Kaiser windowed sinc filter.
+// https://ccrma.stanford.edu/~jos/resample/
+pub struct Resampler {
+    // The distance between two output samples in input samples is
+    // step_in / step_out, both reduced by their greatest common divisor.
+    step_in: u64,
+    step_out: u64,
+    half_taps: usize,
+    phases: u64,
+    // The taps of all phases, one row of 2 * half_taps taps per phase.
+    table: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(from_rate: u32, to_rate: u32, quality: ResampleQuality) -> Self {
+        let from_rate = from_rate.max(1) as u64;
+        let to_rate = to_rate.max(1) as u64;
+        let divisor = gcd(from_rate, to_rate);
+        let step_in = from_rate / divisor;
+        let step_out = to_rate / divisor;
+
+        let (half_taps, beta, cutoff) = quality.filter();
+        // the filter removes everything above the Nyquist frequency of the
+        // lower of both rates
+        let cutoff = cutoff * (step_out as f64 / step_in as f64).min(1.0);
+        let phases = step_out.min(MAX_PHASES);
+        let taps = 2 * half_taps;
+
+        let mut table = Vec::with_capacity(phases as usize * taps);
+
+        for phase in 0..phases {
+            let fraction = phase as f64 / phases as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|tap| {
+                    let x = tap as f64 - (half_taps - 1) as f64 - fraction;
+                    cutoff * sinc(cutoff * x) * kaiser(x / half_taps as f64, beta)
+                })
+                .collect();
+            // a gain of exactly 1 for constant signals
+            let sum: f64 = row.iter().sum();
+
+            table.extend(row.iter().map(|tap| (tap / sum) as f32));
+        }
+
+        Resampler {
+            step_in,
+            step_out,
+            half_taps,
+            phases,
+            table,
+        }
+    }
+
+    // The number of output samples for the given number of input samples.
+    pub fn output_len(&self, input_len: usize) -> usize {
+        (input_len as u64 * self.step_out).div_ceil(self.step_in) as usize
+    }
+
+    // Resamples the samples of a single channel.
+    pub fn process(&self, samples: &[f32]) -> Vec<f32> {
+        if self.step_in == self.step_out {
+            return samples.to_vec();
+        }
+
+        (0..self.output_len(samples.len()) as u64)
+            .map(|n| self.output_sample(n, samples, 0))
+            .collect()
+    }
+
+    // Resamples a single channel given block by block, so it does not have to
+    // be kept in memory as a whole.
+    pub fn stream(self) -> ResamplerStream {
+        ResamplerStream {
+            resampler: self,
+            pending: Vec::new(),
+            offset: 0,
+            input_len: 0,
+            output_len: 0,
+        }
+    }
+
+    // The index of the first input sample under the filter of output sample n
+    // and the phase of the filter.
+    fn filter_position(&self, n: u64) -> (i64, usize) {
+        let position = n * self.step_in;
+        let mut index = position / self.step_out;
+        let mut phase =
+            ((position % self.step_out) * self.phases + self.step_out / 2) / self.step_out;
+
+        if phase == self.phases {
+            index += 1;
+            phase = 0;
+        }
+
+        (index as i64 - (self.half_taps as i64 - 1), phase as usize)
+    }
+
+    // Output sample n, computed from the input samples starting at the input
+    // index offset.
+    fn output_sample(&self, n: u64, samples: &[f32], offset: u64) -> f32 {
+        let taps = 2 * self.half_taps;
+        let (first, phase) = self.filter_position(n);
+        let row = &self.table[phase * taps..(phase + 1) * taps];
+        let first = first - offset as i64;
+
+        if first >= 0 && first as usize + taps <= samples.len() {
+            let window = &samples[first as usize..first as usize + taps];
+            return window.iter().zip(row).map(|(s, t)| s * t).sum();
+        }
+
+        // near the edges, the samples outside of the signal are 0
+        row.iter()
+            .enumerate()
+            .filter_map(|(tap, t)| {
+                let i = first + tap as i64;
+                (i >= 0 && (i as usize) < samples.len()).then(|| samples[i as usize] * t)
+            })
+            .sum()
+    }
+}
+
+// A single channel being resampled block by block. The output is the same as
+// of Resampler::process on all blocks at once.
+pub struct ResamplerStream {
+    resampler: Resampler,
+    // The input samples the filter of the next output sample may still reach.
+    pending: Vec<f32>,
+    // The index of the first pending sample in the input.
+    offset: u64,
+    input_len: u64,
+    output_len: u64,
+}
+
+impl ResamplerStream {
+    // Adds the next block of input samples and returns the output samples
+    // which are complete with it.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.input_len += samples.len() as u64;
+
+        if self.resampler.step_in == self.resampler.step_out {
+            self.output_len = self.input_len;
+            return samples.to_vec();
+        }
+
+        self.pending.extend_from_slice(samples);
+
+        let taps = 2 * self.resampler.half_taps as i64;
+        let mut output = Vec::new();
+
+        loop {
+            let (first, _) = self.resampler.filter_position(self.output_len);
+
+            if first + taps > self.input_len as i64 {
+                // the filter only moves forward, the samples before it are done
+                let done = (first.max(0) as u64 - self.offset).min(self.pending.len() as u64);
+                self.pending.drain(..done as usize);
+                self.offset += done;
+
+                return output;
+            }
+
+            output.push(
+                self.resampler
+                    .output_sample(self.output_len, &self.pending, self.offset),
+            );
+            self.output_len += 1;
+        }
+    }
+
+    // Returns the remaining output samples at the end of the input.
+    pub fn finish(self) -> Vec<f32> {
+        (self.output_len..self.resampler.output_len(self.input_len as usize) as u64)
+            .map(|n| self.resampler.output_sample(n, &self.pending, self.offset))
+            .collect()
+    }
+}
+
+// Resamples interleaved samples of any number of channels.
+pub fn resample(
+    samples: &[f32],
+    num_channels: usize,
+    from_rate: u32,
+    to_rate: u32,
+    quality: ResampleQuality,
+) -> Vec<f32> {
+    let resampler = Resampler::new(from_rate, to_rate, quality);
+
+    if num_channels <= 1 {
+        return resampler.process(samples);
+    }
+
+    let channels: Vec<Vec<f32>> = deinterleave(samples, num_channels)
+        .iter()
+        .map(|channel| resampler.process(channel))
+        .collect();
+    let frames = channels.first().map_or(0, Vec::len);
+
+    (0..frames)
+        .flat_map(|frame| channels.iter().map(move |channel| channel[frame]))
+        .collect()
+}
+
+fn sinc(x: f64) -> f64 {
+    match x == 0.0 {
+        true => 1.0,
+        false => (PI * x).sin() / (PI * x),
+    }
+}
+
+// The Kaiser window for x in [-1, 1].
+fn kaiser(x: f64, beta: f64) -> f64 {
+    match x.abs() < 1.0 {
+        true => bessel_i0(beta * (1.0 - x * x).sqrt()) / bessel_i0(beta),
+        false => 0.0,
+    }
+}
+
+// The modified Bessel function of the first kind of order 0, as power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+
+    while term > sum * 1e-12 {
+        term *= (x / (2.0 * k)) * (x / (2.0 * k));
+        sum += term;
+        k += 1.0;
+    }
+
+    sum
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::resample::{resample, ResampleQuality, Resampler};
+
+    fn sine(sample_rate: u32, frequency: f64, length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|i| {
+                (2.0 * std::f64::consts::PI * frequency * i as f64 / sample_rate as f64).sin()
+                    as f32
+            })
+            .collect()
+    }
+
+    // The largest difference between both signals, ignoring the edges where
+    // the filter runs out of samples.
+    fn max_error(actual: &[f32], expected: &[f32], edge: usize) -> f32 {
+        actual[edge..actual.len() - edge]
+            .iter()
+            .zip(&expected[edge..])
+            .map(|(a, e)| (a - e).abs())
+            .fold(0.0, f32::max)
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_output_length() {
+        for (from_rate, length) in [(8000, 8000), (16000, 16000), (44100, 44100), (48000, 48000)] {
+            let testee = Resampler::new(from_rate, 16000, ResampleQuality::Low);
+
+            assert_eq!(testee.process(&vec![0.0; length]).len(), 16000);
+        }
+
+        assert_eq!(
+            Resampler::new(44100, 16000, ResampleQuality::Low).output_len(1000),
+            363
+        );
+    }
+
+    #[test]
+    fn test_same_rate() {
+        let samples = sine(16000, 440.0, 100);
+
+        assert_eq!(
+            Resampler::new(16000, 16000, ResampleQuality::High).process(&samples),
+            samples
+        );
+    }
+
+    #[test]
+    fn test_downsample_sine() {
+        for (from_rate, quality, tolerance) in [
+            (48000, ResampleQuality::Low, 0.02),
+            (48000, ResampleQuality::Medium, 0.002),
+            (44100, ResampleQuality::High, 0.001),
+        ] {
+            let testee = Resampler::new(from_rate, 16000, quality);
+
+            let actual = testee.process(&sine(from_rate, 1000.0, from_rate as usize / 10));
+
+            assert!(
+                max_error(&actual, &sine(16000, 1000.0, 1600), 64) < tolerance,
+                "{} {:?}",
+                from_rate,
+                quality
+            );
+        }
+    }
+
+    #[test]
+    fn test_upsample_sine() {
+        let testee = Resampler::new(8000, 16000, ResampleQuality::Medium);
+
+        let actual = testee.process(&sine(8000, 1000.0, 800));
+
+        assert!(max_error(&actual, &sine(16000, 1000.0, 1600), 64) < 0.002);
+    }
+
+    #[test]
+    fn test_aliasing_is_suppressed() {
+        // 12kHz is above the Nyquist frequency of 16kHz and would alias to 4kHz
+        for (quality, limit) in [
+            (ResampleQuality::Low, 0.05),
+            (ResampleQuality::Medium, 0.005),
+            (ResampleQuality::High, 0.001),
+        ] {
+            let testee = Resampler::new(48000, 16000, quality);
+
+            let actual = testee.process(&sine(48000, 12000.0, 4800));
+
+            assert!(rms(&actual[64..1536]) < limit, "{:?}", quality);
+        }
+    }
+
+    #[test]
+    fn test_stream() {
+        for (from_rate, block_size) in [(44100, 1000), (8000, 7), (48000, 1), (16000, 100)] {
+            let samples = sine(from_rate, 1000.0, 5000);
+            let resampler = Resampler::new(from_rate, 16000, ResampleQuality::Medium);
+            let expected = resampler.process(&samples);
+            let mut testee = resampler.stream();
+
+            let mut actual: Vec<f32> = samples
+                .chunks(block_size)
+                .flat_map(|block| testee.push(block))
+                .collect();
+            actual.extend(testee.finish());
+
+            assert_eq!(actual, expected, "{}", from_rate);
+        }
+    }
+
+    #[test]
+    fn test_dc_gain() {
+        let actual = Resampler::new(44100, 16000, ResampleQuality::Medium).process(&[0.5; 4410]);
+
+        assert!(max_error(&actual, &[0.5; 1600], 64) < 1e-5);
+    }
+
+    #[test]
+    fn test_resample_interleaved() {
+        let left = sine(8000, 500.0, 800);
+        let samples: Vec<f32> = left.iter().flat_map(|s| [*s, -*s]).collect();
+
+        let actual = resample(&samples, 2, 8000, 16000, ResampleQuality::Medium);
+
+        assert_eq!(actual.len(), 3200);
+        assert_eq!(actual[1001], -actual[1000]);
+    }
+}
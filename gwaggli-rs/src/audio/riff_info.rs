@@ -0,0 +1,252 @@
+use crate::audio::riff_wave::RiffWaveError;
+use std::fmt::Display;
+
+// The well known tags of a LIST/INFO chunk, see
+// https://www.recordingblogs.com/wiki/list-chunk-of-a-wave-file
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum InfoTag {
+    Title,
+    Artist,
+    Album,
+    CreationDate,
+    Comment,
+    Copyright,
+    Genre,
+    Keywords,
+    Subject,
+    Engineer,
+    Software,
+    Source,
+    TrackNumber,
+    Other([u8; 4]),
+}
+
+const KNOWN_TAGS: [(InfoTag, &[u8; 4]); 13] = [
+    (InfoTag::Title, b"INAM"),
+    (InfoTag::Artist, b"IART"),
+    (InfoTag::Album, b"IPRD"),
+    (InfoTag::CreationDate, b"ICRD"),
+    (InfoTag::Comment, b"ICMT"),
+    (InfoTag::Copyright, b"ICOP"),
+    (InfoTag::Genre, b"IGNR"),
+    (InfoTag::Keywords, b"IKEY"),
+    (InfoTag::Subject, b"ISBJ"),
+    (InfoTag::Engineer, b"IENG"),
+    (InfoTag::Software, b"ISFT"),
+    (InfoTag::Source, b"ISRC"),
+    (InfoTag::TrackNumber, b"ITRK"),
+];
+
+impl InfoTag {
+    pub fn from_id(id: [u8; 4]) -> Self {
+        KNOWN_TAGS
+            .iter()
+            .find(|(_, known_id)| **known_id == id)
+            .map_or(InfoTag::Other(id), |(tag, _)| *tag)
+    }
+
+    pub fn id(&self) -> [u8; 4] {
+        match self {
+            InfoTag::Other(id) => *id,
+            tag => *KNOWN_TAGS
+                .iter()
+                .find(|(known_tag, _)| known_tag == tag)
+                .map(|(_, id)| *id)
+                .unwrap(),
+        }
+    }
+}
+
+impl Display for InfoTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InfoTag::Title => write!(f, "Title"),
+            InfoTag::Artist => write!(f, "Artist"),
+            InfoTag::Album => write!(f, "Album"),
+            InfoTag::CreationDate => write!(f, "Creation date"),
+            InfoTag::Comment => write!(f, "Comment"),
+            InfoTag::Copyright => write!(f, "Copyright"),
+            InfoTag::Genre => write!(f, "Genre"),
+            InfoTag::Keywords => write!(f, "Keywords"),
+            InfoTag::Subject => write!(f, "Subject"),
+            InfoTag::Engineer => write!(f, "Engineer"),
+            InfoTag::Software => write!(f, "Software"),
+            InfoTag::Source => write!(f, "Source"),
+            InfoTag::TrackNumber => write!(f, "Track"),
+            InfoTag::Other(id) => write!(f, "{}", String::from_utf8_lossy(id)),
+        }
+    }
+}
+
+// The metadata of the LIST/INFO chunks of a file, in the order of the file.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct RiffInfo {
+    entries: Vec<(InfoTag, String)>,
+}
+
+impl RiffInfo {
+    // Parses the body of a LIST chunk of type INFO, starting after the list type.
+    // Later values replace earlier ones with the same tag.
+    pub fn parse(&mut self, offset: u64, bytes: &[u8]) -> Result<(), RiffWaveError> {
+        let mut position = 0;
+
+        while position < bytes.len() {
+            let header = bytes.get(position..position + 8).ok_or_else(|| {
+                RiffWaveError::TruncatedHeader {
+                    chunk_id: "INFO".to_string(),
+                    expected: 8,
+                    actual: bytes.len() - position,
+                }
+            })?;
+            let id: [u8; 4] = header[0..4].try_into().unwrap();
+            let size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+            let value = bytes
+                .get(position + 8..)
+                .and_then(|rest| rest.get(..size))
+                .ok_or_else(|| RiffWaveError::ChunkOverrun {
+                    chunk_id: String::from_utf8_lossy(&id).to_string(),
+                    offset: offset as usize + position,
+                    size: size as u64,
+                    available: bytes.len() - position - 8,
+                })?;
+
+            self.set(InfoTag::from_id(id), decode_text(value));
+            position += 8 + size + size % 2;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn get(&self, tag: InfoTag) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(entry_tag, _)| *entry_tag == tag)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // Sets the value of a tag, keeping its position if it is already present.
+    pub fn set(&mut self, tag: InfoTag, value: String) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|(entry_tag, _)| *entry_tag == tag)
+        {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((tag, value)),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(InfoTag, String)> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The complete LIST chunk including its header, or nothing if there are no
+    // entries.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let mut body = b"INFO".to_vec();
+
+        for (tag, value) in &self.entries {
+            // values are zero terminated
+            let size = value.len() + 1;
+
+            body.extend_from_slice(&tag.id());
+            body.extend_from_slice(&(size as u32).to_le_bytes());
+            body.extend_from_slice(value.as_bytes());
+            body.push(0);
+
+            if size % 2 == 1 {
+                body.push(0);
+            }
+        }
+
+        let mut bytes = b"LIST".to_vec();
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&body);
+
+        bytes
+    }
+}
+
+// Values are zero terminated (and often zero padded) strings. They are meant to
+// be ASCII, but UTF-8 is common and anything else is most likely Latin-1.
+pub fn decode_text(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    let bytes = &bytes[..end];
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::riff_info::{InfoTag, RiffInfo};
+    use crate::audio::riff_wave::RiffWaveError;
+
+    #[test]
+    fn test_parse() {
+        let bytes = b"INAM\x06\x00\x00\x00Title\x00IART\x03\x00\x00\x00Bob\x00ICRD\x0b\x00\x00\x002023-10-01\x00\x00";
+        let mut testee = RiffInfo::default();
+
+        testee.parse(0, bytes).unwrap();
+
+        assert_eq!(testee.get(InfoTag::Title), Some("Title"));
+        assert_eq!(testee.get(InfoTag::Artist), Some("Bob"));
+        assert_eq!(testee.get(InfoTag::CreationDate), Some("2023-10-01"));
+        assert_eq!(testee.get(InfoTag::Comment), None);
+    }
+
+    #[test]
+    fn test_parse_unknown_tag_and_latin1() {
+        let mut testee = RiffInfo::default();
+
+        testee.parse(0, b"IXYZ\x06\x00\x00\x00Z\xfcrich").unwrap();
+
+        assert_eq!(testee.get(InfoTag::Other(*b"IXYZ")), Some("Zürich"));
+        assert_eq!(InfoTag::from_id(*b"ISFT"), InfoTag::Software);
+        assert_eq!(InfoTag::Software.id(), *b"ISFT");
+    }
+
+    #[test]
+    fn test_parse_overrun() {
+        let mut testee = RiffInfo::default();
+
+        assert_eq!(
+            testee.parse(100, b"INAM\x10\x00\x00\x00Title\x00"),
+            Err(RiffWaveError::ChunkOverrun {
+                chunk_id: "INAM".to_string(),
+                offset: 100,
+                size: 16,
+                available: 6,
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_bytes_round_trip() {
+        let mut testee = RiffInfo::default();
+        testee.set(InfoTag::Title, "Meeting".to_string());
+        testee.set(InfoTag::Comment, "Room 3".to_string());
+        testee.set(InfoTag::Title, "Standup".to_string());
+
+        let bytes = testee.to_bytes();
+        let mut parsed = RiffInfo::default();
+        parsed.parse(0, &bytes[12..]).unwrap();
+
+        assert_eq!(&bytes[0..4], b"LIST");
+        assert_eq!(bytes.len() % 2, 0);
+        assert_eq!(parsed, testee);
+        assert_eq!(parsed.iter().next().unwrap().1, "Standup");
+        assert!(RiffInfo::default().to_bytes().is_empty());
+    }
+}
@@ -1,10 +1,144 @@
+use crate::audio::adpcm::{
+    decode_ima_adpcm, decode_ms_adpcm, i16_vec_to_f32_vec, MS_STANDARD_COEFFICIENTS,
+};
+use crate::audio::bext::BroadcastExtension;
+use crate::audio::cue::Markers;
+use crate::audio::g711::{alaw_vec_to_f32_vec, i16_to_alaw, i16_to_mulaw, mulaw_vec_to_f32_vec};
+use crate::audio::riff_info::RiffInfo;
+use std::borrow::Cow;
+use std::error::Error;
 use std::fmt::Display;
+use std::io;
+use std::io::{Cursor, Read, Write};
 use std::str::from_utf8;
+use std::time::Duration;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum RiffWaveError {
+    // The header of a chunk (or the fields of a fmt or ds64 chunk) is cut off
+    // before all of its fields could be read.
+    TruncatedHeader {
+        chunk_id: String,
+        expected: usize,
+        actual: usize,
+    },
+    // A chunk declares more bytes than there are left in the file.
+    ChunkOverrun {
+        chunk_id: String,
+        offset: usize,
+        size: u64,
+        available: usize,
+    },
+    // A chunk id which is not made of printable ASCII characters.
+    InvalidChunkId {
+        offset: usize,
+    },
+    // A chunk required to read the audio data (fmt, data or ds64) is missing.
+    MissingChunk(String),
+    // The file is not a RIFF/RF64/BW64 container or does not contain WAVE data.
+    UnsupportedContainer(String),
+    UnsupportedAudioFormat(u16),
+    UnsupportedSubFormat([u8; 16]),
+    UnsupportedChannels(u16),
+    // A sample rate too high to express the byte rate in 32 bits.
+    UnsupportedSampleRate(u32),
+    UnsupportedBitsPerSample {
+        audio_format: AudioFormat,
+        bits_per_sample: u16,
+    },
+    // A labl, note or ltxt chunk of a cue point missing in the cue chunk.
+    MissingCuePoint(u32),
+    // A chunk size left as a placeholder by a writer, which did not know the
+    // final size. Reported as a warning in lenient mode only.
+    PlaceholderChunkSize {
+        chunk_id: String,
+        offset: usize,
+        size: u64,
+    },
+    // Reading from the underlying stream failed.
+    Io(io::ErrorKind),
+}
+
+impl Display for RiffWaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiffWaveError::TruncatedHeader {
+                chunk_id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Truncated '{}' header: expected {} bytes, got {}",
+                chunk_id, expected, actual
+            ),
+            RiffWaveError::ChunkOverrun {
+                chunk_id,
+                offset,
+                size,
+                available,
+            } => write!(
+                f,
+                "Chunk '{}' at offset {} declares {} bytes, but only {} are available",
+                chunk_id, offset, size, available
+            ),
+            RiffWaveError::InvalidChunkId { offset } => {
+                write!(f, "Invalid chunk id at offset {}", offset)
+            }
+            RiffWaveError::MissingChunk(chunk_id) => write!(f, "Missing '{}' chunk", chunk_id),
+            RiffWaveError::UnsupportedContainer(container) => {
+                write!(f, "Unsupported container: {}", container)
+            }
+            RiffWaveError::UnsupportedAudioFormat(format_tag) => {
+                write!(f, "Unsupported audio format: {}", format_tag)
+            }
+            RiffWaveError::UnsupportedSubFormat(sub_format) => {
+                write!(f, "Unsupported sub format: {:02x?}", sub_format)
+            }
+            RiffWaveError::UnsupportedChannels(num_channels) => {
+                write!(f, "Unsupported number of channels: {}", num_channels)
+            }
+            RiffWaveError::UnsupportedSampleRate(sample_rate) => {
+                write!(f, "Unsupported sample rate: {}", sample_rate)
+            }
+            RiffWaveError::UnsupportedBitsPerSample {
+                audio_format,
+                bits_per_sample,
+            } => write!(
+                f,
+                "Unsupported bits per sample for {}: {}",
+                audio_format, bits_per_sample
+            ),
+            RiffWaveError::MissingCuePoint(id) => {
+                write!(f, "Missing cue point {} of associated data", id)
+            }
+            RiffWaveError::PlaceholderChunkSize {
+                chunk_id,
+                offset,
+                size,
+            } => write!(
+                f,
+                "Chunk '{}' at offset {} has the placeholder size {:#x}",
+                chunk_id, offset, size
+            ),
+            RiffWaveError::Io(kind) => write!(f, "Error reading audio: {}", kind),
+        }
+    }
+}
+
+impl Error for RiffWaveError {}
+
+impl From<io::Error> for RiffWaveError {
+    fn from(error: io::Error) -> Self {
+        RiffWaveError::Io(error.kind())
+    }
+}
 
 // https://tech.ebu.ch/docs/tech/tech3285.pdf
+#[derive(Clone)]
 pub struct RiffWaveFormat {
-    // The audio format. This is PCM = 1 (i.e. Linear quantization). Values other
-    // than 1 indicate some form of compression.
+    // The audio format. This is PCM = 1 (i.e. Linear quantization) or IEEE
+    // float = 3. Other values indicate some form of compression, i.e. MS ADPCM
+    // = 2, A-law = 6, µ-law = 7 and IMA ADPCM = 0x11.
     pub audio_format: AudioFormat,
     // The number of channels represented in the waveform data: 1 for mono or 2 for
     // stereo.
@@ -23,51 +157,434 @@ pub struct RiffWaveFormat {
     // The number of bits used to represent each sample of a single channel of audio.
     // I.e. 8 bits for 8-bit mono, 16 bits for 16-bit stereo etc.
     pub bits_per_sample: u16,
+    // Additional fields of the WAVE_FORMAT_EXTENSIBLE format (format tag 0xFFFE).
+    // The audio format above is resolved from its sub format GUID.
+    pub extensible: Option<RiffWaveFormatExtensible>,
+    // Additional fields of the ADPCM formats, required to decode their blocks.
+    pub adpcm: Option<RiffWaveFormatAdpcm>,
 }
 
 impl RiffWaveFormat {
-    fn new(bytes: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+    fn new(bytes: &[u8]) -> Result<Self, RiffWaveError> {
+        let header = ChunkReader::new("fmt ", bytes);
+        let format_tag = header.u16(0)?;
+
+        let extensible = match format_tag {
+            WAVE_FORMAT_EXTENSIBLE => Some(RiffWaveFormatExtensible::new(&header)?),
+            _ => None,
+        };
+
+        let audio_format_tag = match &extensible {
+            Some(extensible) => extensible
+                .sub_format_tag()
+                .ok_or(RiffWaveError::UnsupportedSubFormat(extensible.sub_format))?,
+            None => format_tag,
+        };
+
+        let audio_format = match audio_format_tag {
+            1 => AudioFormat::Pcm,
+            2 => AudioFormat::MsAdpcm,
+            3 => AudioFormat::IeeeFloat,
+            6 => AudioFormat::ALaw,
+            7 => AudioFormat::MuLaw,
+            0x11 => AudioFormat::ImaAdpcm,
+            _ => return Err(RiffWaveError::UnsupportedAudioFormat(audio_format_tag)),
+        };
+
+        let num_channels = match header.u16(2)? {
+            0 => return Err(RiffWaveError::UnsupportedChannels(0)),
+            count => Channels::new(count),
+        };
+
+        let bits_per_sample = header.u16(14)?;
+
+        let supported_bits_per_sample = match audio_format {
+            AudioFormat::Pcm => matches!(bits_per_sample, 8 | 16 | 24 | 32),
+            AudioFormat::IeeeFloat => matches!(bits_per_sample, 32 | 64),
+            _ => true,
+        };
+
+        if !supported_bits_per_sample {
+            return Err(RiffWaveError::UnsupportedBitsPerSample {
+                audio_format,
+                bits_per_sample,
+            });
+        }
+
+        let adpcm = match audio_format {
+            AudioFormat::MsAdpcm | AudioFormat::ImaAdpcm => {
+                Some(RiffWaveFormatAdpcm::new(&audio_format, &header)?)
+            }
+            _ => None,
+        };
+
+        Ok(RiffWaveFormat {
+            audio_format,
+            num_channels,
+            sample_rate: header.u32(4)?,
+            byte_rate: header.u32(8)?,
+            block_align: header.u16(12)?,
+            bits_per_sample,
+            extensible,
+            adpcm,
+        })
+    }
+
+    // Creates an uncompressed format (PCM, IEEE float, A-law or µ-law), deriving
+    // block_align and byte_rate from the other fields. Fails for ADPCM, which
+    // needs the parameters of its fmt chunk, and if the fields do not fit into
+    // their 16 and 32 bits.
+    pub fn uncompressed(
+        audio_format: AudioFormat,
+        num_channels: Channels,
+        sample_rate: u32,
+        bits_per_sample: u16,
+    ) -> Result<Self, RiffWaveError> {
+        if matches!(audio_format, AudioFormat::MsAdpcm | AudioFormat::ImaAdpcm) {
+            return Err(RiffWaveError::UnsupportedAudioFormat(audio_format as u16));
+        }
+
+        let block_align = num_channels
+            .count()
+            .checked_mul(bits_per_sample.div_ceil(8))
+            .ok_or(RiffWaveError::UnsupportedChannels(num_channels.count()))?;
+        let byte_rate = sample_rate
+            .checked_mul(block_align as u32)
+            .ok_or(RiffWaveError::UnsupportedSampleRate(sample_rate))?;
+
         Ok(RiffWaveFormat {
-            audio_format: match as_u16_le(bytes[0..2].try_into().unwrap()) {
-                1 => AudioFormat::Pcm,
-                _ => panic!(
-                    "Unsupported audio format: {}",
-                    as_u16_le(bytes[0..2].try_into().unwrap())
-                ),
+            audio_format,
+            num_channels,
+            sample_rate,
+            byte_rate,
+            block_align,
+            bits_per_sample,
+            extensible: None,
+            adpcm: None,
+        })
+    }
+
+    // The number of bits per sample that actually carry information. This is
+    // equal to bits_per_sample unless an extensible format declares less.
+    pub fn valid_bits_per_sample(&self) -> u16 {
+        match &self.extensible {
+            Some(extensible) if extensible.valid_bits_per_sample > 0 => {
+                extensible.valid_bits_per_sample.min(self.bits_per_sample)
+            }
+            _ => self.bits_per_sample,
+        }
+    }
+
+    // The number of bytes the data is read and decoded in. This is one frame
+    // for uncompressed formats and block_align bytes for ADPCM.
+    pub fn block_size(&self) -> usize {
+        match self.audio_format {
+            AudioFormat::MsAdpcm | AudioFormat::ImaAdpcm => (self.block_align as usize).max(1),
+            _ => {
+                self.num_channels.count() as usize
+                    * (self.bits_per_sample as usize).div_ceil(8).max(1)
+            }
+        }
+    }
+
+    // The number of frames decoded from a block of block_size() bytes.
+    pub fn frames_per_block(&self) -> usize {
+        match &self.adpcm {
+            Some(adpcm) => (adpcm.samples_per_block as usize).max(1),
+            None => 1,
+        }
+    }
+
+    // The first frame of the block the given frame is decoded from.
+    pub fn block_start(&self, frame: u64) -> u64 {
+        frame - frame % self.frames_per_block() as u64
+    }
+
+    // The playing time of the given number of frames.
+    pub fn duration_of(&self, frames: u64) -> Duration {
+        Duration::from_nanos(
+            (frames as u128 * 1_000_000_000 / self.sample_rate.max(1) as u128) as u64,
+        )
+    }
+
+    // Encodes interleaved samples in [-1.0, 1.0] into the bytes of a data chunk
+    // in this format. Samples outside of the range are clipped.
+    pub fn encode_f32(&self, samples: &[f32]) -> Result<Vec<u8>, RiffWaveError> {
+        let unsupported_bits_per_sample = RiffWaveError::UnsupportedBitsPerSample {
+            audio_format: self.audio_format,
+            bits_per_sample: self.bits_per_sample,
+        };
+
+        match self.audio_format {
+            AudioFormat::Pcm => match self.bits_per_sample {
+                8 => Ok(from_f32_vec_to_u8_vec(samples)),
+                16 => Ok(from_f32_vec_to_i16_vec(samples)),
+                24 => Ok(from_f32_vec_to_i24_vec(samples)),
+                32 => Ok(from_f32_vec_to_i32_vec(samples)),
+                _ => Err(unsupported_bits_per_sample),
+            },
+            AudioFormat::IeeeFloat => match self.bits_per_sample {
+                32 => Ok(samples.iter().flat_map(|s| s.to_le_bytes()).collect()),
+                64 => Ok(samples
+                    .iter()
+                    .flat_map(|s| (*s as f64).to_le_bytes())
+                    .collect()),
+                _ => Err(unsupported_bits_per_sample),
             },
-            num_channels: match as_u16_le(bytes[2..4].try_into().unwrap()) {
-                1 => Channels::Mono,
-                2 => Channels::Stereo,
-                _ => panic!(
-                    "Unsupported number of channels: {}",
-                    as_u16_le(bytes[2..4].try_into().unwrap())
-                ),
+            AudioFormat::ALaw => Ok(samples
+                .iter()
+                .map(|s| i16_to_alaw(f32_to_i16(*s)))
+                .collect()),
+            AudioFormat::MuLaw => Ok(samples
+                .iter()
+                .map(|s| i16_to_mulaw(f32_to_i16(*s)))
+                .collect()),
+            AudioFormat::MsAdpcm | AudioFormat::ImaAdpcm => Err(
+                RiffWaveError::UnsupportedAudioFormat(self.audio_format as u16),
+            ),
+        }
+    }
+
+    // The body of the fmt chunk describing this format.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut extension = Vec::new();
+
+        let format_tag = match (&self.extensible, &self.adpcm) {
+            (Some(extensible), _) => {
+                extension.extend_from_slice(&extensible.valid_bits_per_sample.to_le_bytes());
+                extension.extend_from_slice(&extensible.channel_mask.to_le_bytes());
+                extension.extend_from_slice(&extensible.sub_format);
+                WAVE_FORMAT_EXTENSIBLE
+            }
+            (None, Some(adpcm)) => {
+                extension.extend_from_slice(&adpcm.samples_per_block.to_le_bytes());
+                if self.audio_format == AudioFormat::MsAdpcm {
+                    extension.extend_from_slice(&(adpcm.coefficients.len() as u16).to_le_bytes());
+                    for (c1, c2) in &adpcm.coefficients {
+                        extension.extend_from_slice(&c1.to_le_bytes());
+                        extension.extend_from_slice(&c2.to_le_bytes());
+                    }
+                }
+                self.audio_format as u16
+            }
+            (None, None) => self.audio_format as u16,
+        };
+
+        let mut bytes = Vec::with_capacity(18 + extension.len());
+
+        bytes.extend_from_slice(&format_tag.to_le_bytes());
+        bytes.extend_from_slice(&self.num_channels.count().to_le_bytes());
+        bytes.extend_from_slice(&self.sample_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.byte_rate.to_le_bytes());
+        bytes.extend_from_slice(&self.block_align.to_le_bytes());
+        bytes.extend_from_slice(&self.bits_per_sample.to_le_bytes());
+
+        // only PCM may omit the size of the extension
+        if format_tag != AudioFormat::Pcm as u16 {
+            bytes.extend_from_slice(&(extension.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&extension);
+        }
+
+        bytes
+    }
+
+    // Decodes the given bytes of a data chunk in this format into interleaved
+    // samples normalized to [-1.0, 1.0].
+    pub fn decode_as_f32(&self, data: &[u8]) -> Vec<f32> {
+        match self.audio_format {
+            AudioFormat::Pcm => {
+                let data =
+                    mask_invalid_bits(data, self.bits_per_sample, self.valid_bits_per_sample());
+
+                match self.bits_per_sample {
+                    8 => from_u8_vec_to_f32_vec(&data),
+                    16 => from_i16_vec_to_f32_vec(&data),
+                    24 => from_i24_vec_to_f32_vec(&data),
+                    32 => from_i32_vec_to_f32_vec(&data),
+                    _ => unreachable!("Unsupported bits per sample are rejected when parsing"),
+                }
+            }
+            AudioFormat::IeeeFloat => match self.bits_per_sample {
+                32 => from_f32_vec_to_f32_vec(data),
+                64 => from_f64_vec_to_f32_vec(data),
+                _ => unreachable!("Unsupported bits per sample are rejected when parsing"),
             },
-            sample_rate: as_u32_le(bytes[4..8].try_into().unwrap()),
-            byte_rate: as_u32_le(bytes[8..12].try_into().unwrap()),
-            block_align: as_u16_le(bytes[12..14].try_into().unwrap()),
-            bits_per_sample: as_u16_le(bytes[14..16].try_into().unwrap()),
+            AudioFormat::ALaw => alaw_vec_to_f32_vec(data),
+            AudioFormat::MuLaw => mulaw_vec_to_f32_vec(data),
+            AudioFormat::MsAdpcm => {
+                let adpcm = self.adpcm.as_ref().expect("Missing ADPCM format");
+
+                i16_vec_to_f32_vec(&decode_ms_adpcm(
+                    data,
+                    self.num_channels.count() as usize,
+                    self.block_align as usize,
+                    adpcm.samples_per_block as usize,
+                    &adpcm.coefficients,
+                ))
+            }
+            AudioFormat::ImaAdpcm => {
+                let adpcm = self.adpcm.as_ref().expect("Missing ADPCM format");
+
+                i16_vec_to_f32_vec(&decode_ima_adpcm(
+                    data,
+                    self.num_channels.count() as usize,
+                    self.block_align as usize,
+                    adpcm.samples_per_block as usize,
+                ))
+            }
+        }
+    }
+
+    // Converts samples stored big-endian, with 8 bit PCM signed, as in AIFF and
+    // Sun .au files, into the bytes of a data chunk in this format.
+    pub fn data_from_big_endian(&self, data: &[u8]) -> Vec<u8> {
+        let bytes_per_sample = (self.bits_per_sample as usize).div_ceil(8).max(1);
+
+        match self.audio_format {
+            AudioFormat::Pcm if bytes_per_sample == 1 => {
+                data.iter().map(|sample| sample ^ 0x80).collect()
+            }
+            AudioFormat::Pcm | AudioFormat::IeeeFloat => data
+                .chunks_exact(bytes_per_sample)
+                .flat_map(|sample| sample.iter().rev().copied())
+                .collect(),
+            _ => data.to_vec(),
+        }
+    }
+}
+
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+// The sub format GUIDs of the extensible format all share this tail, the first
+// two bytes carry the regular format tag (i.e. 1 for PCM, 3 for IEEE float).
+const KSDATAFORMAT_SUBTYPE_TAIL: [u8; 14] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38, 0x9b, 0x71,
+];
+
+// https://learn.microsoft.com/en-us/windows/win32/api/mmreg/ns-mmreg-waveformatextensible
+#[derive(Clone)]
+pub struct RiffWaveFormatExtensible {
+    // The number of bits of precision in each sample. The valid bits are stored
+    // left aligned in a container of bits_per_sample bits. E.g. 20 valid bits in
+    // a 24 bit container.
+    pub valid_bits_per_sample: u16,
+    // Bitmask assigning the channels in the stream to speaker positions. E.g.
+    // 0x3 for front left and front right.
+    pub channel_mask: u32,
+    // The GUID identifying the actual data format of the samples.
+    pub sub_format: [u8; 16],
+}
+
+impl RiffWaveFormatExtensible {
+    fn new(header: &ChunkReader) -> Result<Self, RiffWaveError> {
+        Ok(RiffWaveFormatExtensible {
+            valid_bits_per_sample: header.u16(18)?,
+            channel_mask: header.u32(20)?,
+            sub_format: header.bytes(24, 16)?.try_into().unwrap(),
+        })
+    }
+
+    fn sub_format_tag(&self) -> Option<u16> {
+        if self.sub_format[2..] == KSDATAFORMAT_SUBTYPE_TAIL {
+            Some(as_u16_le(self.sub_format[0..2].try_into().unwrap()))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RiffWaveFormatAdpcm {
+    // The number of samples per channel encoded in each block of block_align
+    // bytes, including the samples stored in the block header.
+    pub samples_per_block: u16,
+    // The predictor coefficient pairs of MS ADPCM, referenced by index from each
+    // block header. Empty for IMA ADPCM.
+    pub coefficients: Vec<(i16, i16)>,
+}
+
+impl RiffWaveFormatAdpcm {
+    fn new(audio_format: &AudioFormat, header: &ChunkReader) -> Result<Self, RiffWaveError> {
+        let samples_per_block = header.u16(18)?;
+
+        let coefficients = match audio_format {
+            AudioFormat::MsAdpcm => {
+                let num_coefficients = header.u16(20)? as usize;
+
+                match num_coefficients {
+                    // some writers omit the table, which has to start with the
+                    // standard coefficients anyway
+                    0 => MS_STANDARD_COEFFICIENTS.to_vec(),
+                    _ => header
+                        .bytes(22, num_coefficients * 4)?
+                        .chunks_exact(4)
+                        .map(|pair| {
+                            (
+                                i16::from_le_bytes([pair[0], pair[1]]),
+                                i16::from_le_bytes([pair[2], pair[3]]),
+                            )
+                        })
+                        .collect(),
+                }
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(RiffWaveFormatAdpcm {
+            samples_per_block,
+            coefficients,
         })
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum AudioFormat {
     Pcm = 1,
+    MsAdpcm = 2,
+    IeeeFloat = 3,
+    ALaw = 6,
+    MuLaw = 7,
+    ImaAdpcm = 0x11,
 }
 
 impl Display for AudioFormat {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             AudioFormat::Pcm => write!(f, "PCM"),
+            AudioFormat::MsAdpcm => write!(f, "MS ADPCM"),
+            AudioFormat::IeeeFloat => write!(f, "IEEE float"),
+            AudioFormat::ALaw => write!(f, "A-law"),
+            AudioFormat::MuLaw => write!(f, "µ-law"),
+            AudioFormat::ImaAdpcm => write!(f, "IMA ADPCM"),
         }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Channels {
-    Mono = 1,
-    Stereo = 2,
+    Mono,
+    Stereo,
+    // Any other number of channels, f.e. one per microphone of a conference
+    // recorder. Never 1 or 2.
+    Multi(u16),
+}
+
+impl Channels {
+    pub fn new(count: u16) -> Self {
+        match count {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            count => Channels::Multi(count),
+        }
+    }
+
+    pub fn count(&self) -> u16 {
+        match self {
+            Channels::Mono => 1,
+            Channels::Stereo => 2,
+            Channels::Multi(count) => *count,
+        }
+    }
 }
 
 impl Display for Channels {
@@ -75,194 +592,2408 @@ impl Display for Channels {
         match self {
             Channels::Mono => write!(f, "Mono"),
             Channels::Stereo => write!(f, "Stereo"),
+            Channels::Multi(count) => write!(f, "{} channels", count),
         }
     }
 }
 
-pub struct RiffWave {
-    pub size: u32,
-    pub format: RiffWaveFormat,
-    pub data: Vec<u8>,
+// Size fields of RF64 and BW64 files, which replace the 32 bit sizes of the
+// RIFF, data and any other oversized chunk by the sentinel 0xFFFFFFFF.
+// https://tech.ebu.ch/docs/tech/tech3306v1_1.pdf
+pub struct RiffWaveDs64 {
+    pub riff_size: u64,
+    pub data_size: u64,
+    pub sample_count: u64,
+    // 64 bit sizes of other chunks than data by their chunk id.
+    pub table: Vec<([u8; 4], u64)>,
 }
 
-impl RiffWave {
-    pub fn new(bytes: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
-        let chunk_id: [u8; 4] = bytes[0..4].try_into().unwrap();
+impl RiffWaveDs64 {
+    fn new(bytes: &[u8]) -> Result<Self, RiffWaveError> {
+        let header = ChunkReader::new("ds64", bytes);
+        let table_length = header.u32(24)? as usize;
 
-        match from_utf8(&chunk_id) {
-            Ok("RIFF") => (),
-            Ok(other) => return Err(format!("Unsupported chunk id: {}", other).into()),
-            Err(e) => return Err(format!("Error parsing chunk id: {}", e).into()),
-        }
+        let table = header
+            .bytes(28, table_length.saturating_mul(12))?
+            .chunks_exact(12)
+            .map(|entry| {
+                (
+                    entry[0..4].try_into().unwrap(),
+                    u64::from_le_bytes(entry[4..12].try_into().unwrap()),
+                )
+            })
+            .collect();
 
-        let chunk_size: u32 = as_u32_le(bytes[4..8].try_into().unwrap());
-        let format: [u8; 4] = bytes[8..12].try_into().unwrap();
+        Ok(RiffWaveDs64 {
+            riff_size: header.u64(0)?,
+            data_size: header.u64(8)?,
+            sample_count: header.u64(16)?,
+            table,
+        })
+    }
 
-        match from_utf8(&format) {
-            Ok("WAVE") => (),
-            Ok(other) => return Err(format!("Unsupported format: {}", other).into()),
-            Err(e) => return Err(format!("Error parsing format: {}", e).into()),
+    fn chunk_size(&self, chunk_id: &[u8; 4]) -> Option<u64> {
+        match chunk_id {
+            b"data" => Some(self.data_size),
+            _ => self
+                .table
+                .iter()
+                .find(|(id, _)| id == chunk_id)
+                .map(|(_, size)| *size),
         }
+    }
+}
 
-        let mut offset = 12;
+const RF64_SIZE_SENTINEL: u32 = 0xFFFFFFFF;
 
-        let mut fmt: Option<RiffWaveFormat> = None;
-        let mut data: Option<Vec<u8>> = None;
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ParseMode {
+    // Any violation of the format is an error.
+    Strict,
+    // Recovers from damaged or unfinished files where possible: a data chunk
+    // with a size of 0 (unless another chunk follows) or 0xFFFFFFFF (as left by
+    // streaming writers) or a size beyond the end of the file extends to the
+    // end of the file, and broken chunks after the data are ignored. The
+    // recovered errors are collected as warnings.
+    Lenient,
+}
 
-        loop {
-            let sub_chunk_id: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
-            let sub_chunk_size: u32 = as_u32_le(bytes[offset + 4..offset + 8].try_into().unwrap());
+pub struct RiffWave {
+    pub size: u64,
+    pub format: RiffWaveFormat,
+    pub data: Vec<u8>,
+    // The metadata of the LIST/INFO chunks.
+    pub info: RiffInfo,
+    // Present for Broadcast Wave files only.
+    pub bext: Option<BroadcastExtension>,
+    // The cue points and their labels.
+    pub markers: Markers,
+    // The errors recovered from in lenient mode.
+    pub warnings: Vec<RiffWaveError>,
+}
 
-            match from_utf8(&sub_chunk_id) {
-                Ok("fmt ") => {
-                    fmt = Some(RiffWaveFormat::new(
-                        bytes[offset + 8..offset + 8 + sub_chunk_size as usize].to_vec(),
-                    )?);
-                }
-                Ok("data") => {
-                    data = Some(bytes[offset + 8..offset + 8 + sub_chunk_size as usize].to_vec());
-                }
-                Ok(_) => {
-                    // skipping unknown chunk
-                }
-                Err(e) => return Err(format!("Error parsing sub chunk id: {}", e).into()),
-            }
+impl RiffWave {
+    // Creates a wave from interleaved samples in [-1.0, 1.0], encoded in the
+    // given format.
+    pub fn from_f32(format: RiffWaveFormat, samples: &[f32]) -> Result<Self, RiffWaveError> {
+        let data = format.encode_f32(samples)?;
 
-            offset += 8 + sub_chunk_size as usize;
+        let mut riff_wave = RiffWave {
+            size: 0,
+            format,
+            data,
+            info: RiffInfo::default(),
+            bext: None,
+            markers: Markers::default(),
+            warnings: Vec::new(),
+        };
+        riff_wave.size = riff_wave.riff_size();
 
-            if offset >= bytes.len() {
-                break;
-            }
-        }
+        Ok(riff_wave)
+    }
 
-        Ok(RiffWave {
-            size: chunk_size,
-            format: fmt.expect("Missing format"),
-            data: data.expect("Missing data"),
-        })
+    // Creates a wave from interleaved 16 bit samples, encoded in the given format.
+    #[cfg(test)]
+    pub fn from_i16(format: RiffWaveFormat, samples: &[i16]) -> Result<Self, RiffWaveError> {
+        RiffWave::from_f32(format, &from_i16_slice_to_f32_vec(samples))
     }
 
     pub fn data_as_f32(&self) -> Vec<f32> {
-        match self.format.audio_format {
-            AudioFormat::Pcm => match self.format.bits_per_sample {
-                16 => from_i16_vec_to_f32_vec(&self.data),
-                _ => panic!(
-                    "Unsupported bits per sample: {}",
-                    self.format.bits_per_sample
-                ),
-            },
-        }
+        self.format.decode_as_f32(&self.data)
     }
-}
 
-impl Display for RiffWave {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}kB {} {} {}kH {}B/s {}bit WAFF/RIFF audio",
-            self.data.len() / 1024,
-            self.format.audio_format,
-            self.format.num_channels,
-            self.format.sample_rate / 1000,
-            self.format.byte_rate,
-            self.format.bits_per_sample,
+    // The samples of each channel.
+    pub fn channels_as_f32(&self) -> Vec<Vec<f32>> {
+        deinterleave(
+            &self.data_as_f32(),
+            self.format.num_channels.count() as usize,
         )
     }
-}
 
-fn as_u32_le(array: &[u8; 4]) -> u32 {
-    (array[0] as u32)
-        + ((array[1] as u32) << 8)
-        + ((array[2] as u32) << 16)
-        + ((array[3] as u32) << 24)
-}
+    // One mono wave per channel, with the samples as 32 bit floats and the
+    // metadata of this wave.
+    pub fn split_channels(&self) -> Result<Vec<RiffWave>, RiffWaveError> {
+        let format = RiffWaveFormat::uncompressed(
+            AudioFormat::IeeeFloat,
+            Channels::Mono,
+            self.format.sample_rate,
+            32,
+        )?;
 
-fn as_u16_le(array: &[u8; 2]) -> u16 {
-    (array[0] as u16) + ((array[1] as u16) << 8)
-}
+        Ok(self
+            .channels_as_f32()
+            .iter()
+            .map(|samples| {
+                let mut channel = RiffWave::from_f32(format.clone(), samples)
+                    .expect("Encoding 32 bit floats does not fail");
+                channel.info = self.info.clone();
+                channel.bext = self.bext.clone();
+                channel.markers = self.markers.clone();
+                channel.size = channel.riff_size();
 
-fn from_i16_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
-    let mut result = Vec::with_capacity(array.len() / 2);
+                channel
+            })
+            .collect())
+    }
 
-    for chunk in array.chunks_exact(2) {
-        if let [low, high] = *chunk {
-            let sample = i16::from_le_bytes([low, high]);
-            result.push(sample as f32 * (1.0 / 32768.0));
-        }
+    // The number of frames of the data, rounded down to whole blocks.
+    pub fn frames(&self) -> u64 {
+        (self.data.len() / self.format.block_size()) as u64 * self.format.frames_per_block() as u64
     }
 
-    result
-}
+    // The playing time of the data, rounded down to whole blocks.
+    #[cfg(test)]
+    pub fn duration(&self) -> Duration {
+        self.format.duration_of(self.frames())
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave};
-    use std::fs::File;
-    use std::io::Read;
+    // A copy of the frames from start up to end, extended to whole blocks for
+    // compressed formats. The time reference of a bext chunk is moved to the
+    // new start, the markers are not copied.
+    pub fn region(&self, start: u64, end: u64) -> RiffWave {
+        let frames_per_block = self.format.frames_per_block() as u64;
+        let block_size = self.format.block_size() as u64;
+        let first_block = start / frames_per_block;
+        let last_block = end.div_ceil(frames_per_block);
+        let data = self
+            .data
+            .get((first_block * block_size).min(self.data.len() as u64) as usize..)
+            .unwrap_or_default();
+        let data = &data[..(((last_block - first_block.min(last_block)) * block_size) as usize)
+            .min(data.len())];
 
-    #[test]
-    fn test_empty_wave_riff_header() {
-        let data = b"RIFF\x24\x00\x00\x00WAVE\
-    fmt \x10\x00\x00\x00\x01\x00\x01\x00\x80\x3e\x00\x00\x00\x7d\x00\x00\x02\x00\x10\x00\
-    data\x00\x00\x00\x00";
+        let mut region = RiffWave {
+            size: 0,
+            format: self.format.clone(),
+            data: data.to_vec(),
+            info: self.info.clone(),
+            bext: self.bext.clone().map(|mut bext| {
+                bext.time_reference = bext
+                    .time_reference
+                    .saturating_add(self.format.block_start(start));
+                bext
+            }),
+            markers: Markers::default(),
+            warnings: Vec::new(),
+        };
+        region.size = region.riff_size();
 
-        let riff_wave = RiffWave::new(data.to_vec()).unwrap();
+        region
+    }
 
-        assert_eq!(riff_wave.format.audio_format, AudioFormat::Pcm);
-        assert_eq!(riff_wave.format.num_channels, Channels::Mono);
-        assert_eq!(riff_wave.format.sample_rate, 16000);
-        assert_eq!(riff_wave.format.byte_rate, 32000);
-        assert_eq!(riff_wave.format.block_align, 2);
-        assert_eq!(riff_wave.format.bits_per_sample, 16);
-        assert_eq!(riff_wave.data.len(), 0);
+    // Writes the wave as a RIFF/WAVE file, or as RF64 file if it does not fit
+    // into the 32 bit sizes of RIFF.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<(), RiffWaveError> {
+        self.write_as(writer, self.riff_size() > u32::MAX as u64)
     }
 
-    #[test]
-    fn test_pcm_s16le_8k_mono() {
-        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_8k_mono.wav");
-        let testee = RiffWave::new(data).unwrap();
+    // The size of the RIFF chunk as written by write, without ds64.
+    pub fn riff_size(&self) -> u64 {
+        4 + self.header_chunks().len() as u64
+            + 8
+            + self.data.len() as u64
+            + self.data.len() as u64 % 2
+    }
 
-        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
-        assert_eq!(testee.format.num_channels, Channels::Mono);
-        assert_eq!(testee.format.sample_rate, 8000);
-        assert_eq!(testee.format.byte_rate, 16000);
-        assert_eq!(testee.format.block_align, 2);
-        assert_eq!(testee.format.bits_per_sample, 16);
-        assert_eq!(testee.data.len(), 264014);
+    // All chunks in front of the data chunk, including their headers.
+    fn header_chunks(&self) -> Vec<u8> {
+        let fmt = self.format.to_bytes();
+        let mut bytes = Vec::new();
+
+        if let Some(bext) = &self.bext {
+            bytes.extend_from_slice(&bext.to_bytes());
+        }
+
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&fmt);
+        bytes.extend_from_slice(&self.info.to_bytes());
+        bytes.extend_from_slice(&self.markers.to_bytes());
+
+        bytes
     }
 
-    #[test]
-    fn test_pcm_s16le_16k_mono() {
-        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_16k_mono.wav");
-        let testee = RiffWave::new(data).unwrap();
+    fn write_as<W: Write>(&self, writer: &mut W, rf64: bool) -> Result<(), RiffWaveError> {
+        let data_size = self.data.len() as u64;
+        let riff_size = self.riff_size();
 
-        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
-        assert_eq!(testee.format.num_channels, Channels::Mono);
-        assert_eq!(testee.format.sample_rate, 16000);
-        assert_eq!(testee.format.byte_rate, 32000);
-        assert_eq!(testee.format.block_align, 2);
-        assert_eq!(testee.format.bits_per_sample, 16);
-        assert_eq!(testee.data.len(), 528028);
+        if !rf64 {
+            writer.write_all(b"RIFF")?;
+            writer.write_all(&(riff_size as u32).to_le_bytes())?;
+            writer.write_all(b"WAVE")?;
+        } else {
+            let frames =
+                data_size / self.format.block_size() as u64 * self.format.frames_per_block() as u64;
+
+            writer.write_all(b"RF64")?;
+            writer.write_all(&RF64_SIZE_SENTINEL.to_le_bytes())?;
+            writer.write_all(b"WAVEds64")?;
+            writer.write_all(&28u32.to_le_bytes())?;
+            writer.write_all(&(riff_size + 8 + 28).to_le_bytes())?;
+            writer.write_all(&data_size.to_le_bytes())?;
+            writer.write_all(&frames.to_le_bytes())?;
+            writer.write_all(&0u32.to_le_bytes())?;
+        }
+
+        writer.write_all(&self.header_chunks())?;
+
+        writer.write_all(b"data")?;
+        let data_size_u32 = if rf64 {
+            RF64_SIZE_SENTINEL
+        } else {
+            data_size as u32
+        };
+        writer.write_all(&data_size_u32.to_le_bytes())?;
+        writer.write_all(&self.data)?;
+
+        if data_size % 2 == 1 {
+            writer.write_all(&[0])?;
+        }
+
+        Ok(())
     }
 
-    fn read_audio_file(file_path: &str) -> Vec<u8> {
-        let mut file = File::open(file_path).expect("File not found");
+    #[cfg(test)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.data.len() + 128);
+        self.write(&mut bytes)
+            .expect("Writing to a Vec does not fail");
 
-        let mut audio_data = Vec::new();
-        file.read_to_end(&mut audio_data)
-            .expect("Unable to read file");
+        bytes
+    }
+}
 
-        audio_data
+// Reads RIFF/WAVE files incrementally from any stream (files, pipes, stdin).
+// The headers up to the data chunk are parsed on creation, the samples are then
+// read in blocks, so the memory used does not depend on the length of the file.
+pub struct RiffWaveReader<R: Read> {
+    stream: ChunkStream<R>,
+    pub size: u64,
+    pub format: RiffWaveFormat,
+    // Present for RF64 and BW64 files only.
+    pub ds64: Option<RiffWaveDs64>,
+    // Includes the chunks after the data once read_remaining_chunks was called.
+    metadata: Metadata,
+    // All chunks in the order of the file, starting with the RIFF header. Also
+    // includes the chunks after the data once read_remaining_chunks was called.
+    chunks: Vec<Chunk>,
+    // The errors recovered from in lenient mode.
+    pub warnings: Vec<RiffWaveError>,
+    mode: ParseMode,
+    // The offset of the data chunk header and the size of its body, which is
+    // unknown until the end of the stream for unfinished files in lenient mode.
+    data_offset: u64,
+    data_size: Option<u64>,
+    data_read: u64,
+}
+
+impl<R: Read> RiffWaveReader<R> {
+    #[cfg(test)]
+    pub fn new(reader: R) -> Result<Self, RiffWaveError> {
+        RiffWaveReader::with_mode(reader, ParseMode::Strict)
     }
 
-    #[test]
-    fn test_from_i16_vec_to_f32_vec() {
-        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_16k_mono.wav");
-        let testee = RiffWave::new(data).unwrap();
-        let result = super::from_i16_vec_to_f32_vec(&testee.data);
+    pub fn with_mode(reader: R, mode: ParseMode) -> Result<Self, RiffWaveError> {
+        let mut stream = ChunkStream {
+            reader,
+            peeked: Cursor::new(Vec::new()),
+            position: 0,
+        };
 
-        assert_eq!(result[0], 0.0);
-        assert_eq!(result[1], 6.1035156e-5);
-        assert_eq!(result[1000], 0.010620117);
+        let riff_header = stream.read_up_to(12)?;
+        let header = ChunkReader::new("RIFF", &riff_header);
+        let chunk_id: [u8; 4] = header.bytes(0, 4)?.try_into().unwrap();
+
+        let is_64_bit = match &chunk_id {
+            b"RIFF" => false,
+            b"RF64" | b"BW64" => true,
+            other => {
+                return Err(RiffWaveError::UnsupportedContainer(
+                    String::from_utf8_lossy(other).to_string(),
+                ))
+            }
+        };
+
+        let chunk_size = header.u32(4)?;
+        let format: [u8; 4] = header.bytes(8, 4)?.try_into().unwrap();
+
+        if &format != b"WAVE" {
+            return Err(RiffWaveError::UnsupportedContainer(
+                String::from_utf8_lossy(&format).to_string(),
+            ));
+        }
+
+        let mut fmt: Option<RiffWaveFormat> = None;
+        let mut ds64: Option<RiffWaveDs64> = None;
+        let mut metadata = Metadata::default();
+        let mut warnings = Vec::new();
+        let mut chunks = vec![Chunk {
+            offset: 0,
+            id: String::from_utf8_lossy(&chunk_id).to_string(),
+            size: chunk_size as u64,
+        }];
+
+        let (format, data_offset, data_size) = loop {
+            let chunk = match stream.read_chunk_header(ds64.as_ref())? {
+                Some(chunk) => chunk,
+                None if fmt.is_none() => {
+                    return Err(RiffWaveError::MissingChunk("fmt ".to_string()))
+                }
+                None => return Err(RiffWaveError::MissingChunk("data".to_string())),
+            };
+
+            chunks.push(chunk.clone());
+
+            match chunk.id.as_str() {
+                "ds64" if is_64_bit => {
+                    ds64 = Some(RiffWaveDs64::new(&stream.read_chunk_body(&chunk)?)?);
+                    stream.skip_padding(&chunk)?;
+                }
+                "fmt " => {
+                    fmt = Some(RiffWaveFormat::new(&stream.read_chunk_body(&chunk)?)?);
+                    stream.skip_padding(&chunk)?;
+                }
+                "data" => match fmt {
+                    Some(fmt) => {
+                        // an empty data chunk of a finished file is followed by
+                        // the next chunk
+                        let has_placeholder_size = match chunk.size {
+                            0 if mode == ParseMode::Lenient => {
+                                matches!(chunk_size, 0 | RF64_SIZE_SENTINEL)
+                                    || !stream.is_chunk_next(chunk_size as u64 + 8)?
+                            }
+                            size => size == RF64_SIZE_SENTINEL as u64 && ds64.is_none(),
+                        };
+
+                        if mode == ParseMode::Lenient && has_placeholder_size {
+                            warnings.push(RiffWaveError::PlaceholderChunkSize {
+                                chunk_id: chunk.id,
+                                offset: chunk.offset as usize,
+                                size: chunk.size,
+                            });
+                            break (fmt, chunk.offset, None);
+                        }
+
+                        break (fmt, chunk.offset, Some(chunk.size));
+                    }
+                    None => return Err(RiffWaveError::MissingChunk("fmt ".to_string())),
+                },
+                _ => {
+                    if !metadata.read_chunk(&mut stream, &chunk, mode, &mut warnings)? {
+                        // skipping unknown chunk
+                        stream.skip_chunk_body(&chunk, chunk.size)?;
+                        stream.skip_padding(&chunk)?;
+                    }
+                }
+            }
+        };
+
+        if is_64_bit && ds64.is_none() {
+            return Err(RiffWaveError::MissingChunk("ds64".to_string()));
+        }
+
+        let size = match (&ds64, chunk_size) {
+            (Some(ds64), RF64_SIZE_SENTINEL) => ds64.riff_size,
+            _ => chunk_size as u64,
+        };
+        chunks[0].size = size;
+
+        Ok(RiffWaveReader {
+            stream,
+            size,
+            format,
+            ds64,
+            metadata,
+            chunks,
+            warnings,
+            mode,
+            data_offset,
+            data_size,
+            data_read: 0,
+        })
+    }
+
+    pub fn info(&self) -> &RiffInfo {
+        &self.metadata.info
+    }
+
+    pub fn bext(&self) -> Option<&BroadcastExtension> {
+        self.metadata.bext.as_ref()
+    }
+
+    pub fn markers(&self) -> &Markers {
+        &self.metadata.markers
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    // The number of bytes read from the stream so far.
+    pub fn position(&self) -> u64 {
+        self.stream.position
+    }
+
+    // The number of bytes in the data chunk, if known. This is None for data
+    // chunks without a valid size in lenient mode, until all data was read.
+    #[cfg(test)]
+    pub fn data_size(&self) -> Option<u64> {
+        self.data_size
+    }
+
+    // Reads up to max_bytes of the data chunk, rounded down to whole blocks
+    // unless the end of the chunk is reached. Returns an empty buffer at the end
+    // of the data.
+    pub fn read_data(&mut self, max_bytes: usize) -> Result<Vec<u8>, RiffWaveError> {
+        let block_size = self.format.block_size() as u64;
+        let remaining = self
+            .data_size
+            .map_or(u64::MAX, |size| size - self.data_read);
+        let mut length = remaining.min(max_bytes as u64);
+
+        if length < remaining && length >= block_size {
+            length -= length % block_size;
+        }
+
+        let data = self.stream.read_up_to(length)?;
+        self.data_read += data.len() as u64;
+
+        if (data.len() as u64) < length {
+            let overrun = self.data_size.map(|size| RiffWaveError::ChunkOverrun {
+                chunk_id: "data".to_string(),
+                offset: self.data_offset as usize,
+                size,
+                available: self.data_read as usize,
+            });
+
+            match (overrun, self.mode) {
+                (Some(overrun), ParseMode::Strict) => return Err(overrun),
+                (Some(overrun), ParseMode::Lenient) => self.warnings.push(overrun),
+                (None, _) => (),
+            }
+
+            // the data ends with the stream
+            self.data_size = Some(self.data_read);
+        }
+
+        Ok(data)
+    }
+
+    // Reads and decodes the samples of up to max_frames frames (one sample per
+    // channel each). Returns an empty buffer at the end of the data.
+    pub fn read_frames(&mut self, max_frames: usize) -> Result<Vec<f32>, RiffWaveError> {
+        let blocks = max_frames.div_ceil(self.format.frames_per_block()).max(1);
+        let data = self.read_data(blocks.saturating_mul(self.format.block_size()))?;
+
+        Ok(self.format.decode_as_f32(&data))
+    }
+
+    // Iterates over the decoded samples in buffers of up to frames_per_buffer
+    // frames.
+    pub fn frames(&mut self, frames_per_buffer: usize) -> RiffWaveFrames<'_, R> {
+        RiffWaveFrames {
+            reader: self,
+            frames_per_buffer,
+        }
+    }
+
+    // Reads the rest of the file into a wave, with the data not read so far.
+    pub fn read_wave(mut self) -> Result<RiffWave, RiffWaveError> {
+        let data = self.read_data(usize::MAX)?;
+        self.read_remaining_chunks()?;
+
+        Ok(RiffWave {
+            size: self.size,
+            format: self.format,
+            data,
+            info: self.metadata.info,
+            bext: self.metadata.bext,
+            markers: self.metadata.markers,
+            warnings: self.warnings,
+        })
+    }
+
+    // Skips the rest of the data chunk and validates the chunks following it.
+    pub fn read_remaining_chunks(&mut self) -> Result<(), RiffWaveError> {
+        while !self.read_data(SKIP_BUFFER_SIZE)?.is_empty() {}
+
+        let data_chunk = Chunk {
+            offset: self.data_offset,
+            id: "data".to_string(),
+            size: self.data_read,
+        };
+        self.stream.skip_padding(&data_chunk)?;
+
+        // the actual size of data chunks without a valid size
+        if let Some(chunk) = self
+            .chunks
+            .iter_mut()
+            .find(|chunk| chunk.offset == self.data_offset)
+        {
+            chunk.size = self.data_read;
+        }
+
+        match (self.read_trailing_chunks(), self.mode) {
+            (Err(error), ParseMode::Lenient) if !matches!(error, RiffWaveError::Io(_)) => {
+                self.warnings.push(error)
+            }
+            (result, _) => result?,
+        }
+
+        // only known once all chunks were read, as the cue chunk may be last
+        for id in self.metadata.markers.take_uncued() {
+            match self.mode {
+                ParseMode::Strict => return Err(RiffWaveError::MissingCuePoint(id)),
+                ParseMode::Lenient => self.warnings.push(RiffWaveError::MissingCuePoint(id)),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_trailing_chunks(&mut self) -> Result<(), RiffWaveError> {
+        while let Some(chunk) = self.stream.read_chunk_header(self.ds64.as_ref())? {
+            self.chunks.push(chunk.clone());
+
+            let is_metadata = self.metadata.read_chunk(
+                &mut self.stream,
+                &chunk,
+                self.mode,
+                &mut self.warnings,
+            )?;
+
+            if !is_metadata {
+                self.stream.skip_chunk_body(&chunk, chunk.size)?;
+                self.stream.skip_padding(&chunk)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct RiffWaveFrames<'a, R: Read> {
+    reader: &'a mut RiffWaveReader<R>,
+    frames_per_buffer: usize,
+}
+
+impl<'a, R: Read> Iterator for RiffWaveFrames<'a, R> {
+    type Item = Result<Vec<f32>, RiffWaveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.reader.read_frames(self.frames_per_buffer) {
+            Ok(samples) if samples.is_empty() => None,
+            result => Some(result),
+        }
+    }
+}
+
+// The header of a chunk, with the size resolved from ds64 for RF64 files.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Chunk {
+    pub offset: u64,
+    pub id: String,
+    pub size: u64,
+}
+
+// The chunks describing the audio, which may be placed before or after the
+// data.
+#[derive(Default)]
+struct Metadata {
+    info: RiffInfo,
+    bext: Option<BroadcastExtension>,
+    markers: Markers,
+}
+
+impl Metadata {
+    // Reads the chunk if it is a metadata chunk, returning whether it was.
+    // Malformed metadata only results in a warning in lenient mode, as long as
+    // the size of the chunk is valid.
+    fn read_chunk<R: Read>(
+        &mut self,
+        stream: &mut ChunkStream<R>,
+        chunk: &Chunk,
+        mode: ParseMode,
+        warnings: &mut Vec<RiffWaveError>,
+    ) -> Result<bool, RiffWaveError> {
+        let result = match chunk.id.as_str() {
+            "bext" => BroadcastExtension::new(&stream.read_chunk_body(chunk)?)
+                .map(|bext| self.bext = Some(bext)),
+            "cue " => self.markers.parse_cue(&stream.read_chunk_body(chunk)?),
+            "LIST" => {
+                let list_type = stream.read_up_to(chunk.size.min(4))?;
+                let offset = chunk.offset + 12;
+
+                match list_type.as_slice() {
+                    b"INFO" => self.info.parse(offset, &read_list_body(stream, chunk)?),
+                    b"adtl" => self
+                        .markers
+                        .parse_adtl(offset, &read_list_body(stream, chunk)?),
+                    _ => stream.skip_chunk_body(chunk, chunk.size - list_type.len() as u64),
+                }
+            }
+            _ => return Ok(false),
+        };
+
+        stream.skip_padding(chunk)?;
+
+        match (result, mode) {
+            (Err(error), ParseMode::Lenient) => warnings.push(error),
+            (result, _) => result?,
+        }
+
+        Ok(true)
+    }
+}
+
+// Reads the rest of a LIST chunk after its type.
+fn read_list_body<R: Read>(
+    stream: &mut ChunkStream<R>,
+    chunk: &Chunk,
+) -> Result<Vec<u8>, RiffWaveError> {
+    let body = stream.read_up_to(chunk.size - 4)?;
+
+    if (body.len() as u64) < chunk.size - 4 {
+        return Err(RiffWaveError::ChunkOverrun {
+            chunk_id: chunk.id.clone(),
+            offset: chunk.offset as usize,
+            size: chunk.size,
+            available: 4 + body.len(),
+        });
+    }
+
+    Ok(body)
+}
+
+// A byte stream which keeps track of the offset of the chunks read from it.
+struct ChunkStream<R: Read> {
+    reader: R,
+    // Bytes read ahead, which are read again before the reader.
+    peeked: Cursor<Vec<u8>>,
+    position: u64,
+}
+
+impl<R: Read> ChunkStream<R> {
+    // Reads the next chunk header or returns None at the end of the stream.
+    fn read_chunk_header(
+        &mut self,
+        ds64: Option<&RiffWaveDs64>,
+    ) -> Result<Option<Chunk>, RiffWaveError> {
+        let offset = self.position;
+        let bytes = self.read_up_to(8)?;
+
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+
+        let header = ChunkReader::new("sub chunk", &bytes);
+        let id: [u8; 4] = header.bytes(0, 4)?.try_into().unwrap();
+        let mut size = header.u32(4)? as u64;
+
+        if size == RF64_SIZE_SENTINEL as u64 {
+            if let Some(ds64_size) = ds64.and_then(|ds64| ds64.chunk_size(&id)) {
+                size = ds64_size;
+            }
+        }
+
+        match from_utf8(&id) {
+            Ok(id) if id.chars().all(|c| c.is_ascii_graphic() || c == ' ') => Ok(Some(Chunk {
+                offset,
+                id: id.to_string(),
+                size,
+            })),
+            _ => Err(RiffWaveError::InvalidChunkId {
+                offset: offset as usize,
+            }),
+        }
+    }
+
+    fn read_chunk_body(&mut self, chunk: &Chunk) -> Result<Vec<u8>, RiffWaveError> {
+        let body = self.read_up_to(chunk.size)?;
+
+        if (body.len() as u64) < chunk.size {
+            return Err(RiffWaveError::ChunkOverrun {
+                chunk_id: chunk.id.clone(),
+                offset: chunk.offset as usize,
+                size: chunk.size,
+                available: body.len(),
+            });
+        }
+
+        Ok(body)
+    }
+
+    fn skip_chunk_body(&mut self, chunk: &Chunk, length: u64) -> Result<(), RiffWaveError> {
+        let skipped = io::copy(
+            &mut (&mut self.peeked).chain(&mut self.reader).take(length),
+            &mut io::sink(),
+        )?;
+        self.position += skipped;
+
+        if skipped < length {
+            return Err(RiffWaveError::ChunkOverrun {
+                chunk_id: chunk.id.clone(),
+                offset: chunk.offset as usize,
+                size: chunk.size,
+                available: (chunk.size - length + skipped) as usize,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Chunks of odd size are followed by a pad byte to keep chunks word aligned.
+    // Files ending without the final pad byte are accepted.
+    fn skip_padding(&mut self, chunk: &Chunk) -> Result<(), RiffWaveError> {
+        if chunk.size % 2 == 1 {
+            self.read_up_to(1)?;
+        }
+
+        Ok(())
+    }
+
+    // Reads the given number of bytes or less if the stream ends before.
+    fn read_up_to(&mut self, length: u64) -> Result<Vec<u8>, RiffWaveError> {
+        let mut buffer = Vec::with_capacity(length.min(READ_BUFFER_LIMIT) as usize);
+        (&mut self.peeked)
+            .chain(&mut self.reader)
+            .take(length)
+            .read_to_end(&mut buffer)?;
+        self.position += buffer.len() as u64;
+
+        Ok(buffer)
+    }
+
+    // Reads the given number of bytes or less without consuming them.
+    fn peek(&mut self, length: u64) -> Result<Vec<u8>, RiffWaveError> {
+        let bytes = self.read_up_to(length)?;
+        self.position -= bytes.len() as u64;
+
+        let mut peeked = bytes.clone();
+        self.peeked.read_to_end(&mut peeked)?;
+        self.peeked = Cursor::new(peeked);
+
+        Ok(bytes)
+    }
+
+    // Whether a chunk header follows, of a chunk which ends before the given
+    // end of the file.
+    fn is_chunk_next(&mut self, end: u64) -> Result<bool, RiffWaveError> {
+        let bytes = self.peek(8)?;
+
+        Ok(bytes.len() == 8
+            && bytes[..4]
+                .iter()
+                .all(|c| c.is_ascii_graphic() || *c == b' ')
+            && self.position + 8 + u32::from_le_bytes(bytes[4..].try_into().unwrap()) as u64 <= end)
+    }
+}
+
+// The size of the buffers the rest of the data is skipped in.
+const SKIP_BUFFER_SIZE: usize = 1 << 16;
+
+// Upper bound for buffers allocated up front, so bogus chunk sizes do not
+// reserve more memory than the stream actually holds.
+const READ_BUFFER_LIMIT: u64 = 1 << 24;
+
+impl Display for RiffWave {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}kB {} {} {}kH {}B/s {}bit WAFF/RIFF audio",
+            self.data.len() / 1024,
+            self.format.audio_format,
+            self.format.num_channels,
+            self.format.sample_rate / 1000,
+            self.format.byte_rate,
+            self.format.bits_per_sample,
+        )?;
+
+        for (tag, value) in self.info.iter() {
+            write!(f, "\n{}: {}", tag, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Bounds checked little endian access to the fields of a chunk header.
+struct ChunkReader<'a> {
+    chunk_id: &'a str,
+    bytes: &'a [u8],
+}
+
+impl<'a> ChunkReader<'a> {
+    fn new(chunk_id: &'a str, bytes: &'a [u8]) -> Self {
+        ChunkReader { chunk_id, bytes }
+    }
+
+    fn bytes(&self, offset: usize, length: usize) -> Result<&'a [u8], RiffWaveError> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| RiffWaveError::TruncatedHeader {
+                chunk_id: self.chunk_id.to_string(),
+                expected: offset.saturating_add(length),
+                actual: self.bytes.len(),
+            })
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, RiffWaveError> {
+        Ok(as_u16_le(self.bytes(offset, 2)?.try_into().unwrap()))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, RiffWaveError> {
+        Ok(as_u32_le(self.bytes(offset, 4)?.try_into().unwrap()))
+    }
+
+    fn u64(&self, offset: usize) -> Result<u64, RiffWaveError> {
+        Ok((self.u32(offset)? as u64) + ((self.u32(offset + 4)? as u64) << 32))
+    }
+}
+
+fn as_u32_le(array: &[u8; 4]) -> u32 {
+    (array[0] as u32)
+        + ((array[1] as u32) << 8)
+        + ((array[2] as u32) << 16)
+        + ((array[3] as u32) << 24)
+}
+
+fn as_u16_le(array: &[u8; 2]) -> u16 {
+    (array[0] as u16) + ((array[1] as u16) << 8)
+}
+
+// Clears the padding bits below the valid bits of each sample, as their content
+// is undefined in the extensible format.
+fn mask_invalid_bits(array: &[u8], bits_per_sample: u16, valid_bits: u16) -> Cow<'_, [u8]> {
+    if valid_bits >= bits_per_sample {
+        return Cow::Borrowed(array);
+    }
+
+    let bytes_per_sample = (bits_per_sample / 8) as usize;
+    let padding_bits = (bits_per_sample - valid_bits) as usize;
+    let mut result = array.to_vec();
+
+    for sample in result.chunks_exact_mut(bytes_per_sample) {
+        for (i, byte) in sample.iter_mut().enumerate() {
+            let byte_padding = padding_bits.saturating_sub(i * 8).min(8);
+            *byte &= (0xffu16 << byte_padding) as u8;
+        }
+    }
+
+    Cow::Owned(result)
+}
+
+// 8-bit PCM samples are stored unsigned with 128 being the zero level, all
+// wider PCM samples are signed two's complement.
+fn from_u8_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    let mut result = Vec::with_capacity(array.len());
+
+    for sample in array {
+        result.push((*sample as f32 - 128.0) * (1.0 / 128.0));
+    }
+
+    result
+}
+
+fn from_i16_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    let mut result = Vec::with_capacity(array.len() / 2);
+
+    for chunk in array.chunks_exact(2) {
+        if let [low, high] = *chunk {
+            let sample = i16::from_le_bytes([low, high]);
+            result.push(sample as f32 * (1.0 / 32768.0));
+        }
+    }
+
+    result
+}
+
+fn from_i24_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    let mut result = Vec::with_capacity(array.len() / 3);
+
+    for chunk in array.chunks_exact(3) {
+        if let [low, mid, high] = *chunk {
+            // Place the packed 24 bits into the upper bytes of an i32 and shift
+            // back down to get the sign extension for free.
+            let sample = i32::from_le_bytes([0, low, mid, high]) >> 8;
+            result.push(sample as f32 * (1.0 / 8_388_608.0));
+        }
+    }
+
+    result
+}
+
+fn from_i32_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    let mut result = Vec::with_capacity(array.len() / 4);
+
+    for chunk in array.chunks_exact(4) {
+        if let [b0, b1, b2, b3] = *chunk {
+            let sample = i32::from_le_bytes([b0, b1, b2, b3]);
+            result.push(sample as f32 * (1.0 / 2_147_483_648.0));
+        }
+    }
+
+    result
+}
+
+// Splits interleaved samples into one buffer per channel. A trailing incomplete
+// frame is dropped.
+pub fn deinterleave(samples: &[f32], num_channels: usize) -> Vec<Vec<f32>> {
+    let mut channels = vec![Vec::with_capacity(samples.len() / num_channels.max(1)); num_channels];
+
+    for frame in samples.chunks_exact(num_channels.max(1)) {
+        for (channel, sample) in channels.iter_mut().zip(frame) {
+            channel.push(*sample);
+        }
+    }
+
+    channels
+}
+
+#[cfg(test)]
+fn from_i16_slice_to_f32_vec(samples: &[i16]) -> Vec<f32> {
+    samples
+        .iter()
+        .map(|sample| *sample as f32 * (1.0 / 32768.0))
+        .collect()
+}
+
+fn f32_to_i16(sample: f32) -> i16 {
+    (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16
+}
+
+fn from_f32_vec_to_u8_vec(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .map(|sample| (sample * 128.0 + 128.0).round().clamp(0.0, 255.0) as u8)
+        .collect()
+}
+
+fn from_f32_vec_to_i16_vec(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|sample| f32_to_i16(*sample).to_le_bytes())
+        .collect()
+}
+
+fn from_f32_vec_to_i24_vec(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|sample| {
+            let sample = (*sample as f64 * 8_388_608.0)
+                .round()
+                .clamp(-8_388_608.0, 8_388_607.0) as i32;
+            let [b0, b1, b2, _] = sample.to_le_bytes();
+            [b0, b1, b2]
+        })
+        .collect()
+}
+
+fn from_f32_vec_to_i32_vec(samples: &[f32]) -> Vec<u8> {
+    samples
+        .iter()
+        .flat_map(|sample| {
+            let sample = (*sample as f64 * 2_147_483_648.0)
+                .round()
+                .clamp(-2_147_483_648.0, 2_147_483_647.0) as i32;
+            sample.to_le_bytes()
+        })
+        .collect()
+}
+
+fn from_f32_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    let mut result = Vec::with_capacity(array.len() / 4);
+
+    for chunk in array.chunks_exact(4) {
+        if let [b0, b1, b2, b3] = *chunk {
+            result.push(f32::from_le_bytes([b0, b1, b2, b3]));
+        }
+    }
+
+    result
+}
+
+fn from_f64_vec_to_f32_vec(array: &[u8]) -> Vec<f32> {
+    let mut result = Vec::with_capacity(array.len() / 8);
+
+    for chunk in array.chunks_exact(8) {
+        let sample = f64::from_le_bytes(chunk.try_into().unwrap());
+        result.push(sample as f32);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::adpcm;
+    use crate::audio::bext::BroadcastExtension;
+    use crate::audio::cue::{Marker, Markers};
+    use crate::audio::g711;
+    use crate::audio::riff_info::{InfoTag, RiffInfo};
+    use crate::audio::riff_wave::{
+        deinterleave, AudioFormat, Channels, ParseMode, RiffWave, RiffWaveError, RiffWaveFormat,
+        RiffWaveFormatAdpcm, RiffWaveFormatExtensible, RiffWaveReader, KSDATAFORMAT_SUBTYPE_TAIL,
+    };
+    use std::fs::File;
+    use std::io::{ErrorKind, Read};
+    use std::time::Duration;
+
+    // Reads a complete file in strict mode.
+    fn read_wave(bytes: Vec<u8>) -> Result<RiffWave, RiffWaveError> {
+        RiffWaveReader::new(bytes.as_slice())?.read_wave()
+    }
+
+    #[test]
+    fn test_empty_wave_riff_header() {
+        let data = b"RIFF\x24\x00\x00\x00WAVE\
+    fmt \x10\x00\x00\x00\x01\x00\x01\x00\x80\x3e\x00\x00\x00\x7d\x00\x00\x02\x00\x10\x00\
+    data\x00\x00\x00\x00";
+
+        let riff_wave = read_wave(data.to_vec()).unwrap();
+
+        assert_eq!(riff_wave.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(riff_wave.format.num_channels, Channels::Mono);
+        assert_eq!(riff_wave.format.sample_rate, 16000);
+        assert_eq!(riff_wave.format.byte_rate, 32000);
+        assert_eq!(riff_wave.format.block_align, 2);
+        assert_eq!(riff_wave.format.bits_per_sample, 16);
+        assert_eq!(riff_wave.data.len(), 0);
+    }
+
+    #[test]
+    fn test_pcm_s16le_8k_mono() {
+        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_8k_mono.wav");
+        let testee = read_wave(data).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(testee.format.num_channels, Channels::Mono);
+        assert_eq!(testee.format.sample_rate, 8000);
+        assert_eq!(testee.format.byte_rate, 16000);
+        assert_eq!(testee.format.block_align, 2);
+        assert_eq!(testee.format.bits_per_sample, 16);
+        assert_eq!(testee.data.len(), 264014);
+    }
+
+    #[test]
+    fn test_pcm_s16le_16k_mono() {
+        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_16k_mono.wav");
+        let testee = read_wave(data).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(testee.format.num_channels, Channels::Mono);
+        assert_eq!(testee.format.sample_rate, 16000);
+        assert_eq!(testee.format.byte_rate, 32000);
+        assert_eq!(testee.format.block_align, 2);
+        assert_eq!(testee.format.bits_per_sample, 16);
+        assert_eq!(testee.data.len(), 528028);
+    }
+
+    fn read_audio_file(file_path: &str) -> Vec<u8> {
+        let mut file = File::open(file_path).expect("File not found");
+
+        let mut audio_data = Vec::new();
+        file.read_to_end(&mut audio_data)
+            .expect("Unable to read file");
+
+        audio_data
+    }
+
+    #[test]
+    fn test_from_i16_vec_to_f32_vec() {
+        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_16k_mono.wav");
+        let testee = read_wave(data).unwrap();
+        let result = super::from_i16_vec_to_f32_vec(&testee.data);
+
+        assert_eq!(result[0], 0.0);
+        assert_eq!(result[1], 6.1035156e-5);
+        assert_eq!(result[1000], 0.010620117);
+    }
+
+    #[test]
+    fn test_from_u8_vec_to_f32_vec() {
+        let result = super::from_u8_vec_to_f32_vec(&[0x00, 0x80, 0xff, 0x40]);
+
+        assert_eq!(result, vec![-1.0, 0.0, 0.9921875, -0.5]);
+    }
+
+    #[test]
+    fn test_from_i24_vec_to_f32_vec() {
+        let result = super::from_i24_vec_to_f32_vec(&[
+            0x00, 0x00, 0x80, // min
+            0x00, 0x00, 0x00, // zero
+            0xff, 0xff, 0x7f, // max
+            0xff, 0xff, 0xff, // -1
+            0x00, 0x00, 0x40, // half
+        ]);
+
+        assert_eq!(result, vec![-1.0, 0.0, 0.9999999, -1.1920929e-7, 0.5]);
+    }
+
+    #[test]
+    fn test_from_i32_vec_to_f32_vec() {
+        let result = super::from_i32_vec_to_f32_vec(&[
+            0x00, 0x00, 0x00, 0x80, // min
+            0x00, 0x00, 0x00, 0x00, // zero
+            0xff, 0xff, 0xff, 0x7f, // max
+            0x00, 0x00, 0x00, 0xc0, // minus half
+        ]);
+
+        assert_eq!(result, vec![-1.0, 0.0, 1.0, -0.5]);
+    }
+
+    #[test]
+    fn test_data_as_f32_pcm_u8() {
+        let data = b"RIFF\x28\x00\x00\x00WAVE\
+    fmt \x10\x00\x00\x00\x01\x00\x01\x00\x40\x1f\x00\x00\x40\x1f\x00\x00\x01\x00\x08\x00\
+    data\x04\x00\x00\x00\x00\x80\xff\xc0";
+
+        let testee = read_wave(data.to_vec()).unwrap();
+
+        assert_eq!(testee.format.bits_per_sample, 8);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, 0.0, 0.9921875, 0.5]);
+    }
+
+    #[test]
+    fn test_data_as_f32_pcm_s24le() {
+        let data = b"RIFF\x2a\x00\x00\x00WAVE\
+    fmt \x10\x00\x00\x00\x01\x00\x01\x00\x80\xbb\x00\x00\x80\x32\x02\x00\x03\x00\x18\x00\
+    data\x06\x00\x00\x00\x00\x00\x80\x00\x00\x40";
+
+        let testee = read_wave(data.to_vec()).unwrap();
+
+        assert_eq!(testee.format.bits_per_sample, 24);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_data_as_f32_pcm_s32le() {
+        let data = b"RIFF\x2c\x00\x00\x00WAVE\
+    fmt \x10\x00\x00\x00\x01\x00\x01\x00\x80\xbb\x00\x00\x00\xee\x02\x00\x04\x00\x20\x00\
+    data\x08\x00\x00\x00\x00\x00\x00\x80\x00\x00\x00\x40";
+
+        let testee = read_wave(data.to_vec()).unwrap();
+
+        assert_eq!(testee.format.bits_per_sample, 32);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_from_f32_vec_to_f32_vec() {
+        let data: Vec<u8> = [-1.0f32, 0.0, 0.25, 1.0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+
+        let result = super::from_f32_vec_to_f32_vec(&data);
+
+        assert_eq!(result, vec![-1.0, 0.0, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn test_from_f64_vec_to_f32_vec() {
+        let data: Vec<u8> = [-1.0f64, 0.0, 0.125, 1.0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+
+        let result = super::from_f64_vec_to_f32_vec(&data);
+
+        assert_eq!(result, vec![-1.0, 0.0, 0.125, 1.0]);
+    }
+
+    #[test]
+    fn test_data_as_f32_ieee_float_32() {
+        let data = b"RIFF\x2c\x00\x00\x00WAVE\
+    fmt \x10\x00\x00\x00\x03\x00\x01\x00\x80\x3e\x00\x00\x00\xfa\x00\x00\x04\x00\x20\x00\
+    data\x08\x00\x00\x00\x00\x00\x80\xbf\x00\x00\x00\x3f";
+
+        let testee = read_wave(data.to_vec()).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(testee.format.bits_per_sample, 32);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_data_as_f32_ieee_float_64() {
+        let data = b"RIFF\x34\x00\x00\x00WAVE\
+    fmt \x10\x00\x00\x00\x03\x00\x01\x00\x80\x3e\x00\x00\x00\xf4\x01\x00\x08\x00\x40\x00\
+    data\x10\x00\x00\x00\x00\x00\x00\x00\x00\x00\xf0\xbf\x00\x00\x00\x00\x00\x00\xe0\x3f";
+
+        let testee = read_wave(data.to_vec()).unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(testee.format.bits_per_sample, 64);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_extensible_pcm_s24_in_32() {
+        let data = b"RIFF\x44\x00\x00\x00WAVE\
+    fmt \x28\x00\x00\x00\xfe\xff\x01\x00\x80\xbb\x00\x00\x00\xee\x02\x00\x04\x00\x20\x00\
+    \x16\x00\x18\x00\x04\x00\x00\x00\
+    \x01\x00\x00\x00\x00\x00\x10\x00\x80\x00\x00\xaa\x00\x38\x9b\x71\
+    data\x08\x00\x00\x00\xff\x00\x00\x80\xff\x00\x00\x40";
+
+        let testee = read_wave(data.to_vec()).unwrap();
+        let extensible = testee.format.extensible.as_ref().unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::Pcm);
+        assert_eq!(testee.format.num_channels, Channels::Mono);
+        assert_eq!(testee.format.bits_per_sample, 32);
+        assert_eq!(testee.format.valid_bits_per_sample(), 24);
+        assert_eq!(extensible.valid_bits_per_sample, 24);
+        assert_eq!(extensible.channel_mask, 0x4);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_extensible_ieee_float() {
+        let data = b"RIFF\x44\x00\x00\x00WAVE\
+    fmt \x28\x00\x00\x00\xfe\xff\x02\x00\x80\x3e\x00\x00\x00\xf4\x01\x00\x08\x00\x20\x00\
+    \x16\x00\x20\x00\x03\x00\x00\x00\
+    \x03\x00\x00\x00\x00\x00\x10\x00\x80\x00\x00\xaa\x00\x38\x9b\x71\
+    data\x08\x00\x00\x00\x00\x00\x80\xbf\x00\x00\x00\x3f";
+
+        let testee = read_wave(data.to_vec()).unwrap();
+        let extensible = testee.format.extensible.as_ref().unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(testee.format.num_channels, Channels::Stereo);
+        assert_eq!(testee.format.valid_bits_per_sample(), 32);
+        assert_eq!(extensible.channel_mask, 0x3);
+        assert_eq!(testee.data_as_f32(), vec![-1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_extensible_unknown_sub_format() {
+        let data = b"RIFF\x3c\x00\x00\x00WAVE\
+    fmt \x28\x00\x00\x00\xfe\xff\x01\x00\x80\x3e\x00\x00\x00\x7d\x00\x00\x02\x00\x10\x00\
+    \x16\x00\x10\x00\x04\x00\x00\x00\
+    \x01\x00\x00\x00\x00\x00\x10\x00\x80\x00\x00\xaa\x00\x38\x9b\x72\
+    data\x00\x00\x00\x00";
+
+        assert_eq!(
+            read_wave(data.to_vec()).err(),
+            Some(RiffWaveError::UnsupportedSubFormat([
+                0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xaa, 0x00, 0x38,
+                0x9b, 0x72
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_mask_invalid_bits() {
+        let result = super::mask_invalid_bits(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff], 24, 20);
+
+        assert_eq!(result.to_vec(), vec![0xf0, 0xff, 0xff, 0xf0, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_data_as_f32_alaw() {
+        let samples = sine_wave_i16(8000, 440.0, 800);
+        let encoded: Vec<u8> = samples.iter().map(|s| g711::i16_to_alaw(*s)).collect();
+
+        let testee = read_wave(wave_file(
+            RiffWaveFormat::uncompressed(AudioFormat::ALaw, Channels::Mono, 8000, 8).unwrap(),
+            &encoded,
+        ))
+        .unwrap();
+        let result = testee.data_as_f32();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::ALaw);
+        assert_eq!(result.len(), samples.len());
+        assert_close_to_i16(&result, &samples, 1024);
+    }
+
+    #[test]
+    fn test_data_as_f32_mulaw() {
+        let samples = sine_wave_i16(8000, 440.0, 800);
+        let encoded: Vec<u8> = samples.iter().map(|s| g711::i16_to_mulaw(*s)).collect();
+
+        let testee = read_wave(wave_file(
+            RiffWaveFormat::uncompressed(AudioFormat::MuLaw, Channels::Mono, 8000, 8).unwrap(),
+            &encoded,
+        ))
+        .unwrap();
+        let result = testee.data_as_f32();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::MuLaw);
+        assert_eq!(result.len(), samples.len());
+        assert_close_to_i16(&result, &samples, 1024);
+    }
+
+    #[test]
+    fn test_data_as_f32_ima_adpcm() {
+        let data = [0x10, 0x00, 0x00, 0x00, 0x77, 0x00, 0x88, 0x08];
+
+        let testee = read_wave(wave_file(ima_adpcm_format(), &data)).unwrap();
+        let adpcm = testee.format.adpcm.as_ref().unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::ImaAdpcm);
+        assert_eq!(adpcm.samples_per_block, 9);
+        assert!(adpcm.coefficients.is_empty());
+        assert_close_to_i16(
+            &testee.data_as_f32(),
+            &[16, 27, 57, 61, 64, 61, 58, 56, 58],
+            0,
+        );
+    }
+
+    #[test]
+    fn test_data_as_f32_ms_adpcm() {
+        let data = [0x00, 0x10, 0x00, 0x64, 0x00, 0x32, 0x00, 0x10, 0xf0];
+
+        let testee = read_wave(wave_file(ms_adpcm_format(), &data)).unwrap();
+        let adpcm = testee.format.adpcm.as_ref().unwrap();
+
+        assert_eq!(testee.format.audio_format, AudioFormat::MsAdpcm);
+        assert_eq!(adpcm.samples_per_block, 6);
+        assert_eq!(adpcm.coefficients[1], (512, -256));
+        assert_close_to_i16(&testee.data_as_f32(), &[50, 100, 116, 116, 100, 100], 0);
+    }
+
+    #[test]
+    fn test_rf64() {
+        let samples: Vec<u8> = [0i16, 16384, -16384]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+
+        let bytes = rf64_bytes(b"RF64", &samples);
+        let reader = RiffWaveReader::new(bytes.as_slice()).unwrap();
+        let ds64 = reader.ds64.as_ref().unwrap();
+        let testee = read_wave(bytes.clone()).unwrap();
+
+        assert_eq!(testee.size, 0x1_0000_0000);
+        assert_eq!(ds64.data_size, 6);
+        assert_eq!(ds64.sample_count, 3);
+        assert_eq!(ds64.table, vec![(*b"junk", 4)]);
+        assert_eq!(testee.format.sample_rate, 16000);
+        assert_eq!(testee.data_as_f32(), vec![0.0, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_bw64() {
+        let bytes = rf64_bytes(b"BW64", &[0x00, 0x40]);
+        let reader = RiffWaveReader::new(bytes.as_slice()).unwrap();
+        let testee = read_wave(bytes.clone()).unwrap();
+
+        assert_eq!(reader.ds64.as_ref().unwrap().data_size, 2);
+        assert_eq!(testee.data_as_f32(), vec![0.5]);
+    }
+
+    #[test]
+    fn test_rf64_missing_ds64() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40]);
+        data[0..4].copy_from_slice(b"RF64");
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::MissingChunk("ds64".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_error_empty_file() {
+        assert_eq!(
+            read_wave(vec![]).err(),
+            Some(RiffWaveError::TruncatedHeader {
+                chunk_id: "RIFF".to_string(),
+                expected: 4,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_truncated_riff_header() {
+        assert_eq!(
+            read_wave(b"RIFF\x24\x00\x00\x00WAV".to_vec()).err(),
+            Some(RiffWaveError::TruncatedHeader {
+                chunk_id: "RIFF".to_string(),
+                expected: 12,
+                actual: 11
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_unsupported_container() {
+        assert_eq!(
+            read_wave(b"FORM\x24\x00\x00\x00AIFF".to_vec()).err(),
+            Some(RiffWaveError::UnsupportedContainer("FORM".to_string()))
+        );
+        assert_eq!(
+            read_wave(b"RIFF\x24\x00\x00\x00AVI ".to_vec()).err(),
+            Some(RiffWaveError::UnsupportedContainer("AVI ".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_error_truncated_sub_chunk_header() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40]);
+        data.extend_from_slice(b"LIST\x04");
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::TruncatedHeader {
+                chunk_id: "sub chunk".to_string(),
+                expected: 8,
+                actual: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_chunk_overrun() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40, 0x00, 0x40]);
+        data.truncate(data.len() - 1);
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::ChunkOverrun {
+                chunk_id: "data".to_string(),
+                offset: 36,
+                size: 4,
+                available: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_invalid_chunk_id() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40]);
+        data.extend_from_slice(b"\x00\xffAB\x00\x00\x00\x00");
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::InvalidChunkId { offset: 46 })
+        );
+    }
+
+    #[test]
+    fn test_error_missing_fmt_chunk() {
+        let data = b"RIFF\x0e\x00\x00\x00WAVEdata\x02\x00\x00\x00\x00\x40";
+
+        assert_eq!(
+            read_wave(data.to_vec()).err(),
+            Some(RiffWaveError::MissingChunk("fmt ".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_error_missing_data_chunk() {
+        let data = b"RIFF\x1c\x00\x00\x00WAVE\
+    fmt \x10\x00\x00\x00\x01\x00\x01\x00\x80\x3e\x00\x00\x00\x7d\x00\x00\x02\x00\x10\x00";
+
+        assert_eq!(
+            read_wave(data.to_vec()).err(),
+            Some(RiffWaveError::MissingChunk("data".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_error_truncated_fmt_chunk() {
+        let data = b"RIFF\x1a\x00\x00\x00WAVE\
+    fmt \x0e\x00\x00\x00\x01\x00\x01\x00\x80\x3e\x00\x00\x00\x7d\x00\x00\x02\x00";
+
+        assert_eq!(
+            read_wave(data.to_vec()).err(),
+            Some(RiffWaveError::TruncatedHeader {
+                chunk_id: "fmt ".to_string(),
+                expected: 16,
+                actual: 14
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_truncated_extensible_fmt_chunk() {
+        // an extensible format tag in a 16 byte fmt chunk
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[]);
+        data[20..22].copy_from_slice(&0xfffeu16.to_le_bytes());
+        data.truncate(36);
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::TruncatedHeader {
+                chunk_id: "fmt ".to_string(),
+                expected: 20,
+                actual: 16
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_unsupported_audio_format() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[]);
+        data[20..22].copy_from_slice(&0x55u16.to_le_bytes());
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::UnsupportedAudioFormat(0x55))
+        );
+    }
+
+    #[test]
+    fn test_error_unsupported_channels() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[]);
+        data[22..24].copy_from_slice(&0u16.to_le_bytes());
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::UnsupportedChannels(0))
+        );
+    }
+
+    #[test]
+    fn test_error_unsupported_bits_per_sample() {
+        let mut pcm_12_bit = pcm_wave_file(Channels::Mono, 16000, 16, &[]);
+        pcm_12_bit[34..36].copy_from_slice(&12u16.to_le_bytes());
+        let mut float_16_bit = pcm_wave_file(Channels::Mono, 16000, 16, &[]);
+        float_16_bit[20..22].copy_from_slice(&3u16.to_le_bytes());
+
+        assert_eq!(
+            read_wave(pcm_12_bit).err(),
+            Some(RiffWaveError::UnsupportedBitsPerSample {
+                audio_format: AudioFormat::Pcm,
+                bits_per_sample: 12
+            })
+        );
+        assert_eq!(
+            read_wave(float_16_bit).err(),
+            Some(RiffWaveError::UnsupportedBitsPerSample {
+                audio_format: AudioFormat::IeeeFloat,
+                bits_per_sample: 16
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_truncated_ds64_chunk() {
+        let mut data = rf64_bytes(b"RF64", &[0x00, 0x40]);
+        data[16..20].copy_from_slice(&20u32.to_le_bytes());
+        data.truncate(40);
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::TruncatedHeader {
+                chunk_id: "ds64".to_string(),
+                expected: 28,
+                actual: 20
+            })
+        );
+    }
+
+    #[test]
+    fn test_reader_frames() {
+        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_16k_mono.wav");
+        let expected = read_wave(data.clone()).unwrap().data_as_f32();
+
+        let mut testee = RiffWaveReader::new(OneByteReader(&data)).unwrap();
+
+        assert_eq!(testee.format.sample_rate, 16000);
+        assert_eq!(testee.data_size(), Some(528028));
+
+        let buffers: Vec<Vec<f32>> = testee.frames(4096).map(|b| b.unwrap()).collect();
+
+        assert_eq!(buffers.len(), 65);
+        assert!(buffers.iter().all(|b| b.len() <= 4096));
+        assert_eq!(buffers.concat(), expected);
+        assert!(testee.read_frames(4096).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reader_frames_stereo() {
+        let samples: Vec<u8> = [0i16, 16384, -16384, 8192, 0, -8192]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let data = pcm_wave_file(Channels::Stereo, 16000, 16, &samples);
+
+        let mut testee = RiffWaveReader::new(data.as_slice()).unwrap();
+
+        assert_eq!(testee.read_frames(2).unwrap(), vec![0.0, 0.5, -0.5, 0.25]);
+        assert_eq!(testee.read_frames(2).unwrap(), vec![0.0, -0.25]);
+        assert!(testee.read_frames(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reader_frames_adpcm_blocks() {
+        let block = [0x10, 0x00, 0x00, 0x00, 0x77, 0x00, 0x88, 0x08];
+        let data = wave_file(ima_adpcm_format(), &block.repeat(3));
+
+        let mut testee = RiffWaveReader::new(data.as_slice()).unwrap();
+
+        // frames are decoded in whole blocks of 9 samples
+        assert_eq!(testee.read_frames(10).unwrap().len(), 18);
+        assert_eq!(testee.read_frames(1).unwrap().len(), 9);
+        assert!(testee.read_frames(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reader_truncated_data() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00; 10]);
+        data.truncate(data.len() - 3);
+
+        let mut testee = RiffWaveReader::new(data.as_slice()).unwrap();
+
+        assert_eq!(testee.read_frames(2).unwrap().len(), 2);
+        assert_eq!(
+            testee.read_frames(10).err(),
+            Some(RiffWaveError::ChunkOverrun {
+                chunk_id: "data".to_string(),
+                offset: 36,
+                size: 10,
+                available: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_read_wave_from_reader() {
+        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_8k_mono.wav");
+
+        let testee = RiffWaveReader::new(OneByteReader(&data))
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+
+        assert_eq!(testee.format.sample_rate, 8000);
+        assert_eq!(testee.data.len(), 264014);
+    }
+
+    #[test]
+    fn test_odd_sized_chunk_padding() {
+        let mut data = b"RIFF\x00\x00\x00\x00WAVELIST\x03\x00\x00\x00abc\x00".to_vec();
+        data.extend_from_slice(&pcm_wave_file(Channels::Mono, 8000, 8, &[0x80, 0xc0, 0x40])[12..]);
+        data.extend_from_slice(b"junk\x01\x00\x00\x00x");
+
+        let testee = read_wave(data).unwrap();
+
+        assert_eq!(testee.data, vec![0x80, 0xc0, 0x40]);
+        assert!(testee.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_missing_final_padding() {
+        let data = pcm_wave_file(Channels::Mono, 8000, 8, &[0x80, 0xc0, 0x40]);
+        let unpadded = &data[..data.len() - 1];
+
+        assert_eq!(read_wave(unpadded.to_vec()).unwrap().data.len(), 3);
+        assert_eq!(
+            RiffWaveReader::with_mode(FailingReader(unpadded, false), ParseMode::Lenient)
+                .and_then(RiffWaveReader::read_wave)
+                .err(),
+            Some(RiffWaveError::Io(ErrorKind::BrokenPipe))
+        );
+    }
+
+    #[test]
+    fn test_lenient_placeholder_data_size() {
+        for placeholder in [0u32, 0xffffffff] {
+            let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40, 0x00, 0xc0]);
+            data[40..44].copy_from_slice(&placeholder.to_le_bytes());
+
+            let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+                .and_then(RiffWaveReader::read_wave)
+                .unwrap();
+
+            assert_eq!(testee.data_as_f32(), vec![0.5, -0.5]);
+            assert_eq!(
+                testee.warnings,
+                vec![RiffWaveError::PlaceholderChunkSize {
+                    chunk_id: "data".to_string(),
+                    offset: 36,
+                    size: placeholder as u64
+                }]
+            );
+        }
+
+        // an empty data chunk of a finished file, followed by another chunk
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[]);
+        data.extend_from_slice(b"LIST\x0e\x00\x00\x00INFOINAM\x02\x00\x00\x00A\x00");
+        let riff_size = data.len() as u32 - 8;
+        data[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+        let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+
+        assert!(testee.data.is_empty());
+        assert_eq!(testee.info.get(InfoTag::Title), Some("A"));
+        assert!(testee.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_strict_placeholder_data_size() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40, 0x00, 0xc0]);
+        data[40..44].copy_from_slice(&[0xff; 4]);
+
+        assert_eq!(
+            read_wave(data).err(),
+            Some(RiffWaveError::ChunkOverrun {
+                chunk_id: "data".to_string(),
+                offset: 36,
+                size: 0xffffffff,
+                available: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_lenient_data_overrun() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40, 0x00, 0xc0]);
+        data[40..44].copy_from_slice(&100u32.to_le_bytes());
+
+        let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+
+        assert_eq!(testee.data_as_f32(), vec![0.5, -0.5]);
+        assert_eq!(
+            testee.warnings,
+            vec![RiffWaveError::ChunkOverrun {
+                chunk_id: "data".to_string(),
+                offset: 36,
+                size: 100,
+                available: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lenient_broken_trailing_chunk() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40]);
+        data.extend_from_slice(b"LIST\x10\x00\x00\x00INFO");
+
+        let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+
+        assert_eq!(testee.data_as_f32(), vec![0.5]);
+        assert_eq!(
+            testee.warnings,
+            vec![RiffWaveError::ChunkOverrun {
+                chunk_id: "LIST".to_string(),
+                offset: 46,
+                size: 16,
+                available: 4
+            }]
+        );
+    }
+
+    #[test]
+    fn test_reader_lenient_unknown_data_size() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00; 8]);
+        data[40..44].copy_from_slice(&[0xff; 4]);
+
+        let mut testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient).unwrap();
+
+        assert_eq!(testee.data_size(), None);
+        assert_eq!(testee.read_frames(3).unwrap().len(), 3);
+        assert_eq!(testee.read_frames(3).unwrap().len(), 1);
+        assert_eq!(testee.data_size(), Some(8));
+        assert!(testee.read_frames(3).unwrap().is_empty());
+    }
+
+    // A reader which returns a single byte per read, like a slow pipe.
+    struct OneByteReader<'a>(&'a [u8]);
+
+    impl Read for OneByteReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((byte, rest)), Some(target)) => {
+                    *target = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    // A reader which fails once after its bytes are read and then ends.
+    struct FailingReader<'a>(&'a [u8], bool);
+
+    impl Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match (self.0.is_empty(), self.1) {
+                (true, false) => {
+                    self.1 = true;
+                    Err(ErrorKind::BrokenPipe.into())
+                }
+                _ => self.0.read(buf),
+            }
+        }
+    }
+
+    #[test]
+    fn test_uncompressed_format() {
+        let testee =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Stereo, 44100, 24).unwrap();
+
+        assert_eq!(testee.block_align, 6);
+        assert_eq!(testee.byte_rate, 264600);
+
+        assert_eq!(
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::new(40000), 8000, 16).err(),
+            Some(RiffWaveError::UnsupportedChannels(40000))
+        );
+        assert_eq!(
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 3_000_000_000, 16).err(),
+            Some(RiffWaveError::UnsupportedSampleRate(3_000_000_000))
+        );
+        assert_eq!(
+            RiffWaveFormat::uncompressed(AudioFormat::ImaAdpcm, Channels::Mono, 8000, 4).err(),
+            Some(RiffWaveError::UnsupportedAudioFormat(0x11))
+        );
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let samples = [0.0, 0.5, -0.5, 0.25, -1.0, 0.75];
+        let formats = [
+            (AudioFormat::Pcm, 8, 1.0 / 128.0),
+            (AudioFormat::Pcm, 16, 1.0 / 32768.0),
+            (AudioFormat::Pcm, 24, 1.0 / 8388608.0),
+            (AudioFormat::Pcm, 32, 0.0),
+            (AudioFormat::IeeeFloat, 32, 0.0),
+            (AudioFormat::IeeeFloat, 64, 0.0),
+            (AudioFormat::ALaw, 8, 1.0 / 32.0),
+            (AudioFormat::MuLaw, 8, 1.0 / 32.0),
+        ];
+
+        for (audio_format, bits_per_sample, tolerance) in formats {
+            let format = RiffWaveFormat::uncompressed(
+                audio_format,
+                Channels::Stereo,
+                16000,
+                bits_per_sample,
+            )
+            .unwrap();
+            let bytes = RiffWave::from_f32(format, &samples).unwrap().to_bytes();
+
+            let testee = read_wave(bytes).unwrap();
+
+            assert_eq!(testee.format.audio_format, audio_format);
+            assert_eq!(testee.format.num_channels, Channels::Stereo);
+            assert_eq!(testee.format.bits_per_sample, bits_per_sample);
+            assert_eq!(testee.format.block_align, bits_per_sample / 4);
+            assert_eq!(testee.format.byte_rate, 4000 * bits_per_sample as u32);
+            for (actual, expected) in testee.data_as_f32().iter().zip(samples) {
+                assert!(
+                    (actual - expected).abs() <= tolerance,
+                    "{:?} {}bit: {} is not close to {}",
+                    audio_format,
+                    bits_per_sample,
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_from_i16() {
+        let samples = sine_wave_i16(16000, 440.0, 100);
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 16000, 16).unwrap();
+
+        let testee = read_wave(RiffWave::from_i16(format, &samples).unwrap().to_bytes()).unwrap();
+
+        assert_close_to_i16(&testee.data_as_f32(), &samples, 0);
+    }
+
+    #[test]
+    fn test_write_clips_samples() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 8000, 16).unwrap();
+
+        let testee = RiffWave::from_f32(format, &[2.0, -2.0]).unwrap();
+
+        assert_eq!(testee.data, vec![0xff, 0x7f, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn test_write_odd_data_padding() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 8000, 8).unwrap();
+        let bytes = RiffWave::from_f32(format, &[0.0, 0.5, -0.5])
+            .unwrap()
+            .to_bytes();
+
+        assert_eq!(bytes.len(), 44 + 4);
+        assert_eq!(&bytes[4..8], &40u32.to_le_bytes());
+        assert_eq!(&bytes[40..44], &3u32.to_le_bytes());
+        assert_eq!(bytes[47], 0);
+        assert_eq!(read_wave(bytes).unwrap().data, vec![128, 192, 64]);
+    }
+
+    #[test]
+    fn test_write_real_file() {
+        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_8k_mono.wav");
+        let original = read_wave(data).unwrap();
+
+        let testee = read_wave(original.to_bytes()).unwrap();
+
+        assert_eq!(testee.format.sample_rate, 8000);
+        assert_eq!(testee.data, original.data);
+    }
+
+    #[test]
+    fn test_write_extensible() {
+        let mut format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 8000, 32).unwrap();
+        let mut sub_format = [0x01, 0x00].to_vec();
+        sub_format.extend_from_slice(&KSDATAFORMAT_SUBTYPE_TAIL);
+        format.extensible = Some(RiffWaveFormatExtensible {
+            valid_bits_per_sample: 24,
+            channel_mask: 0x4,
+            sub_format: sub_format.try_into().unwrap(),
+        });
+        let bytes = wave_file(format, &[0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0xc0]);
+
+        let testee = read_wave(bytes.clone()).unwrap();
+
+        assert_eq!(bytes[16..22], [40, 0, 0, 0, 0xfe, 0xff]);
+        assert_eq!(testee.format.valid_bits_per_sample(), 24);
+        assert_eq!(testee.data_as_f32(), vec![0.5, -0.5]);
+        assert_eq!(testee.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_write_adpcm() {
+        let data = [0x00, 0x10, 0x00, 0x64, 0x00, 0x32, 0x00, 0x10, 0xf0];
+        let bytes = wave_file(ms_adpcm_format(), &data);
+        let original = read_wave(bytes).unwrap();
+
+        let testee = read_wave(original.to_bytes()).unwrap();
+
+        assert_eq!(testee.format.adpcm.as_ref().unwrap().samples_per_block, 6);
+        assert_close_to_i16(&testee.data_as_f32(), &[50, 100, 116, 116, 100, 100], 0);
+        assert_eq!(
+            RiffWave::from_f32(original.format.clone(), &[0.0]).err(),
+            Some(RiffWaveError::UnsupportedAudioFormat(2))
+        );
+    }
+
+    #[test]
+    fn test_write_rf64() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 16000, 16).unwrap();
+        let original = RiffWave::from_f32(format, &[0.0, 0.5, -0.5]).unwrap();
+        let mut bytes = Vec::new();
+        original.write_as(&mut bytes, true).unwrap();
+
+        let reader = RiffWaveReader::new(bytes.as_slice()).unwrap();
+        let ds64 = reader.ds64.as_ref().unwrap();
+        let testee = read_wave(bytes.clone()).unwrap();
+
+        assert_eq!(ds64.data_size, 6);
+        assert_eq!(ds64.sample_count, 3);
+        assert_eq!(testee.data_as_f32(), vec![0.0, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_info() {
+        let data = read_audio_file("test_data/audio/riff_wave/pcm_s16le_16k_mono.wav");
+        let testee = read_wave(data).unwrap();
+
+        assert_eq!(testee.info.get(InfoTag::Software), Some("Lavf58.76.100"));
+        assert!(testee.to_string().ends_with("\nSoftware: Lavf58.76.100"));
+    }
+
+    #[test]
+    fn test_info_after_data() {
+        let mut data = pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40]);
+        data.extend_from_slice(b"LIST\x12\x00\x00\x00INFOICMT\x05\x00\x00\x00note\x00\x00");
+        data.extend_from_slice(b"LIST\x08\x00\x00\x00exifabcd");
+
+        let testee = read_wave(data).unwrap();
+
+        assert_eq!(testee.info.get(InfoTag::Comment), Some("note"));
+        assert_eq!(testee.info.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_info_malformed() {
+        let mut data = b"RIFF\x00\x00\x00\x00WAVE".to_vec();
+        data.extend_from_slice(b"LIST\x0c\x00\x00\x00INFOINAM\x10\x00\x00\x00");
+        data.extend_from_slice(&pcm_wave_file(Channels::Mono, 16000, 16, &[0x00, 0x40])[12..]);
+        let error = RiffWaveError::ChunkOverrun {
+            chunk_id: "INAM".to_string(),
+            offset: 24,
+            size: 16,
+            available: 0,
+        };
+
+        assert_eq!(read_wave(data.clone()).err(), Some(error.clone()));
+
+        let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+
+        assert_eq!(testee.warnings, vec![error]);
+        assert_eq!(testee.data_as_f32(), vec![0.5]);
+    }
+
+    #[test]
+    fn test_write_info() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 16000, 16).unwrap();
+        let mut original = RiffWave::from_f32(format, &[0.5]).unwrap();
+        original.info.set(InfoTag::Title, "Interview".to_string());
+        original
+            .info
+            .set(InfoTag::CreationDate, "2023-10-01".to_string());
+
+        let testee = read_wave(original.to_bytes()).unwrap();
+
+        assert_eq!(testee.info, original.info);
+        assert_eq!(testee.data_as_f32(), vec![0.5]);
+    }
+
+    #[test]
+    fn test_bext() {
+        let mut bext = vec![0; 602];
+        bext[256..262].copy_from_slice(b"Studio");
+        bext[338..342].copy_from_slice(&48000u32.to_le_bytes());
+        let mut data = b"RIFF\x00\x00\x00\x00WAVEbext\x5a\x02\x00\x00".to_vec();
+        data.extend_from_slice(&bext);
+        data.extend_from_slice(&pcm_wave_file(Channels::Mono, 48000, 16, &[0x00, 0x40])[12..]);
+
+        let testee = read_wave(data).unwrap();
+        let bext = testee.bext.as_ref().unwrap();
+
+        assert_eq!(bext.originator, "Studio");
+        assert_eq!(bext.time_reference, 48000);
+        assert_eq!(testee.data_as_f32(), vec![0.5]);
+    }
+
+    #[test]
+    fn test_bext_truncated() {
+        let mut data = b"RIFF\x00\x00\x00\x00WAVEbext\x04\x00\x00\x00abcd".to_vec();
+        data.extend_from_slice(&pcm_wave_file(Channels::Mono, 48000, 16, &[0x00, 0x40])[12..]);
+        let error = RiffWaveError::TruncatedHeader {
+            chunk_id: "bext".to_string(),
+            expected: 602,
+            actual: 4,
+        };
+
+        assert_eq!(read_wave(data.clone()).err(), Some(error.clone()));
+
+        let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+
+        assert!(testee.bext.is_none());
+        assert_eq!(testee.warnings, vec![error]);
+    }
+
+    #[test]
+    fn test_write_bext() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 48000, 16).unwrap();
+        let mut original = RiffWave::from_f32(format, &[0.5]).unwrap();
+        let mut bext = BroadcastExtension::new(&[0; 602]).unwrap();
+        bext.description = "Evening news".to_string();
+        bext.time_reference = 19 * 60 * 60 * 48000;
+        original.bext = Some(bext);
+
+        let bytes = original.to_bytes();
+        let testee = read_wave(bytes.clone()).unwrap();
+
+        assert_eq!(&bytes[12..16], b"bext");
+        assert_eq!(testee.size, bytes.len() as u64 - 8);
+        assert_eq!(testee.bext, original.bext);
+        assert_eq!(testee.duration(), Duration::from_nanos(20_833));
+    }
+
+    #[test]
+    fn test_markers() {
+        let mut data = pcm_wave_file(Channels::Mono, 8000, 16, &[0x00, 0x40, 0x00, 0x20]);
+        data.extend_from_slice(b"cue \x1c\x00\x00\x00\x01\x00\x00\x00");
+        data.extend_from_slice(b"\x05\x00\x00\x00\x00\x00\x00\x00data");
+        data.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00");
+        data.extend_from_slice(
+            b"LIST\x12\x00\x00\x00adtllabl\x06\x00\x00\x00\x05\x00\x00\x00B\x00",
+        );
+
+        let testee = read_wave(data).unwrap();
+        let markers: Vec<&Marker> = testee.markers.iter().collect();
+
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].id, 5);
+        assert_eq!(markers[0].position, 1);
+        assert_eq!(markers[0].label.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn test_markers_without_cue_point() {
+        let mut data = pcm_wave_file(Channels::Mono, 8000, 16, &[0x00, 0x40, 0x00, 0x20]);
+        data.extend_from_slice(
+            b"LIST\x12\x00\x00\x00adtllabl\x06\x00\x00\x00\x09\x00\x00\x00B\x00",
+        );
+        data.extend_from_slice(b"cue \x1c\x00\x00\x00\x01\x00\x00\x00");
+        data.extend_from_slice(b"\x05\x00\x00\x00\x00\x00\x00\x00data");
+        data.extend_from_slice(b"\x00\x00\x00\x00\x00\x00\x00\x00\x01\x00\x00\x00");
+
+        assert_eq!(
+            read_wave(data.clone()).err(),
+            Some(RiffWaveError::MissingCuePoint(9))
+        );
+
+        let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+        let markers: Vec<&Marker> = testee.markers.iter().collect();
+
+        assert_eq!(testee.warnings, vec![RiffWaveError::MissingCuePoint(9)]);
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].id, 5);
+        assert_eq!(markers[0].label, None);
+    }
+
+    #[test]
+    fn test_markers_malformed() {
+        let mut data = pcm_wave_file(Channels::Mono, 8000, 16, &[0x00, 0x40]);
+        data.extend_from_slice(b"cue \x04\x00\x00\x00\x01\x00\x00\x00");
+        data.extend_from_slice(b"LIST\x08\x00\x00\x00INFOINAM");
+
+        assert!(read_wave(data.clone()).is_err());
+
+        let testee = RiffWaveReader::with_mode(data.as_slice(), ParseMode::Lenient)
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
+
+        assert_eq!(testee.warnings.len(), 2);
+        assert!(testee.markers.is_empty());
+    }
+
+    #[test]
+    fn test_write_markers() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 8000, 16).unwrap();
+        let mut original = RiffWave::from_f32(format, &[0.0; 16]).unwrap();
+        original.markers.push(Marker {
+            id: 1,
+            position: 4,
+            length: Some(8),
+            label: Some("Speaker 1".to_string()),
+            ..Marker::default()
+        });
+
+        let testee = read_wave(original.to_bytes()).unwrap();
+
+        assert_eq!(testee.markers, original.markers);
+    }
+
+    #[test]
+    fn test_region() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Stereo, 8000, 16).unwrap();
+        let mut original = RiffWave::from_f32(format, &[0.0, 0.0, 0.5, 0.5, -0.5, -0.5]).unwrap();
+        original.bext = Some(BroadcastExtension::new(&[0; 602]).unwrap());
+
+        let testee = original.region(1, 2);
+
+        assert_eq!(testee.frames(), 1);
+        assert_eq!(testee.data_as_f32(), vec![0.5, 0.5]);
+        assert_eq!(testee.bext.unwrap().time_reference, 1);
+        assert_eq!(original.region(2, 10).frames(), 1);
+        assert_eq!(original.region(5, 10).frames(), 0);
+
+        original.bext.as_mut().unwrap().time_reference = u64::MAX;
+        assert_eq!(original.region(1, 2).bext.unwrap().time_reference, u64::MAX);
+    }
+
+    #[test]
+    fn test_region_adpcm_blocks() {
+        let data = [0x10, 0x00, 0x00, 0x00, 0x77, 0x00, 0x88, 0x08].repeat(3);
+        let original = read_wave(wave_file(ima_adpcm_format(), &data)).unwrap();
+
+        let testee = original.region(10, 12);
+
+        assert_eq!(testee.data.len(), 8);
+        assert_eq!(testee.frames(), 9);
+    }
+
+    #[test]
+    fn test_multichannel() {
+        let samples: Vec<u8> = [0i16, 8192, 16384, -16384, 0, -8192, 16384, 8192]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+
+        let testee = read_wave(pcm_wave_file(Channels::Multi(4), 16000, 16, &samples)).unwrap();
+
+        assert_eq!(testee.format.num_channels, Channels::Multi(4));
+        assert_eq!(testee.format.block_align, 8);
+        assert_eq!(testee.frames(), 2);
+        assert_eq!(
+            testee.channels_as_f32(),
+            vec![
+                vec![0.0, 0.0],
+                vec![0.25, -0.25],
+                vec![0.5, 0.5],
+                vec![-0.5, 0.25]
+            ]
+        );
+        assert_eq!(
+            testee.to_string(),
+            "0kB PCM 4 channels 16kH 128000B/s 16bit WAFF/RIFF audio"
+        );
+    }
+
+    #[test]
+    fn test_channels() {
+        assert_eq!(Channels::new(1), Channels::Mono);
+        assert_eq!(Channels::new(2), Channels::Stereo);
+        assert_eq!(Channels::new(8), Channels::Multi(8));
+        assert_eq!(Channels::Multi(8).count(), 8);
+        assert_eq!(Channels::Stereo.count(), 2);
+    }
+
+    #[test]
+    fn test_deinterleave() {
+        assert_eq!(
+            deinterleave(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0], 3),
+            vec![vec![1.0, 4.0], vec![2.0, 5.0], vec![3.0, 6.0]]
+        );
+    }
+
+    #[test]
+    fn test_split_channels() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::new(3), 16000, 16).unwrap();
+        let mut original = RiffWave::from_f32(format, &[0.5, -0.5, 0.25, 0.0, 0.125, 1.0]).unwrap();
+        original.bext = Some(BroadcastExtension::new(&[0; 602]).unwrap());
+
+        let testee = original.split_channels().unwrap();
+
+        assert_eq!(testee.len(), 3);
+        assert_eq!(testee[1].format.num_channels, Channels::Mono);
+        assert_eq!(testee[1].format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(testee[1].data_as_f32(), vec![-0.5, 0.125]);
+        assert!(testee[2].bext.is_some());
+    }
+
+    // Builds an RF64 style file where the RIFF, junk and data chunk sizes are
+    // taken from the ds64 chunk.
+    fn rf64_bytes(chunk_id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(chunk_id);
+        bytes.extend_from_slice(&[0xff; 4]);
+        bytes.extend_from_slice(b"WAVEds64");
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&0x1_0000_0000u64.to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(data.len() as u64 / 2).to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(b"junk");
+        bytes.extend_from_slice(&4u64.to_le_bytes());
+        bytes.extend_from_slice(
+            b"fmt \x10\x00\x00\x00\x01\x00\x01\x00\x80\x3e\x00\x00\x00\x7d\x00\x00\x02\x00\x10\x00",
+        );
+        bytes.extend_from_slice(b"junk");
+        bytes.extend_from_slice(&[0xff; 4]);
+        bytes.extend_from_slice(&[0x00; 4]);
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&[0xff; 4]);
+        bytes.extend_from_slice(data);
+
+        bytes
+    }
+
+    // The bytes of a wave file with the given format and data chunk, as
+    // written by RiffWave::write.
+    fn wave_file(format: RiffWaveFormat, data: &[u8]) -> Vec<u8> {
+        RiffWave {
+            size: 0,
+            format,
+            data: data.to_vec(),
+            info: RiffInfo::default(),
+            bext: None,
+            markers: Markers::default(),
+            warnings: Vec::new(),
+        }
+        .to_bytes()
+    }
+
+    fn pcm_wave_file(
+        num_channels: Channels,
+        sample_rate: u32,
+        bits_per_sample: u16,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let format = RiffWaveFormat::uncompressed(
+            AudioFormat::Pcm,
+            num_channels,
+            sample_rate,
+            bits_per_sample,
+        )
+        .unwrap();
+
+        wave_file(format, data)
+    }
+
+    // Mono IMA ADPCM in blocks of 8 bytes, which hold 9 samples each.
+    fn ima_adpcm_format() -> RiffWaveFormat {
+        RiffWaveFormat {
+            audio_format: AudioFormat::ImaAdpcm,
+            num_channels: Channels::Mono,
+            sample_rate: 8000,
+            byte_rate: 7111,
+            block_align: 8,
+            bits_per_sample: 4,
+            extensible: None,
+            adpcm: Some(RiffWaveFormatAdpcm {
+                samples_per_block: 9,
+                coefficients: Vec::new(),
+            }),
+        }
+    }
+
+    // Mono MS ADPCM with the standard coefficients in blocks of 9 bytes, which
+    // hold 6 samples each.
+    fn ms_adpcm_format() -> RiffWaveFormat {
+        RiffWaveFormat {
+            audio_format: AudioFormat::MsAdpcm,
+            num_channels: Channels::Mono,
+            sample_rate: 8000,
+            byte_rate: 12000,
+            block_align: 9,
+            bits_per_sample: 4,
+            extensible: None,
+            adpcm: Some(RiffWaveFormatAdpcm {
+                samples_per_block: 6,
+                coefficients: adpcm::MS_STANDARD_COEFFICIENTS.to_vec(),
+            }),
+        }
+    }
+
+    fn sine_wave_i16(sample_rate: u32, frequency: f32, length: usize) -> Vec<i16> {
+        (0..length)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                ((2.0 * std::f32::consts::PI * frequency * t).sin() * 30000.0) as i16
+            })
+            .collect()
+    }
+
+    fn assert_close_to_i16(actual: &[f32], expected: &[i16], tolerance: i32) {
+        for (a, e) in actual.iter().zip(expected) {
+            let a = (a * 32768.0).round() as i32;
+            assert!(
+                (a - *e as i32).abs() <= tolerance,
+                "{} is not close to {}",
+                a,
+                e
+            );
+        }
     }
 }
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+// A range of samples that contains speech.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SpeechRegion {
+    pub start: usize,
+    pub end: usize,
+}
+
+// Detects speech in mono samples by the energy of short frames relative to the
+// noise floor of the recording. Loud frames with the zero crossing rate of
+// broadband noise (hiss, wind, clicks) don't count as speech.
+pub struct VoiceActivityDetector {
+    pub sample_rate: u32,
+    // How far the energy of a frame has to be above the noise floor.
+    pub threshold_db: f32,
+    // Shorter detections are dropped.
+    pub min_speech: Duration,
+    // Shorter pauses between two detections are bridged.
+    pub min_silence: Duration,
+    // Added before and after each region to keep soft onsets and endings.
+    pub padding: Duration,
+}
+
+const FRAME_LENGTH: Duration = Duration::from_millis(30);
+
+// Frames below this level are silent, no matter how quiet the recording is.
+const MIN_SPEECH_DB: f32 = -55.0;
+
+// The noise floor is never assumed to be above this level, so recordings with
+// speech all the way through are still detected.
+const MAX_NOISE_FLOOR_DB: f32 = -40.0;
+
+// White noise crosses zero at every other sample, voiced speech at far fewer.
+const MAX_ZERO_CROSSING_RATE: f32 = 0.4;
+
+impl VoiceActivityDetector {
+    pub fn new(sample_rate: u32) -> Self {
+        VoiceActivityDetector {
+            sample_rate,
+            threshold_db: 10.0,
+            min_speech: Duration::from_millis(250),
+            min_silence: Duration::from_millis(500),
+            padding: Duration::from_millis(200),
+        }
+    }
+
+    pub fn detect(&self, samples: &[f32]) -> Vec<SpeechRegion> {
+        let frame_length = self.samples_of(FRAME_LENGTH).max(1);
+        let frames: Vec<(f32, f32)> = samples
+            .chunks(frame_length)
+            .map(|frame| (energy_db(frame), zero_crossing_rate(frame)))
+            .collect();
+
+        if frames.is_empty() {
+            return Vec::new();
+        }
+
+        let threshold = (noise_floor_db(&frames) + self.threshold_db).max(MIN_SPEECH_DB);
+        let mut regions: Vec<SpeechRegion> = Vec::new();
+
+        for (i, (energy, zero_crossing_rate)) in frames.iter().enumerate() {
+            if *energy < threshold || *zero_crossing_rate > MAX_ZERO_CROSSING_RATE {
+                continue;
+            }
+
+            let start = i * frame_length;
+            let end = (start + frame_length).min(samples.len());
+
+            match regions.last_mut() {
+                Some(last) if start - last.end < self.samples_of(self.min_silence) => {
+                    last.end = end
+                }
+                _ => regions.push(SpeechRegion { start, end }),
+            }
+        }
+
+        let padding = self.samples_of(self.padding);
+        let mut padded: Vec<SpeechRegion> = Vec::with_capacity(regions.len());
+
+        for region in regions
+            .into_iter()
+            .filter(|region| region.end - region.start >= self.samples_of(self.min_speech))
+        {
+            let start = region.start.saturating_sub(padding);
+            let end = (region.end + padding).min(samples.len());
+
+            match padded.last_mut() {
+                Some(last) if start <= last.end => last.end = end,
+                _ => padded.push(SpeechRegion { start, end }),
+            }
+        }
+
+        padded
+    }
+
+    fn samples_of(&self, duration: Duration) -> usize {
+        (duration.as_micros() * self.sample_rate as u128 / 1_000_000) as usize
+    }
+}
+
+fn energy_db(frame: &[f32]) -> f32 {
+    let mean_square = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+
+    10.0 * (mean_square + 1e-10).log10()
+}
+
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    let crossings = frame
+        .windows(2)
+        .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+        .count();
+
+    crossings as f32 / frame.len().max(2) as f32
+}
+
+// The energy of the quietest tenth of the frames.
+fn noise_floor_db(frames: &[(f32, f32)]) -> f32 {
+    let mut energies: Vec<f32> = frames.iter().map(|(energy, _)| *energy).collect();
+    energies.sort_by(f32::total_cmp);
+
+    energies[energies.len() / 10].min(MAX_NOISE_FLOOR_DB)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::vad::{SpeechRegion, VoiceActivityDetector};
+    use rand::{Rng, SeedableRng};
+
+    const SAMPLE_RATE: usize = 16000;
+
+    // A 200Hz tone between the given seconds, on top of quiet noise.
+    fn recording(seconds: f32, tones: &[(f32, f32)], noise: f32) -> Vec<f32> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let tone = match tones.iter().any(|(start, end)| t >= *start && t < *end) {
+                    true => 0.5 * (2.0 * std::f32::consts::PI * 200.0 * t).sin(),
+                    false => 0.0,
+                };
+
+                tone + rng.gen_range(-noise..=noise)
+            })
+            .collect()
+    }
+
+    fn seconds(regions: &[SpeechRegion]) -> Vec<(f32, f32)> {
+        regions
+            .iter()
+            .map(|region| {
+                (
+                    region.start as f32 / SAMPLE_RATE as f32,
+                    region.end as f32 / SAMPLE_RATE as f32,
+                )
+            })
+            .collect()
+    }
+
+    fn assert_regions(actual: &[SpeechRegion], expected: &[(f32, f32)]) {
+        let actual = seconds(actual);
+
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+
+        for ((start, end), (expected_start, expected_end)) in actual.iter().zip(expected) {
+            assert!((start - expected_start).abs() <= 0.05, "{:?}", actual);
+            assert!((end - expected_end).abs() <= 0.05, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn test_detect_speech_between_silence() {
+        let testee = VoiceActivityDetector::new(SAMPLE_RATE as u32);
+
+        let actual = testee.detect(&recording(10.0, &[(2.0, 4.0), (7.0, 8.5)], 0.001));
+
+        assert_regions(&actual, &[(1.8, 4.2), (6.8, 8.7)]);
+    }
+
+    #[test]
+    fn test_short_pauses_are_bridged() {
+        let testee = VoiceActivityDetector::new(SAMPLE_RATE as u32);
+
+        let actual = testee.detect(&recording(5.0, &[(1.0, 2.0), (2.3, 3.0)], 0.001));
+
+        assert_regions(&actual, &[(0.8, 3.2)]);
+    }
+
+    #[test]
+    fn test_short_clicks_are_dropped() {
+        let testee = VoiceActivityDetector::new(SAMPLE_RATE as u32);
+
+        let actual = testee.detect(&recording(5.0, &[(1.0, 1.1), (3.0, 4.0)], 0.001));
+
+        assert_regions(&actual, &[(2.8, 4.2)]);
+    }
+
+    #[test]
+    fn test_speech_all_the_way_through() {
+        let testee = VoiceActivityDetector::new(SAMPLE_RATE as u32);
+
+        let actual = testee.detect(&recording(3.0, &[(0.0, 3.0)], 0.0));
+
+        assert_eq!(
+            actual,
+            vec![SpeechRegion {
+                start: 0,
+                end: 48000
+            }]
+        );
+    }
+
+    #[test]
+    fn test_no_speech_in_silence_and_noise() {
+        let testee = VoiceActivityDetector::new(SAMPLE_RATE as u32);
+
+        assert_eq!(testee.detect(&[]), vec![]);
+        assert_eq!(testee.detect(&vec![0.0; SAMPLE_RATE * 3]), vec![]);
+        assert_eq!(testee.detect(&recording(3.0, &[], 0.3)), vec![]);
+    }
+}
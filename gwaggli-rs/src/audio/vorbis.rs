@@ -0,0 +1,124 @@
+use crate::audio::flac::parse_vorbis_comment;
+use crate::audio::ogg::{trim_to_granule_position, OggError, OggReader};
+use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveFormat};
+use lewton::audio::{read_audio_packet_generic, PreviousWindowRight};
+use lewton::header::{read_header_ident, read_header_setup};
+use lewton::samples::InterleavedSamples;
+use std::io::Read;
+
+// Decodes the packets of an Ogg Vorbis stream following the identification
+// header into a wave of 32 bit floats.
+pub fn decode<R: Read>(ident: &[u8], mut packets: OggReader<R>) -> Result<RiffWave, OggError> {
+    let ident = read_header_ident(ident).map_err(header_error)?;
+
+    // https://xiph.org/vorbis/doc/Vorbis_I_spec.html#x1-630004.2.3
+    let comment = packets
+        .read_packet()?
+        .filter(|packet| packet.data.starts_with(b"\x03vorbis"))
+        .ok_or(OggError::InvalidHeader("Vorbis comment header".to_string()))?;
+    let setup = packets
+        .read_packet()?
+        .ok_or(OggError::InvalidHeader("Vorbis setup header".to_string()))?;
+    let setup = read_header_setup(
+        &setup.data,
+        ident.audio_channels,
+        (ident.blocksize_0, ident.blocksize_1),
+    )
+    .map_err(header_error)?;
+
+    let num_channels = ident.audio_channels as usize;
+    let mut previous_window = PreviousWindowRight::new();
+    let mut samples = Vec::new();
+    let mut end_of_stream = None;
+
+    for packet in packets {
+        let packet = packet?;
+
+        if !packet.data.is_empty() {
+            // the first packet only fills the previous window and yields nothing
+            let decoded: InterleavedSamples<f32> =
+                read_audio_packet_generic(&ident, &setup, &packet.data, &mut previous_window)
+                    .map_err(|error| OggError::Decode(error.to_string()))?;
+
+            samples.extend_from_slice(&decoded.samples);
+        }
+
+        if packet.end_of_stream {
+            end_of_stream = packet.granule_position;
+        }
+    }
+
+    trim_to_granule_position(&mut samples, num_channels, end_of_stream);
+
+    let mut riff_wave = RiffWaveFormat::uncompressed(
+        AudioFormat::IeeeFloat,
+        Channels::new(num_channels as u16),
+        ident.audio_sample_rate,
+        32,
+    )
+    .and_then(|format| RiffWave::from_f32(format, &samples))
+    .map_err(|error| OggError::Decode(error.to_string()))?;
+    riff_wave.info = parse_vorbis_comment(&comment.data[7..]);
+
+    Ok(riff_wave)
+}
+
+fn header_error(error: lewton::header::HeaderReadError) -> OggError {
+    OggError::InvalidHeader(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::ogg::{decode, OggError};
+    use crate::audio::riff_info::InfoTag;
+    use crate::audio::riff_wave::{AudioFormat, Channels};
+    use std::f32::consts::PI;
+    use std::fs::File;
+    use std::io::Cursor;
+
+    const VORBIS: &str = "./test_data/audio/ogg/vorbis_8k_stereo.ogg";
+
+    #[test]
+    fn test_decode() {
+        let riff_wave = decode(File::open(VORBIS).unwrap()).unwrap();
+
+        assert_eq!(riff_wave.format.audio_format, AudioFormat::IeeeFloat);
+        assert_eq!(riff_wave.format.num_channels, Channels::Stereo);
+        assert_eq!(riff_wave.format.sample_rate, 8000);
+        assert_eq!(riff_wave.frames(), 8000);
+        assert_eq!(riff_wave.info.get(InfoTag::Title), Some("Tones"));
+        assert_eq!(riff_wave.info.get(InfoTag::Artist), Some("gwaggli"));
+
+        // 440 Hz at 0.5 on the left and 1000 Hz at 0.25 on the right channel
+        let channels = riff_wave.channels_as_f32();
+        for (samples, frequency, amplitude) in
+            [(&channels[0], 440.0, 0.5), (&channels[1], 1000.0, 0.25)]
+        {
+            let error = samples
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| {
+                    (sample - amplitude * (2.0 * PI * frequency * i as f32 / 8000.0).sin()).powi(2)
+                })
+                .sum::<f32>()
+                / samples.len() as f32;
+
+            assert!(
+                error.sqrt() < 0.01,
+                "error of {} Hz: {}",
+                frequency,
+                error.sqrt()
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let bytes = std::fs::read(VORBIS).unwrap();
+
+        assert!(matches!(
+            decode(Cursor::new(bytes[..bytes.len() - 10].to_vec())),
+            Err(OggError::Truncated { .. })
+        ));
+    }
+}
@@ -1,13 +1,22 @@
-use crate::audio::riff_wave::RiffWave;
+use crate::audio::convert::{Conversion, SampleFormat};
+use crate::audio::decode::{decode, open, Input};
+use crate::audio::downmix::Downmix;
+use crate::audio::filter::Filters;
+use crate::audio::inspect::AudioInfo;
+use crate::audio::preprocess::{Normalization, Preprocessing};
+use crate::audio::resample::ResampleQuality;
+use crate::audio::riff_wave::{ParseMode, RiffWave, RiffWaveReader};
 use crate::environment::fs::clear_cache;
 use crate::transcription::whisper::{WhisperConfig, WhisperModel, WhisperTranscriber};
-use crate::transcription::Transcribe;
+use crate::transcription::{Segment, Transcribe};
 use clap::{Parser, Subcommand, ValueEnum};
+use colored::Colorize;
 use std::error::Error;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{stdin, BufReader, BufWriter, Read, Write};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -23,13 +32,121 @@ struct Cli {
 enum Commands {
     /// Transcribes a an audio file into text
     Transcribe {
-        /// Path to the wave file
+        /// Path to the wave, AIFF, AU, FLAC, Opus, Vorbis, MP3 or AAC file, or - to read it from stdin
         #[arg(short, long)]
         input: PathBuf,
 
         /// Quality of the transcription; Higher takes more time to process
         #[arg(short, long, value_enum, default_value = "medium", ignore_case = true)]
         quality: Quality,
+
+        /// Prefix each segment with its time; the time of day for Broadcast Wave files
+        #[arg(short, long)]
+        timestamps: bool,
+
+        /// Transcribe each region marked by cue points separately
+        #[arg(short, long)]
+        regions: bool,
+
+        /// Transcribe each channel separately, f.e. one microphone per speaker
+        #[arg(short, long)]
+        channels: bool,
+
+        /// How to mix recordings with more than one channel down to mono
+        #[arg(short, long, value_enum, default_value = "average", ignore_case = true)]
+        downmix: Downmix,
+
+        /// The quality of the conversion of other sample rates to 16kHz
+        #[arg(long, value_enum, default_value = "medium", ignore_case = true)]
+        resample_quality: ResampleQuality,
+
+        /// Skip silence and only transcribe the regions with speech
+        #[arg(long)]
+        vad: bool,
+
+        /// Remove a constant offset from the samples
+        #[arg(long)]
+        remove_dc: bool,
+
+        /// Remove rumble below the given frequency in Hz, f.e. 80
+        #[arg(long)]
+        high_pass: Option<f32>,
+
+        /// Remove mains hum of the given frequency in Hz and its harmonics, 50 or 60
+        #[arg(long)]
+        hum: Option<f32>,
+
+        /// Reduce constant background noise learned from the quietest part of the recording
+        #[arg(long)]
+        denoise: bool,
+
+        /// Cut off the silence at the start and the end
+        #[arg(long)]
+        trim_silence: bool,
+
+        /// Adjust the level of the recording before the transcription
+        #[arg(long, value_enum, ignore_case = true)]
+        normalize: Option<Normalization>,
+
+        /// The target level of the normalization in dBFS or LUFS (default: -1 peak, -20 rms, -23 loudness)
+        #[arg(long, allow_hyphen_values = true, requires = "normalize")]
+        normalize_target: Option<f32>,
+    },
+    /// Shows the format, chunks, metadata and levels of an audio file
+    Info {
+        /// Path to the audio file, or - to read it from stdin
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Print the information as JSON
+        #[arg(short, long)]
+        json: bool,
+
+        /// Fail on the first violation of the format instead of listing it
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Converts a wave, AIFF, AU, FLAC, Opus, Vorbis, MP3 or AAC file to another sample rate, channel layout or sample format
+    Convert {
+        /// Path to the wave, AIFF, AU, FLAC, Opus, Vorbis, MP3 or AAC file, or - to read it from stdin
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Path to the converted wave file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// The sample rate in Hz, f.e. 16000 (default: as the input)
+        #[arg(short = 'r', long, value_parser = clap::value_parser!(u32).range(1..))]
+        sample_rate: Option<u32>,
+
+        /// The number of channels, f.e. 1 for mono (default: as the input)
+        #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+        channels: Option<u16>,
+
+        /// The encoding of the samples (default: as the input, pcm for ADPCM)
+        #[arg(short, long, value_enum, ignore_case = true)]
+        format: Option<SampleFormat>,
+
+        /// The bits per sample (default: as the input, or 16 pcm, 32 float, 8 alaw and mulaw)
+        #[arg(short, long)]
+        bits: Option<u16>,
+
+        /// Drop everything before this time in seconds
+        #[arg(long, value_parser = parse_seconds)]
+        start: Option<Duration>,
+
+        /// Drop everything after this time in seconds
+        #[arg(long, value_parser = parse_seconds)]
+        end: Option<Duration>,
+
+        /// How to mix recordings with more than one channel down to mono
+        #[arg(short, long, value_enum, default_value = "average", ignore_case = true)]
+        downmix: Downmix,
+
+        /// The quality of the sample rate conversion
+        #[arg(long, value_enum, default_value = "medium", ignore_case = true)]
+        resample_quality: ResampleQuality,
     },
     /// Clears local cache in the file system (f.e. downloaded models)
     ClearCache {},
@@ -42,6 +159,15 @@ enum Quality {
     High,
 }
 
+// How the transcription is split up and printed.
+#[derive(Debug, Clone, Copy, Default)]
+struct TranscribeOptions {
+    timestamps: bool,
+    regions: bool,
+    channels: bool,
+    vad: bool,
+}
+
 impl Display for Quality {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -60,9 +186,92 @@ pub async fn run() -> Result<String, Box<dyn Error>> {
     }
 
     match &cli.command {
-        Some(Commands::Transcribe { input, quality }) => {
-            cmd_transcribe(input.clone(), quality.clone()).await
+        Some(Commands::Transcribe {
+            input,
+            quality,
+            timestamps,
+            regions,
+            channels,
+            downmix,
+            resample_quality,
+            vad,
+            remove_dc,
+            high_pass,
+            hum,
+            denoise,
+            trim_silence,
+            normalize,
+            normalize_target,
+        }) => {
+            let options = TranscribeOptions {
+                timestamps: *timestamps,
+                regions: *regions,
+                channels: *channels,
+                vad: *vad,
+            };
+            let preprocessing = Preprocessing {
+                remove_dc: *remove_dc,
+                filters: Filters {
+                    high_pass: *high_pass,
+                    hum: *hum,
+                    noise_reduction: *denoise,
+                },
+                trim_silence: *trim_silence,
+                normalization: normalize.map(|normalization| {
+                    (
+                        normalization,
+                        normalize_target.unwrap_or(normalization.default_target()),
+                    )
+                }),
+            };
+
+            cmd_transcribe(
+                input.clone(),
+                quality.clone(),
+                *downmix,
+                *resample_quality,
+                preprocessing,
+                options,
+            )
+            .await
         }
+        Some(Commands::Info {
+            input,
+            json,
+            strict,
+        }) => cmd_info(
+            input.clone(),
+            *json,
+            match strict {
+                true => ParseMode::Strict,
+                false => ParseMode::Lenient,
+            },
+        ),
+        Some(Commands::Convert {
+            input,
+            output,
+            sample_rate,
+            channels,
+            format,
+            bits,
+            start,
+            end,
+            downmix,
+            resample_quality,
+        }) => cmd_convert(
+            input.clone(),
+            output.clone(),
+            Conversion {
+                sample_rate: *sample_rate,
+                channels: *channels,
+                sample_format: *format,
+                bits_per_sample: *bits,
+                start: *start,
+                end: *end,
+                downmix: *downmix,
+                resample_quality: *resample_quality,
+            },
+        ),
         Some(Commands::ClearCache {}) => cmd_clear_cache(),
         None => {
             println!("No command specified");
@@ -71,18 +280,27 @@ pub async fn run() -> Result<String, Box<dyn Error>> {
     }
 }
 
-async fn cmd_transcribe(input: PathBuf, quality: Quality) -> Result<String, Box<dyn Error>> {
+async fn cmd_transcribe(
+    input: PathBuf,
+    quality: Quality,
+    downmix: Downmix,
+    resample_quality: ResampleQuality,
+    preprocessing: Preprocessing,
+    options: TranscribeOptions,
+) -> Result<String, Box<dyn Error>> {
     println!("Transcribing file with {} quality: {:?}", quality, input);
 
-    if !input.exists() {
-        return Err(format!("File does not exist: {:?}", input).into());
-    }
+    let reader: Box<dyn Read> = if input.as_os_str() == "-" {
+        Box::new(BufReader::new(stdin().lock()))
+    } else {
+        if !input.exists() {
+            return Err(format!("File does not exist: {:?}", input).into());
+        }
 
-    let mut reader = BufReader::new(File::open(input).unwrap());
-    let mut buffer = Vec::new();
-    reader.read_to_end(&mut buffer)?;
+        Box::new(BufReader::new(File::open(input)?))
+    };
 
-    let riff_wave = RiffWave::new(buffer).unwrap();
+    let input = open(reader, ParseMode::Lenient)?;
 
     let mut transcriber = WhisperTranscriber::new(WhisperConfig {
         model: match quality {
@@ -90,14 +308,417 @@ async fn cmd_transcribe(input: PathBuf, quality: Quality) -> Result<String, Box<
             Quality::Medium => WhisperModel::Medium,
             Quality::High => WhisperModel::Large,
         },
+        downmix,
+        resample_quality,
+        vad: options.vad,
+        preprocessing,
     });
 
-    let result = transcriber.load_context().await?.transcribe(&riff_wave)?;
+    let transcriber = transcriber.load_context().await?;
 
-    Ok(result)
+    // regions and channels are cut from the whole wave
+    let streamed = !options.regions && !options.channels && downmix.is_per_frame();
+
+    match input {
+        Input::Stream(reader) if streamed => transcribe_stream(transcriber, reader, options),
+        Input::Stream(reader) => transcribe_wave(transcriber, &reader.read_wave()?, options),
+        Input::Decoded(_, riff_wave) => transcribe_wave(transcriber, &riff_wave, options),
+    }
+}
+
+// Transcribes a RIFF/WAVE file while it is read, keeping only the samples
+// prepared for whisper in memory.
+fn transcribe_stream<R: Read>(
+    transcriber: &WhisperTranscriber,
+    mut reader: RiffWaveReader<R>,
+    options: TranscribeOptions,
+) -> Result<String, Box<dyn Error>> {
+    let format = &reader.format;
+
+    println!(
+        "{} {} {} {}Hz {}bit",
+        "Streaming".green().bold(),
+        format.audio_format,
+        format.num_channels,
+        format.sample_rate,
+        format.bits_per_sample
+    );
+
+    let segments = transcriber.transcribe_stream(&mut reader)?;
+
+    // all samples are read, which leaves the metadata after the data
+    let riff_wave = reader.read_wave()?;
+
+    for warning in &riff_wave.warnings {
+        println!("{} {}", "Warning".yellow().bold(), warning);
+    }
+
+    match options.timestamps {
+        true => Ok(format_segments(&segments, &riff_wave, true)),
+        false => Ok(segments
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect()),
+    }
+}
+
+fn transcribe_wave(
+    transcriber: &impl Transcribe,
+    riff_wave: &RiffWave,
+    options: TranscribeOptions,
+) -> Result<String, Box<dyn Error>> {
+    for warning in &riff_wave.warnings {
+        println!("{} {}", "Warning".yellow().bold(), warning);
+    }
+
+    println!("{}", riff_wave);
+
+    if options.regions {
+        return transcribe_regions(transcriber, riff_wave, options);
+    }
+
+    transcribe_part(transcriber, riff_wave, riff_wave, Duration::ZERO, options)
+}
+
+// Transcribes the regions between the markers of the file one by one, each
+// headed by its label and time.
+fn transcribe_regions(
+    transcriber: &impl Transcribe,
+    riff_wave: &RiffWave,
+    options: TranscribeOptions,
+) -> Result<String, Box<dyn Error>> {
+    let regions = riff_wave.markers.regions(riff_wave.frames());
+
+    if regions.is_empty() {
+        return Err("The file has no markers to take the regions from".into());
+    }
+
+    let mut results = Vec::with_capacity(regions.len());
+
+    for region in regions {
+        let start = riff_wave.format.duration_of(region.start);
+        let end = riff_wave.format.duration_of(region.end);
+        let part = riff_wave.region(region.start, region.end);
+        // compressed regions start at the beginning of a block
+        let offset = riff_wave
+            .format
+            .duration_of(riff_wave.format.block_start(region.start));
+
+        results.push(format!(
+            "[{}] {} --> {}\n{}",
+            region.label,
+            format_time(time_of(riff_wave, start)),
+            format_time(time_of(riff_wave, end)),
+            transcribe_part(transcriber, &part, riff_wave, offset, options)?.trim()
+        ));
+    }
+
+    Ok(results.join("\n\n"))
+}
+
+// Transcribes a part of the file starting at offset, which is the whole file
+// unless regions are transcribed.
+fn transcribe_part(
+    transcriber: &impl Transcribe,
+    part: &RiffWave,
+    riff_wave: &RiffWave,
+    offset: Duration,
+    options: TranscribeOptions,
+) -> Result<String, Box<dyn Error>> {
+    let segments = if options.channels {
+        transcriber.transcribe_channels(part)?
+    } else if options.timestamps {
+        transcriber.transcribe_segments(part)?
+    } else {
+        return transcriber.transcribe(part);
+    };
+
+    let segments: Vec<Segment> = segments
+        .into_iter()
+        .map(|segment| Segment {
+            start: segment.start + offset,
+            end: segment.end + offset,
+            text: segment.text,
+        })
+        .collect();
+
+    Ok(format_segments(&segments, riff_wave, options.timestamps))
+}
+
+// One line per segment, optionally with its start and end time.
+fn format_segments(segments: &[Segment], riff_wave: &RiffWave, timestamps: bool) -> String {
+    segments
+        .iter()
+        .map(|segment| match timestamps {
+            true => format!(
+                "[{} --> {}] {}",
+                format_time(time_of(riff_wave, segment.start)),
+                format_time(time_of(riff_wave, segment.end)),
+                segment.text.trim()
+            ),
+            false => segment.text.trim().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// The position relative to the start of the file, or the time of day if the
+// file has a time reference.
+fn time_of(riff_wave: &RiffWave, position: Duration) -> Duration {
+    match &riff_wave.bext {
+        Some(bext) => bext.time_of_day(riff_wave.format.sample_rate, position),
+        None => position,
+    }
+}
+
+fn format_time(time: Duration) -> String {
+    let millis = time.as_millis();
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+fn cmd_info(input: PathBuf, json: bool, mode: ParseMode) -> Result<String, Box<dyn Error>> {
+    let info = if input.as_os_str() == "-" {
+        AudioInfo::read(BufReader::new(stdin().lock()), mode)?
+    } else {
+        if !input.exists() {
+            return Err(format!("File does not exist: {:?}", input).into());
+        }
+
+        AudioInfo::read(BufReader::new(File::open(input)?), mode)?
+    };
+
+    match json {
+        true => Ok(serde_json::to_string_pretty(&info)?),
+        false => Ok(info.to_string()),
+    }
+}
+
+fn cmd_convert(
+    input: PathBuf,
+    output: PathBuf,
+    conversion: Conversion,
+) -> Result<String, Box<dyn Error>> {
+    let riff_wave = if input.as_os_str() == "-" {
+        decode(BufReader::new(stdin().lock()), ParseMode::Lenient)?
+    } else {
+        if !input.exists() {
+            return Err(format!("File does not exist: {:?}", input).into());
+        }
+
+        decode(BufReader::new(File::open(&input)?), ParseMode::Lenient)?
+    };
+
+    for warning in &riff_wave.warnings {
+        println!("{} {}", "Warning".yellow().bold(), warning);
+    }
+
+    let converted = conversion.apply(&riff_wave)?;
+
+    let mut writer = BufWriter::new(File::create(&output)?);
+    converted.write(&mut writer)?;
+    writer.flush()?;
+
+    Ok(format!(
+        "Converted {:?} to {:?}\n{}",
+        input, output, converted
+    ))
+}
+
+// A time given in seconds, f.e. 1.5
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .map_err(|error| error.to_string())
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).map_err(|error| error.to_string()))
 }
 
 fn cmd_clear_cache() -> Result<String, Box<dyn Error>> {
     clear_cache();
     Ok("Cache cleared.".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        format_segments, format_time, parse_seconds, transcribe_part, transcribe_regions,
+        TranscribeOptions,
+    };
+    use crate::audio::bext::BroadcastExtension;
+    use crate::audio::cue::Marker;
+    use crate::audio::riff_wave::{
+        AudioFormat, Channels, RiffWave, RiffWaveFormat, RiffWaveFormatAdpcm,
+    };
+    use crate::transcription::fake::FakeTranscriber;
+    use crate::transcription::Segment;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(Duration::ZERO), "00:00:00.000");
+        assert_eq!(
+            format_time(Duration::from_millis(52_261_042)),
+            "14:31:01.042"
+        );
+    }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(parse_seconds("1.5"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_seconds("90"), Ok(Duration::from_secs(90)));
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("1:30").is_err());
+    }
+
+    #[test]
+    fn test_format_segments() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 16000, 16).unwrap();
+        let mut riff_wave = RiffWave::from_f32(format, &[]).unwrap();
+        let segments = vec![
+            Segment {
+                start: Duration::ZERO,
+                end: Duration::from_millis(1500),
+                text: " Hello".to_string(),
+            },
+            Segment {
+                start: Duration::from_millis(1500),
+                end: Duration::from_millis(3000),
+                text: " world.".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            format_segments(&segments, &riff_wave, true),
+            "[00:00:00.000 --> 00:00:01.500] Hello\n[00:00:01.500 --> 00:00:03.000] world."
+        );
+
+        let mut bext = BroadcastExtension::new(&[0; 602]).unwrap();
+        bext.time_reference = 52_200 * 16000;
+        riff_wave.bext = Some(bext);
+
+        assert_eq!(
+            format_segments(&segments[..1], &riff_wave, true),
+            "[14:30:00.000 --> 14:30:01.500] Hello"
+        );
+    }
+
+    #[test]
+    fn test_transcribe_regions() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Mono, 8000, 16).unwrap();
+        let mut riff_wave = RiffWave::from_f32(format, &[0.0; 16000]).unwrap();
+
+        let options = TranscribeOptions::default();
+
+        assert!(transcribe_regions(&FakeTranscriber {}, &riff_wave, options).is_err());
+
+        for (id, position, label) in [(1, 0, "Anna"), (2, 12000, "Ben")] {
+            riff_wave.markers.push(Marker {
+                id,
+                position,
+                label: Some(label.to_string()),
+                ..Marker::default()
+            });
+        }
+
+        assert_eq!(
+            transcribe_regions(&FakeTranscriber {}, &riff_wave, options).unwrap(),
+            "[Anna] 00:00:00.000 --> 00:00:01.500\n\
+No real transcription, but returning some data. Length=24000\n\n\
+[Ben] 00:00:01.500 --> 00:00:02.000\n\
+No real transcription, but returning some data. Length=8000"
+        );
+        assert_eq!(
+            transcribe_regions(
+                &FakeTranscriber {},
+                &riff_wave,
+                TranscribeOptions {
+                    timestamps: true,
+                    ..options
+                }
+            )
+            .unwrap()
+                .lines()
+                .nth(4)
+                .unwrap(),
+            "[00:00:01.500 --> 00:00:02.000] No real transcription, but returning some data. Length=8000"
+        );
+    }
+
+    #[test]
+    fn test_transcribe_regions_adpcm() {
+        // 32 silent IMA ADPCM blocks of 505 frames each
+        let mut riff_wave = RiffWave {
+            size: 0,
+            format: RiffWaveFormat {
+                audio_format: AudioFormat::ImaAdpcm,
+                num_channels: Channels::Mono,
+                sample_rate: 8000,
+                byte_rate: 4055,
+                block_align: 256,
+                bits_per_sample: 4,
+                extensible: None,
+                adpcm: Some(RiffWaveFormatAdpcm {
+                    samples_per_block: 505,
+                    coefficients: Vec::new(),
+                }),
+            },
+            data: vec![0; 256 * 32],
+            info: Default::default(),
+            bext: None,
+            markers: Default::default(),
+            warnings: Vec::new(),
+        };
+        riff_wave.markers.push(Marker {
+            id: 1,
+            position: 6000,
+            label: Some("Ben".to_string()),
+            ..Marker::default()
+        });
+
+        let options = TranscribeOptions {
+            timestamps: true,
+            ..TranscribeOptions::default()
+        };
+
+        // the region is read from the start of block 11 at frame 5555
+        assert_eq!(
+            transcribe_regions(&FakeTranscriber {}, &riff_wave, options).unwrap(),
+            "[Ben] 00:00:00.750 --> 00:00:02.020\n\
+[00:00:00.694 --> 00:00:02.020] No real transcription, but returning some data. Length=5376"
+        );
+    }
+
+    #[test]
+    fn test_transcribe_channels() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::new(4), 8000, 16).unwrap();
+        let riff_wave = RiffWave::from_f32(format, &[0.0; 32000]).unwrap();
+        let options = TranscribeOptions {
+            channels: true,
+            ..TranscribeOptions::default()
+        };
+
+        let result = transcribe_part(
+            &FakeTranscriber {},
+            &riff_wave,
+            &riff_wave,
+            Duration::ZERO,
+            options,
+        )
+        .unwrap();
+
+        assert_eq!(result.lines().count(), 4);
+        assert_eq!(
+            result.lines().nth(3).unwrap(),
+            "Channel 4: No real transcription, but returning some data. Length=32000"
+        );
+    }
+}
@@ -1,22 +1,32 @@
 use crate::audio::riff_wave::RiffWave;
-use crate::transcription::Transcribe;
+use crate::transcription::{Segment, Transcribe};
+use std::time::Duration;
 
 pub struct FakeTranscriber {}
 
 impl Transcribe for FakeTranscriber {
-    fn transcribe(&self, data: &RiffWave) -> Result<String, Box<dyn std::error::Error>> {
-        Ok(format!(
-            "No real transcription, but returning some data. Length={}",
-            data.data.len()
-        ))
+    fn transcribe_segments(
+        &self,
+        data: &RiffWave,
+    ) -> Result<Vec<Segment>, Box<dyn std::error::Error>> {
+        Ok(vec![Segment {
+            start: Duration::ZERO,
+            end: data.duration(),
+            text: format!(
+                "No real transcription, but returning some data. Length={}",
+                data.data.len()
+            ),
+        }])
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::audio::riff_wave::RiffWaveReader;
     use crate::transcription::Transcribe;
     use std::fs::File;
     use std::io::Read;
+    use std::time::Duration;
 
     #[test]
     fn test_transcribe() {
@@ -28,7 +38,9 @@ mod tests {
         file.read_to_end(&mut audio_data)
             .expect("Unable to read file");
 
-        let riff_wave = crate::audio::riff_wave::RiffWave::new(audio_data).unwrap();
+        let riff_wave = RiffWaveReader::new(audio_data.as_slice())
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
 
         let testee = super::FakeTranscriber {};
 
@@ -39,4 +51,22 @@ mod tests {
             "No real transcription, but returning some data. Length=264014".to_string()
         );
     }
+
+    #[test]
+    fn test_transcribe_segments() {
+        let riff_wave = RiffWaveReader::new(
+            File::open("test_data/audio/riff_wave/pcm_s16le_8k_mono.wav")
+                .expect("Unable to read file"),
+        )
+        .and_then(RiffWaveReader::read_wave)
+        .unwrap();
+
+        let result = super::FakeTranscriber {}
+            .transcribe_segments(&riff_wave)
+            .unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].start, Duration::ZERO);
+        assert_eq!(result[0].end, Duration::from_nanos(16_500_875_000));
+    }
 }
@@ -1,9 +1,100 @@
 use crate::audio::riff_wave::RiffWave;
 use std::error::Error;
+use std::time::Duration;
 
+#[cfg(test)]
 pub mod fake;
 pub mod whisper;
 
+// A part of a transcription, positioned relative to the start of the audio.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Segment {
+    pub start: Duration,
+    pub end: Duration,
+    pub text: String,
+}
+
 pub trait Transcribe {
-    fn transcribe(&self, data: &RiffWave) -> Result<String, Box<dyn Error>>;
+    fn transcribe(&self, data: &RiffWave) -> Result<String, Box<dyn Error>> {
+        Ok(self
+            .transcribe_segments(data)?
+            .iter()
+            .map(|segment| segment.text.as_str())
+            .collect())
+    }
+
+    fn transcribe_segments(&self, data: &RiffWave) -> Result<Vec<Segment>, Box<dyn Error>>;
+
+    // Transcribes each channel on its own and merges the segments of all
+    // channels by time, each labelled with the number of its channel.
+    fn transcribe_channels(&self, data: &RiffWave) -> Result<Vec<Segment>, Box<dyn Error>> {
+        let mut segments = Vec::new();
+
+        for (index, channel) in data.split_channels()?.iter().enumerate() {
+            for segment in self.transcribe_segments(channel)? {
+                segments.push(Segment {
+                    text: format!("Channel {}: {}", index + 1, segment.text.trim()),
+                    ..segment
+                });
+            }
+        }
+
+        // stable, so segments starting at the same time stay in channel order
+        segments.sort_by_key(|segment| segment.start);
+
+        Ok(segments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::riff_wave::{AudioFormat, Channels, RiffWave, RiffWaveFormat};
+    use crate::transcription::{Segment, Transcribe};
+    use std::error::Error;
+    use std::time::Duration;
+
+    // Returns one segment for each sample of the data, starting at the sample
+    // value in seconds.
+    struct SampleTranscriber {}
+
+    impl Transcribe for SampleTranscriber {
+        fn transcribe_segments(&self, data: &RiffWave) -> Result<Vec<Segment>, Box<dyn Error>> {
+            Ok(data
+                .data_as_f32()
+                .iter()
+                .map(|sample| Segment {
+                    start: Duration::from_secs_f32(*sample),
+                    end: Duration::from_secs_f32(*sample + 0.125),
+                    text: format!(" at {}", sample),
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_transcribe_channels() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::IeeeFloat, Channels::Stereo, 16000, 32)
+                .unwrap();
+        let riff_wave = RiffWave::from_f32(format, &[0.5, 0.25, 0.75, 0.5]).unwrap();
+
+        let result = SampleTranscriber {}
+            .transcribe_channels(&riff_wave)
+            .unwrap();
+
+        assert_eq!(
+            result.iter().map(|s| s.text.as_str()).collect::<Vec<_>>(),
+            vec![
+                "Channel 2: at 0.25",
+                "Channel 1: at 0.5",
+                "Channel 2: at 0.5",
+                "Channel 1: at 0.75"
+            ]
+        );
+        assert_eq!(result[0].end, Duration::from_millis(375));
+        assert_eq!(
+            SampleTranscriber {}.transcribe(&riff_wave).unwrap(),
+            " at 0.5 at 0.25 at 0.75 at 0.5"
+        );
+    }
 }
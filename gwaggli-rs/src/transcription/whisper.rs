@@ -1,15 +1,19 @@
-use crate::audio::riff_wave::Channels::Mono;
-use crate::audio::riff_wave::RiffWave;
+use crate::audio::downmix::Downmix;
+use crate::audio::preprocess::Preprocessing;
+use crate::audio::resample::{resample, ResampleQuality, Resampler};
+use crate::audio::riff_wave::{RiffWave, RiffWaveFormat, RiffWaveReader};
+use crate::audio::vad::{SpeechRegion, VoiceActivityDetector};
 use crate::environment::fs::models_dir;
 use crate::environment::http::download;
-use crate::transcription::Transcribe;
+use crate::transcription::{Segment, Transcribe};
 use clap::ValueEnum;
 use colored::Colorize;
 use std::error::Error;
 use std::fmt::Display;
 use std::fs;
+use std::io::Read;
 use std::path::PathBuf;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use url::Url;
 use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
 
@@ -20,8 +24,22 @@ pub struct WhisperTranscriber {
 
 pub struct WhisperConfig {
     pub model: WhisperModel,
+    // How recordings with more than one channel are mixed down to mono.
+    pub downmix: Downmix,
+    // How recordings with another sample rate are converted to 16kHz.
+    pub resample_quality: ResampleQuality,
+    // Only the regions with speech are transcribed, silence is skipped.
+    pub vad: bool,
+    // Clean-up of the mono 16kHz samples before the inference.
+    pub preprocessing: Preprocessing,
 }
 
+// The sample rate whisper expects.
+const SAMPLE_RATE: u32 = 16_000;
+
+// The number of frames read from a stream at once.
+const FRAMES_PER_BLOCK: usize = 1 << 16;
+
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Eq, ValueEnum)]
 pub enum WhisperModel {
@@ -95,48 +113,184 @@ impl WhisperTranscriber {
     }
 }
 
+impl WhisperTranscriber {
+    // Converts the data into the mono 16kHz samples whisper expects.
+    fn prepare_samples(&self, data: &RiffWave) -> Result<Vec<f32>, Box<dyn Error>> {
+        let num_channels = data.format.num_channels.count() as usize;
+
+        self.print_conversion(&data.format);
+
+        let samples = self.config.downmix.apply(&data.data_as_f32(), num_channels);
+
+        if data.format.sample_rate == SAMPLE_RATE {
+            return Ok(samples);
+        }
+
+        Ok(resample(
+            &samples,
+            1,
+            data.format.sample_rate,
+            SAMPLE_RATE,
+            self.config.resample_quality,
+        ))
+    }
+
+    // Converts the samples of a stream into the mono 16kHz samples whisper
+    // expects block by block, so only those are kept in memory. The downmix has
+    // to mix each frame on its own.
+    fn prepare_stream<R: Read>(
+        &self,
+        reader: &mut RiffWaveReader<R>,
+    ) -> Result<Vec<f32>, Box<dyn Error>> {
+        let format = reader.format.clone();
+        let num_channels = format.num_channels.count() as usize;
+
+        self.print_conversion(&format);
+
+        let mut resampler = Resampler::new(
+            format.sample_rate,
+            SAMPLE_RATE,
+            self.config.resample_quality,
+        )
+        .stream();
+        let mut samples = Vec::new();
+
+        for block in reader.frames(FRAMES_PER_BLOCK) {
+            let mono = self.config.downmix.apply(&block?, num_channels);
+            samples.extend(resampler.push(&mono));
+        }
+
+        samples.extend(resampler.finish());
+
+        Ok(samples)
+    }
+
+    fn print_conversion(&self, format: &RiffWaveFormat) {
+        if format.num_channels.count() > 1 {
+            println!(
+                "{} {} to mono ({})",
+                "Mixing".green().bold(),
+                format.num_channels,
+                self.config.downmix
+            );
+        }
+
+        if format.sample_rate != SAMPLE_RATE {
+            println!(
+                "{} {}Hz to {}Hz ({} quality)",
+                "Resampling".green().bold(),
+                format.sample_rate,
+                SAMPLE_RATE,
+                self.config.resample_quality
+            );
+        }
+    }
+}
+
+impl WhisperTranscriber {
+    // The regions of the samples to run the inference on.
+    fn speech_regions(&self, samples: &[f32]) -> Vec<SpeechRegion> {
+        if !self.config.vad {
+            return vec![SpeechRegion {
+                start: 0,
+                end: samples.len(),
+            }];
+        }
+
+        let regions = VoiceActivityDetector::new(SAMPLE_RATE).detect(samples);
+        let speech: usize = regions.iter().map(|region| region.end - region.start).sum();
+
+        println!(
+            "{} {} speech regions, {:.1}s of {:.1}s",
+            "Detected".green().bold(),
+            regions.len(),
+            speech as f32 / SAMPLE_RATE as f32,
+            samples.len() as f32 / SAMPLE_RATE as f32
+        );
+
+        regions
+    }
+}
+
 impl Transcribe for WhisperTranscriber {
-    fn transcribe(&self, data: &RiffWave) -> Result<String, Box<dyn Error>> {
+    fn transcribe_segments(&self, data: &RiffWave) -> Result<Vec<Segment>, Box<dyn Error>> {
+        let samples = self.prepare_samples(data)?;
+
+        self.transcribe_samples(samples)
+    }
+}
+
+impl WhisperTranscriber {
+    // Transcribes a wave while it is read from the stream, see prepare_stream.
+    pub fn transcribe_stream<R: Read>(
+        &self,
+        reader: &mut RiffWaveReader<R>,
+    ) -> Result<Vec<Segment>, Box<dyn Error>> {
+        let samples = self.prepare_stream(reader)?;
+
+        self.transcribe_samples(samples)
+    }
+
+    // Runs the inference on mono 16kHz samples.
+    fn transcribe_samples(&self, mut samples: Vec<f32>) -> Result<Vec<Segment>, Box<dyn Error>> {
         let start = Instant::now();
 
-        if data.format.sample_rate != 16_000 {
-            return Err(format!("Unsupported sample rate: {}", data.format.sample_rate,).into());
+        if !self.config.preprocessing.is_empty() {
+            println!(
+                "{} {}",
+                "Preprocessing".green().bold(),
+                self.config.preprocessing
+            );
         }
 
-        if data.format.num_channels != Mono {
-            return Err(format!(
-                "Unsupported number of channels: {}",
-                data.format.num_channels,
-            )
-            .into());
-        }
+        let trimmed = self.config.preprocessing.apply(&mut samples, SAMPLE_RATE);
 
         let context = self.context.as_ref().expect("Context not loaded");
 
         println!("{} state", "Creating".green().bold());
         let mut state = context.create_state()?;
 
-        let mut params = FullParams::new(SamplingStrategy::default());
-        params.set_n_threads(2);
-        params.set_translate(false);
-        params.set_print_progress(false);
-        params.set_print_realtime(false);
-        params.set_print_timestamps(false);
-        params.set_print_special(false);
-
-        println!("{} inference on {}", "Running".green().bold(), data);
-        state.full(params, &data.data_as_f32())?;
+        let regions = self.speech_regions(&samples);
 
-        let num_segments = state.full_n_segments()?;
-
-        let mut result = String::from("");
-
-        for i in 0..num_segments {
-            let segment = state
-                .full_get_segment_text(i)
-                .expect("Failed to get segment text");
+        println!(
+            "{} inference on {:.1}s of audio",
+            "Running".green().bold(),
+            samples.len() as f32 / SAMPLE_RATE as f32
+        );
 
-            result.push_str(&segment);
+        let mut result = Vec::new();
+
+        for region in regions {
+            let mut params = FullParams::new(SamplingStrategy::default());
+            params.set_n_threads(2);
+            params.set_translate(false);
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+            params.set_print_timestamps(false);
+            params.set_print_special(false);
+
+            state.full(params, &samples[region.start..region.end])?;
+
+            let num_segments = state.full_n_segments()?;
+            // timestamps are relative to the region of the trimmed samples
+            let offset = Duration::from_micros(
+                (trimmed + region.start) as u64 * 1_000_000 / SAMPLE_RATE as u64,
+            );
+
+            for i in 0..num_segments {
+                let segment = state
+                    .full_get_segment_text(i)
+                    .expect("Failed to get segment text");
+
+                // timestamps are in units of 10ms
+                result.push(Segment {
+                    start: offset
+                        + Duration::from_millis(state.full_get_segment_t0(i)?.max(0) as u64 * 10),
+                    end: offset
+                        + Duration::from_millis(state.full_get_segment_t1(i)?.max(0) as u64 * 10),
+                    text: segment,
+                });
+            }
         }
 
         let duration = start.elapsed();
@@ -154,6 +308,13 @@ impl Transcribe for WhisperTranscriber {
 #[cfg(test)]
 mod tests {
     use super::{WhisperConfig, WhisperModel, WhisperTranscriber};
+    use crate::audio::downmix::Downmix;
+    use crate::audio::preprocess::Preprocessing;
+    use crate::audio::resample::ResampleQuality;
+    use crate::audio::riff_wave::{
+        AudioFormat, Channels, RiffWave, RiffWaveFormat, RiffWaveReader,
+    };
+    use crate::audio::vad::SpeechRegion;
     use crate::transcription::Transcribe;
     use std::fs::File;
     use std::io::Read;
@@ -168,10 +329,16 @@ mod tests {
         file.read_to_end(&mut audio_data)
             .expect("Unable to read file");
 
-        let riff_wave = crate::audio::riff_wave::RiffWave::new(audio_data).unwrap();
+        let riff_wave = RiffWaveReader::new(audio_data.as_slice())
+            .and_then(RiffWaveReader::read_wave)
+            .unwrap();
 
         let mut testee = WhisperTranscriber::new(WhisperConfig {
             model: WhisperModel::Tiny,
+            downmix: Downmix::Average,
+            resample_quality: ResampleQuality::Medium,
+            vad: false,
+            preprocessing: Preprocessing::default(),
         });
 
         testee.load_context().await.unwrap();
@@ -215,4 +382,98 @@ mod tests {
                 .to_string()
         );
     }
+
+    #[test]
+    fn test_prepare_samples_downmix() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Stereo, 16000, 16).unwrap();
+        let riff_wave = RiffWave::from_f32(format, &[0.5, 0.0, -0.5, -0.25]).unwrap();
+        let testee = WhisperTranscriber::new(WhisperConfig {
+            model: WhisperModel::Tiny,
+            downmix: Downmix::Right,
+            resample_quality: ResampleQuality::Medium,
+            vad: false,
+            preprocessing: Preprocessing::default(),
+        });
+
+        assert_eq!(
+            testee.prepare_samples(&riff_wave).unwrap(),
+            vec![0.0, -0.25]
+        );
+    }
+
+    #[test]
+    fn test_prepare_samples_resample() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Stereo, 8000, 16).unwrap();
+        let riff_wave = RiffWave::from_f32(format, &[0.25; 1600]).unwrap();
+        let testee = WhisperTranscriber::new(WhisperConfig {
+            model: WhisperModel::Tiny,
+            downmix: Downmix::Average,
+            resample_quality: ResampleQuality::Low,
+            vad: false,
+            preprocessing: Preprocessing::default(),
+        });
+
+        let samples = testee.prepare_samples(&riff_wave).unwrap();
+
+        assert_eq!(samples.len(), 1600);
+        assert!((samples[800] - 0.25).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_prepare_stream() {
+        let format =
+            RiffWaveFormat::uncompressed(AudioFormat::Pcm, Channels::Stereo, 44100, 16).unwrap();
+        let samples: Vec<f32> = (0..200_000)
+            .map(|i| (i as f32 * 0.01).sin() * 0.5)
+            .collect();
+        let riff_wave = RiffWave::from_f32(format, &samples).unwrap();
+        let bytes = riff_wave.to_bytes();
+        let testee = WhisperTranscriber::new(WhisperConfig {
+            model: WhisperModel::Tiny,
+            downmix: Downmix::Left,
+            resample_quality: ResampleQuality::Low,
+            vad: false,
+            preprocessing: Preprocessing::default(),
+        });
+
+        let mut reader = RiffWaveReader::new(bytes.as_slice()).unwrap();
+
+        assert_eq!(
+            testee.prepare_stream(&mut reader).unwrap(),
+            testee.prepare_samples(&riff_wave).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_speech_regions() {
+        let mut samples = vec![0.0; 48000];
+        samples[16000..32000].fill(0.5);
+        let mut testee = WhisperTranscriber::new(WhisperConfig {
+            model: WhisperModel::Tiny,
+            downmix: Downmix::Average,
+            resample_quality: ResampleQuality::Medium,
+            vad: false,
+            preprocessing: Preprocessing::default(),
+        });
+
+        assert_eq!(
+            testee.speech_regions(&samples),
+            vec![SpeechRegion {
+                start: 0,
+                end: 48000
+            }]
+        );
+
+        testee.config.vad = true;
+
+        assert_eq!(
+            testee.speech_regions(&samples),
+            vec![SpeechRegion {
+                start: 12640,
+                end: 35360
+            }]
+        );
+    }
 }
@@ -32,3 +32,114 @@ fn test_cli_transcribe() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_cli_info() -> Result<(), Box<dyn Error>> {
+    let mut cmd = Command::cargo_bin("gwaggli-rs")?;
+
+    cmd.arg("info")
+        .arg("--input=./test_data/audio/riff_wave/pcm_s16le_16k_mono.wav")
+        .arg("--json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"sample_rate\": 16000"))
+        .stdout(predicate::str::contains("\"id\": \"data\""));
+
+    Ok(())
+}
+
+#[test]
+fn test_cli_convert() -> Result<(), Box<dyn Error>> {
+    let output = std::env::temp_dir().join(format!(
+        "gwaggli_test_cli_convert_{}.wav",
+        std::process::id()
+    ));
+    let mut cmd = Command::cargo_bin("gwaggli-rs")?;
+
+    cmd.arg("convert")
+        .arg("--input=./test_data/audio/riff_wave/pcm_s16le_8k_mono.wav")
+        .arg(format!("--output={}", output.display()))
+        .arg("--sample-rate=16000")
+        .arg("--channels=2")
+        .arg("--format=float")
+        .arg("--end=1.5");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Converted"));
+
+    let mut cmd = Command::cargo_bin("gwaggli-rs")?;
+
+    cmd.arg("info")
+        .arg(format!("--input={}", output.display()))
+        .arg("--json");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("\"audio_format\": \"IEEE float\""))
+        .stdout(predicate::str::contains("\"channels\": 2"))
+        .stdout(predicate::str::contains("\"sample_rate\": 16000"))
+        .stdout(predicate::str::contains("\"frames\": 24000"));
+
+    std::fs::remove_file(output)?;
+
+    Ok(())
+}
+
+// Every decodable fixture with its number of frames at 16kHz.
+const FIXTURES: [(&str, u64); 15] = [
+    ("aiff/f32be_8k_mono.aifc", 16000),
+    ("aiff/mulaw_8k_mono.aifc", 16000),
+    ("aiff/pcm_s16be_8k_mono.aiff", 16000),
+    ("aiff/pcm_s16le_8k_mono.aifc", 16000),
+    ("aiff/pcm_s24be_8k_stereo.aiff", 8000),
+    ("au/alaw_8k_mono.au", 16000),
+    ("au/pcm_s16be_8k_mono.au", 16000),
+    ("flac/pcm_s16le_8k_mono.flac", 264014),
+    ("flac/pcm_s24le_8k_stereo.flac", 40000),
+    ("mpeg/aac_48k_mono.aac", 8534),
+    ("mpeg/aac_48k_stereo.m4a", 16384),
+    ("mpeg/mp3_48k_stereo.mp3", 16000),
+    ("ogg/opus_16k_mono.opus", 264014),
+    ("ogg/opus_48k_stereo.opus", 16000),
+    ("ogg/vorbis_8k_stereo.ogg", 16000),
+];
+
+#[test]
+fn test_cli_convert_fixtures() -> Result<(), Box<dyn Error>> {
+    for (fixture, frames) in FIXTURES {
+        // unique per process, so parallel runs do not share files
+        let output = std::env::temp_dir().join(format!(
+            "gwaggli_test_cli_convert_{}_{}.wav",
+            std::process::id(),
+            fixture.replace(['/', '.'], "_")
+        ));
+        let mut cmd = Command::cargo_bin("gwaggli-rs")?;
+
+        cmd.arg("convert")
+            .arg(format!("--input=./test_data/audio/{}", fixture))
+            .arg(format!("--output={}", output.display()))
+            .arg("--sample-rate=16000")
+            .arg("--channels=1")
+            .arg("--format=pcm");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Converted"));
+
+        let mut cmd = Command::cargo_bin("gwaggli-rs")?;
+
+        cmd.arg("info")
+            .arg(format!("--input={}", output.display()))
+            .arg("--json");
+
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(format!("\"frames\": {},", frames)));
+
+        std::fs::remove_file(output)?;
+    }
+
+    Ok(())
+}